@@ -0,0 +1,298 @@
+use crate::Pos;
+use std::fmt;
+
+// What can be attached to a move: comments, NAGs (numeric annotation
+// glyphs) and the square highlights and arrows PGN viewers draw from the
+// [%csl] and [%cal] commands in comments
+
+// The colours of highlights and arrows, written G, R, Y and B in PGN
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum MarkColor {
+    Green,
+    Red,
+    Yellow,
+    Blue,
+}
+
+impl MarkColor {
+    pub fn letter(&self) -> char {
+        match self {
+            MarkColor::Green => 'G',
+            MarkColor::Red => 'R',
+            MarkColor::Yellow => 'Y',
+            MarkColor::Blue => 'B',
+        }
+    }
+
+    pub fn from_letter(letter: char) -> Option<MarkColor> {
+        match letter {
+            'G' => Some(MarkColor::Green),
+            'R' => Some(MarkColor::Red),
+            'Y' => Some(MarkColor::Yellow),
+            'B' => Some(MarkColor::Blue),
+            _ => None,
+        }
+    }
+}
+
+// A highlighted square, e.g. "Gd4" in [%csl Gd4]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Highlight {
+    pub color: MarkColor,
+    pub square: Pos,
+}
+
+// An arrow between two squares, e.g. "Re2e4" in [%cal Re2e4]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Arrow {
+    pub color: MarkColor,
+    pub from: Pos,
+    pub to: Pos,
+}
+
+// Returned by Annotation::add_comment for text with a '}', which would end
+// the comment early in PGN and has no escape there
+// Holds the rejected text
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommentError(pub String);
+
+impl fmt::Display for CommentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "comment \"{}\" contains '}}'", self.0)
+    }
+}
+
+impl std::error::Error for CommentError {}
+
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct Annotation {
+    // Without '}', see add_comment
+    pub comments: Vec<String>,
+    // e.g. 1 for "!", 2 for "?" and 3 for "!!", see nag_glyph
+    pub nags: Vec<u8>,
+    pub highlights: Vec<Highlight>,
+    pub arrows: Vec<Arrow>,
+}
+
+impl Annotation {
+    pub fn is_empty(&self) -> bool {
+        self.comments.is_empty()
+            && self.nags.is_empty()
+            && self.highlights.is_empty()
+            && self.arrows.is_empty()
+    }
+
+    // Adds the text of a PGN comment, taking the highlights and arrows of any
+    // [%csl] and [%cal] commands out of it
+    // Other commands such as [%clk] are kept in the text
+    // Text with a '}' is rejected and nothing is added, as PGN cannot write it
+    pub fn add_comment(&mut self, text: &str) -> Result<(), CommentError> {
+        if text.contains('}') {
+            return Err(CommentError(text.to_string()));
+        }
+        let mut rest = String::new();
+        let mut text = text;
+        while let Some(start) = text.find("[%") {
+            let Some(len) = text[start..].find(']') else {
+                break;
+            };
+            let command = &text[start + 2..start + len];
+            rest.push_str(&text[..start]);
+            match command.split_once(char::is_whitespace) {
+                Some(("csl", items)) => self
+                    .highlights
+                    .extend(items_of(items).filter_map(highlight)),
+                Some(("cal", items)) => self.arrows.extend(items_of(items).filter_map(arrow)),
+                _ => rest.push_str(&text[start..=start + len]),
+            }
+            text = &text[start + len + 1..];
+        }
+        rest.push_str(text);
+
+        let rest = rest.split_whitespace().collect::<Vec<&str>>().join(" ");
+        if !rest.is_empty() {
+            self.comments.push(rest);
+        }
+        Ok(())
+    }
+
+    // The annotation as PGN movetext: the NAGs, then the comments with the
+    // highlights and arrows in front of the first one
+    pub(crate) fn pgn_tokens(&self) -> Vec<String> {
+        let mut tokens: Vec<String> = self.nags.iter().map(|nag| format!("${}", nag)).collect();
+
+        let mut marks = String::new();
+        if !self.highlights.is_empty() {
+            let items: Vec<String> = self
+                .highlights
+                .iter()
+                .map(|h| format!("{}{}", h.color.letter(), h.square))
+                .collect();
+            marks.push_str(&format!("[%csl {}]", items.join(",")));
+        }
+        if !self.arrows.is_empty() {
+            let items: Vec<String> = self
+                .arrows
+                .iter()
+                .map(|a| format!("{}{}{}", a.color.letter(), a.from, a.to))
+                .collect();
+            marks.push_str(&format!("[%cal {}]", items.join(",")));
+        }
+
+        let mut comments = self.comments.iter();
+        match (marks.is_empty(), comments.next()) {
+            (true, Some(first)) => tokens.push(comment(first)),
+            (false, Some(first)) => tokens.push(comment(&format!("{} {}", marks, first))),
+            (false, None) => tokens.push(comment(&marks)),
+            (true, None) => (),
+        }
+        tokens.extend(comments.map(|c| comment(c)));
+        tokens
+    }
+}
+
+// The glyph a NAG stands for, for the six move assessments
+pub fn nag_glyph(nag: u8) -> Option<&'static str> {
+    match nag {
+        1 => Some("!"),
+        2 => Some("?"),
+        3 => Some("!!"),
+        4 => Some("??"),
+        5 => Some("!?"),
+        6 => Some("?!"),
+        _ => None,
+    }
+}
+
+// The NAG for a glyph such as "!?", as written after a move
+pub fn nag_from_glyph(glyph: &str) -> Option<u8> {
+    (1..=6).find(|&nag| nag_glyph(nag) == Some(glyph))
+}
+
+// A '}' pushed straight into comments is written as ')' rather than end
+// the comment early, add_comment does not let one in
+fn comment(text: &str) -> String {
+    format!("{{{}}}", text.replace('}', ")"))
+}
+
+fn items_of(items: &str) -> impl Iterator<Item = &str> {
+    items
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+}
+
+pub(crate) fn highlight(item: &str) -> Option<Highlight> {
+    let color = MarkColor::from_letter(item.chars().next()?)?;
+    let square = item.get(1..)?.parse::<Pos>().ok()?;
+    Some(Highlight { color, square })
+}
+
+pub(crate) fn arrow(item: &str) -> Option<Arrow> {
+    let color = MarkColor::from_letter(item.chars().next()?)?;
+    let from = item.get(1..3)?.parse::<Pos>().ok()?;
+    let to = item.get(3..)?.parse::<Pos>().ok()?;
+    Some(Arrow { color, from, to })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn::{parse_game, to_pgn, PgnError};
+    use crate::san::parse_san;
+    use crate::Game;
+
+    fn sq(name: &str) -> Pos {
+        name.parse().unwrap()
+    }
+
+    fn annotated() -> Annotation {
+        let mut annotation = Annotation::default();
+        annotation
+            .add_comment("Central [%csl Gd4,Re5] control [%cal Ge2e4] [%clk 0:05:00]")
+            .unwrap();
+        annotation.add_comment("Second").unwrap();
+        annotation.nags.push(1);
+        annotation
+    }
+
+    #[test]
+    fn add_comment_takes_out_marks() {
+        let annotation = annotated();
+        assert_eq!(
+            annotation.highlights,
+            vec![
+                Highlight {
+                    color: MarkColor::Green,
+                    square: sq("d4"),
+                },
+                Highlight {
+                    color: MarkColor::Red,
+                    square: sq("e5"),
+                },
+            ]
+        );
+        assert_eq!(
+            annotation.arrows,
+            vec![Arrow {
+                color: MarkColor::Green,
+                from: sq("e2"),
+                to: sq("e4"),
+            }]
+        );
+        assert_eq!(
+            annotation.comments,
+            vec!["Central control [%clk 0:05:00]", "Second"]
+        );
+    }
+
+    #[test]
+    fn comments_are_reassembled() {
+        assert_eq!(
+            annotated().pgn_tokens(),
+            vec![
+                "$1",
+                "{[%csl Gd4,Re5][%cal Ge2e4] Central control [%clk 0:05:00]}",
+                "{Second}",
+            ]
+        );
+    }
+
+    #[test]
+    fn closing_brace_is_rejected() {
+        let mut annotation = Annotation::default();
+        assert_eq!(
+            annotation.add_comment("a } b"),
+            Err(CommentError("a } b".to_string()))
+        );
+        assert!(annotation.is_empty());
+        assert_eq!(
+            parse_game("1. e4 ; ends } here\n*").unwrap_err(),
+            PgnError::Comment(CommentError("ends } here".to_string()))
+        );
+    }
+
+    #[test]
+    fn pgn_round_trip() {
+        let mut game = Game::new();
+        let mv = parse_san(&game, "e4").unwrap();
+        game = game.play_move(mv);
+        game.annotations[0] = annotated();
+
+        let (_, read) = parse_game(&to_pgn(&game, &[])).unwrap();
+        assert_eq!(read.annotations, game.annotations);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let annotation = annotated();
+        let json = serde_json::to_string(&annotation).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Annotation>(&json).unwrap(),
+            annotation
+        );
+        let brace = r#"{"comments": ["a } b"], "nags": [], "highlights": [], "arrows": []}"#;
+        assert!(serde_json::from_str::<Annotation>(brace).is_err());
+    }
+}
@@ -0,0 +1,207 @@
+use crate::{
+    Game, PieceType, Pos, Square, BISHOP_DIRECTIONS, KING_OFFSETS, KNIGHT_OFFSETS, ROOK_DIRECTIONS,
+};
+
+// Attack queries worked out from the pieces on the board, without the check
+// boards, so they are right for any position and see through nothing: a
+// slider attacks up to and including the first piece in its way
+
+impl Game {
+    // Returns true if a piece of the given colour attacks sq
+    pub fn is_square_attacked(&self, sq: Pos, by_white: bool) -> bool {
+        is_attacked(&self.boards.board, sq, by_white)
+    }
+
+    // The squares of all pieces, of either colour, that attack sq
+    pub fn attackers_of(&self, sq: Pos) -> Vec<Pos> {
+        let mut attackers_ = attackers(&self.boards.board, sq, true);
+        attackers_.extend(attackers(&self.boards.board, sq, false));
+        attackers_
+    }
+
+    // Returns true if the king of the player to move is attacked
+    pub fn is_in_check(&self) -> bool {
+        !self.checkers().is_empty()
+    }
+
+    // The squares of the pieces giving check to the player to move
+    pub fn checkers(&self) -> Vec<Pos> {
+        let white = self.side_to_move();
+        attackers(&self.boards.board, self.king_pos(white), !white)
+    }
+
+    // The squares of the pieces of the player to move that are pinned to
+    // their own king and may only move along the pin
+    pub fn pinned_pieces(&self) -> Vec<Pos> {
+        let white = self.side_to_move();
+        pinned(&self.boards.board, self.king_pos(white), white)
+    }
+
+    pub(crate) fn king_pos(&self, white: bool) -> Pos {
+        match white {
+            true => self.w_king_pos,
+            false => self.b_king_pos,
+        }
+    }
+}
+
+fn piece_at(board: &[Vec<Square>], pos: Pos) -> Option<(PieceType, bool)> {
+    let square = &board[pos.rank().index()][pos.file().index()];
+    match square.occupied {
+        true => Some((square.piece.piece_type, square.piece.white)),
+        false => None,
+    }
+}
+
+// The first occupied square from sq in the direction (file_step, rank_step)
+fn first_piece(board: &[Vec<Square>], sq: Pos, (file_step, rank_step): (i8, i8)) -> Option<Pos> {
+    let mut pos = sq;
+    loop {
+        pos = pos.offset(file_step, rank_step)?;
+        if piece_at(board, pos).is_some() {
+            return Some(pos);
+        }
+    }
+}
+
+// Whether piece_type slides along the rook or the bishop directions
+fn slides(piece_type: PieceType, straight: bool) -> bool {
+    match straight {
+        true => matches!(piece_type, PieceType::Rook | PieceType::Queen),
+        false => matches!(piece_type, PieceType::Bishop | PieceType::Queen),
+    }
+}
+
+// The squares of the pieces of the given colour that attack sq on board
+pub(crate) fn attackers(board: &[Vec<Square>], sq: Pos, by_white: bool) -> Vec<Pos> {
+    let mut found: Vec<Pos> = Vec::new();
+    let mut steppers = |offsets: &[(i8, i8)], piece_type: PieceType| {
+        for &(file_offset, rank_offset) in offsets {
+            if let Some(pos) = sq.offset(file_offset, rank_offset) {
+                if piece_at(board, pos) == Some((piece_type, by_white)) {
+                    found.push(pos);
+                }
+            }
+        }
+    };
+    steppers(&KNIGHT_OFFSETS, PieceType::Knight);
+    steppers(&KING_OFFSETS, PieceType::King);
+    // A pawn attacks sq from one rank behind it, seen from the pawn's side
+    let behind = if by_white { -1 } else { 1 };
+    steppers(&[(-1, behind), (1, behind)], PieceType::Pawn);
+
+    for (directions, straight) in [(&ROOK_DIRECTIONS, true), (&BISHOP_DIRECTIONS, false)] {
+        for &direction in directions {
+            if let Some(pos) = first_piece(board, sq, direction) {
+                match piece_at(board, pos) {
+                    Some((piece_type, white))
+                        if white == by_white && slides(piece_type, straight) =>
+                    {
+                        found.push(pos)
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+    found
+}
+
+pub(crate) fn is_attacked(board: &[Vec<Square>], sq: Pos, by_white: bool) -> bool {
+    !attackers(board, sq, by_white).is_empty()
+}
+
+// The pieces of the given colour pinned to their king on king
+pub(crate) fn pinned(board: &[Vec<Square>], king: Pos, white: bool) -> Vec<Pos> {
+    let mut found: Vec<Pos> = Vec::new();
+    for (directions, straight) in [(&ROOK_DIRECTIONS, true), (&BISHOP_DIRECTIONS, false)] {
+        for &direction in directions {
+            let Some(first) = first_piece(board, king, direction) else {
+                continue;
+            };
+            if piece_at(board, first).map(|(_, w)| w) != Some(white) {
+                continue;
+            }
+            let Some(second) = first_piece(board, first, direction) else {
+                continue;
+            };
+            match piece_at(board, second) {
+                Some((piece_type, w)) if w != white && slides(piece_type, straight) => {
+                    found.push(first)
+                }
+                _ => (),
+            }
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::san::parse_san;
+
+    fn sorted(squares: Vec<Pos>) -> Vec<String> {
+        let mut names: Vec<String> = squares.iter().map(|pos| pos.to_string()).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn absolute_pin() {
+        // The knight on e2 is pinned by the rook, the bishop on f2 is not
+        // as no bishop stands behind it
+        let game = Game::from_fen("4k3/8/8/7b/4r3/8/4NB2/4K3 w - - 0 1").unwrap();
+        assert_eq!(sorted(game.pinned_pieces()), vec!["e2"]);
+        // Two white pieces between the king and the slider pin neither
+        let game = Game::from_fen("4k3/8/8/8/4r3/4N3/4N3/4K3 w - - 0 1").unwrap();
+        assert!(game.pinned_pieces().is_empty());
+        let game = Game::from_fen("4k3/8/8/8/8/6b1/4NB2/4K3 w - - 0 1").unwrap();
+        assert_eq!(sorted(game.pinned_pieces()), vec!["f2"]);
+        assert!(!game.is_in_check());
+    }
+
+    #[test]
+    fn double_check() {
+        let game = Game::from_fen("4k3/8/3N4/8/8/8/8/4R1K1 b - - 0 1").unwrap();
+        assert!(game.is_in_check());
+        assert_eq!(sorted(game.checkers()), vec!["d6", "e1"]);
+        // Only the king can get out of a double check
+        assert!(game
+            .legal_moves()
+            .iter()
+            .all(|mv| mv.from == game.b_king_pos));
+    }
+
+    #[test]
+    fn discovered_attack() {
+        let game = Game::from_fen("4k3/8/8/8/8/8/4N3/4R1K1 w - - 0 1").unwrap();
+        assert!(!game.is_square_attacked(game.b_king_pos, true));
+        assert_eq!(
+            sorted(game.attackers_of("e5".parse().unwrap())),
+            Vec::<String>::new()
+        );
+
+        let mv = parse_san(&game, "Nc3").unwrap();
+        let game = game.play_move(mv);
+        assert!(game.is_square_attacked(game.b_king_pos, true));
+        assert_eq!(sorted(game.checkers()), vec!["e1"]);
+        assert_eq!(sorted(game.attackers_of("e5".parse().unwrap())), vec!["e1"]);
+    }
+
+    #[test]
+    fn pawns_attack_diagonally() {
+        let game = Game::from_fen("4k3/8/8/3p4/8/4P3/8/4K3 w - - 0 1").unwrap();
+        let sq = |name: &str| name.parse::<Pos>().unwrap();
+        assert!(game.is_square_attacked(sq("d4"), true));
+        assert!(game.is_square_attacked(sq("f4"), true));
+        assert!(!game.is_square_attacked(sq("e4"), true));
+        // Black pawns attack towards the first rank
+        assert!(game.is_square_attacked(sq("e4"), false));
+        assert!(game.is_square_attacked(sq("c4"), false));
+        assert!(!game.is_square_attacked(sq("d4"), false));
+        assert!(!game.is_square_attacked(sq("e6"), false));
+        assert_eq!(sorted(game.attackers_of(sq("d4"))), vec!["e3"]);
+        assert_eq!(sorted(game.attackers_of(sq("e4"))), vec!["d5"]);
+    }
+}
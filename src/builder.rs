@@ -0,0 +1,250 @@
+use crate::fen::{game_from_board, new_square, FenError};
+use crate::{CastlingRights, Game, PieceType, Pos, Rank, Square};
+
+// Sets up a position piece by piece, e.g. for a position editor
+// Starts from an empty board with white to move and no castling rights
+// build checks the position the same way Game::from_fen does
+#[derive(Clone, Debug)]
+pub struct BoardBuilder {
+    board: Vec<Vec<Square>>,
+    white_to_move: bool,
+    castling: CastlingRights,
+    en_passant: Option<Pos>,
+    halfmove_clock: u32,
+    fullmove_number: u32,
+}
+
+impl Default for BoardBuilder {
+    fn default() -> BoardBuilder {
+        BoardBuilder::new()
+    }
+}
+
+impl BoardBuilder {
+    pub fn new() -> BoardBuilder {
+        BoardBuilder {
+            board: Rank::all()
+                .map(|rank| {
+                    Pos::rank_squares(rank)
+                        .map(|pos| new_square(pos, PieceType::Unoccupied, true))
+                        .collect()
+                })
+                .collect(),
+            white_to_move: true,
+            castling: CastlingRights::none(),
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+        }
+    }
+
+    // Starts from the current position of game, to edit it
+    pub fn from_game(game: &Game) -> BoardBuilder {
+        BoardBuilder {
+            board: game.get_board(),
+            white_to_move: game.side_to_move(),
+            castling: game.castling,
+            en_passant: game.en_passant,
+            halfmove_clock: game.halfmove_clock,
+            fullmove_number: game.fullmove_number,
+        }
+    }
+
+    // Puts a piece on pos, replacing whatever stood there
+    // PieceType::Unoccupied clears the square
+    pub fn put(mut self, pos: Pos, piece_type: PieceType, white: bool) -> BoardBuilder {
+        self.board[pos.rank().index()][pos.file().index()] = new_square(pos, piece_type, white);
+        self
+    }
+
+    pub fn clear(self, pos: Pos) -> BoardBuilder {
+        self.put(pos, PieceType::Unoccupied, true)
+    }
+
+    // The piece type and colour on pos, None if the square is empty
+    pub fn piece(&self, pos: Pos) -> Option<(PieceType, bool)> {
+        let square = &self.board[pos.rank().index()][pos.file().index()];
+        match square.occupied {
+            true => Some((square.piece.piece_type, square.piece.white)),
+            false => None,
+        }
+    }
+
+    pub fn side_to_move(mut self, white: bool) -> BoardBuilder {
+        self.white_to_move = white;
+        self
+    }
+
+    pub fn castling(mut self, castling: CastlingRights) -> BoardBuilder {
+        self.castling = castling;
+        self
+    }
+
+    // The square a pawn of the player not to move just skipped over
+    pub fn en_passant(mut self, en_passant: Option<Pos>) -> BoardBuilder {
+        self.en_passant = en_passant;
+        self
+    }
+
+    // The plies since the last capture or pawn move and the move number, as in FEN
+    pub fn clocks(mut self, halfmove_clock: u32, fullmove_number: u32) -> BoardBuilder {
+        self.halfmove_clock = halfmove_clock;
+        self.fullmove_number = fullmove_number.max(1);
+        self
+    }
+
+    // Returns a game from the position with all moves generated
+    // Fails if either player does not have exactly one king, a pawn stands on
+    // a back rank, the player not to move is in check, a castling right has
+    // no king or rook to go with it, or the en passant square is not right
+    // behind a pawn that could just have moved two squares
+    pub fn build(&self) -> Result<Game, FenError> {
+        game_from_board(
+            self.board.clone(),
+            self.white_to_move,
+            self.halfmove_clock,
+            self.fullmove_number,
+            self.castling,
+            self.en_passant,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::File;
+
+    fn sq(name: &str) -> Pos {
+        name.parse().unwrap()
+    }
+
+    // White king on e1 and black king on e8
+    fn kings() -> BoardBuilder {
+        BoardBuilder::new()
+            .put(sq("e1"), PieceType::King, true)
+            .put(sq("e8"), PieceType::King, false)
+    }
+
+    #[test]
+    fn builds_position() {
+        let game = kings()
+            .put(sq("h1"), PieceType::Rook, true)
+            .put(sq("d5"), PieceType::Pawn, false)
+            .put(sq("e5"), PieceType::Pawn, true)
+            .castling({
+                let mut castling = CastlingRights::none();
+                castling.set(true, true, Some(File::H));
+                castling
+            })
+            .en_passant(Some(sq("d6")))
+            .clocks(0, 12)
+            .build()
+            .unwrap();
+        assert_eq!(game.to_fen(), "4k3/8/8/3pP3/8/8/8/4K2R w K d6 0 12");
+        assert_eq!(
+            BoardBuilder::from_game(&game).build().unwrap().to_fen(),
+            game.to_fen()
+        );
+    }
+
+    #[test]
+    fn king_count() {
+        assert_eq!(
+            BoardBuilder::new().build().unwrap_err(),
+            FenError::KingCount { white: 0, black: 0 }
+        );
+        assert_eq!(
+            kings().clear(sq("e8")).build().unwrap_err(),
+            FenError::KingCount { white: 1, black: 0 }
+        );
+        assert_eq!(
+            kings()
+                .put(sq("a1"), PieceType::King, true)
+                .build()
+                .unwrap_err(),
+            FenError::KingCount { white: 2, black: 1 }
+        );
+    }
+
+    #[test]
+    fn pawn_on_back_rank() {
+        for (square, white) in [("a1", true), ("h8", false), ("c8", true), ("d1", false)] {
+            assert_eq!(
+                kings()
+                    .put(sq(square), PieceType::Pawn, white)
+                    .build()
+                    .unwrap_err(),
+                FenError::PawnOnBackRank(sq(square))
+            );
+        }
+    }
+
+    #[test]
+    fn side_not_to_move_in_check() {
+        let builder = kings().put(sq("e4"), PieceType::Rook, true);
+        assert_eq!(builder.build().unwrap_err(), FenError::OpponentInCheck);
+        assert!(builder.side_to_move(false).build().unwrap().is_in_check());
+    }
+
+    #[test]
+    fn castling_needs_king_and_rook() {
+        let white_short = || {
+            let mut castling = CastlingRights::none();
+            castling.set(true, true, Some(File::H));
+            castling
+        };
+        assert_eq!(
+            kings().castling(white_short()).build().unwrap_err(),
+            FenError::Castling("H".to_string())
+        );
+        assert_eq!(
+            kings()
+                .clear(sq("e1"))
+                .put(sq("e2"), PieceType::King, true)
+                .put(sq("h1"), PieceType::Rook, true)
+                .castling(white_short())
+                .build()
+                .unwrap_err(),
+            FenError::Castling("H".to_string())
+        );
+        // The rook must be on the side it castles to
+        let mut black_long = CastlingRights::none();
+        black_long.set(false, false, Some(File::H));
+        assert_eq!(
+            kings()
+                .put(sq("h8"), PieceType::Rook, false)
+                .castling(black_long)
+                .build()
+                .unwrap_err(),
+            FenError::Castling("h".to_string())
+        );
+    }
+
+    #[test]
+    fn bad_en_passant_square() {
+        let builder = kings().put(sq("d5"), PieceType::Pawn, false);
+        assert!(builder.clone().en_passant(Some(sq("d6"))).build().is_ok());
+        // Not behind the pawn, on the wrong rank, or for the wrong side
+        for (target, white_to_move) in [("c6", true), ("d4", true), ("d6", false)] {
+            assert_eq!(
+                builder
+                    .clone()
+                    .side_to_move(white_to_move)
+                    .en_passant(Some(sq(target)))
+                    .build()
+                    .unwrap_err(),
+                FenError::EnPassant(target.to_string())
+            );
+        }
+        // The square the pawn skipped is taken
+        assert_eq!(
+            builder
+                .put(sq("d7"), PieceType::Knight, false)
+                .en_passant(Some(sq("d6")))
+                .build()
+                .unwrap_err(),
+            FenError::EnPassant("d6".to_string())
+        );
+    }
+}
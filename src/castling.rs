@@ -0,0 +1,191 @@
+use crate::fen::new_square;
+use crate::{attacks, square_to_unoccupied, File, Game, Move, PieceType, Pos, Rank};
+
+// Castling follows the Chess960 rules, which include standard chess: the king
+// and the rook may start on any files of the back rank, and after castling
+// the king stands on the g (c) file and the rook on the f (d) file.
+//
+// A castling move is written as the king taking its own rook, so that its
+// from and to squares always tell it apart from a king move. e1g1 in standard
+// chess is accepted by find_move as well.
+
+// The castling rights of both players, as the file of the rook each one may
+// still castle with on either wing
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct CastlingRights {
+    // [white, black][king side, queen side]
+    rooks: [[Option<File>; 2]; 2],
+}
+
+impl CastlingRights {
+    // No rights at all
+    pub fn none() -> CastlingRights {
+        CastlingRights::default()
+    }
+
+    // The rights of the standard starting position, with the a and h rooks
+    pub fn standard() -> CastlingRights {
+        CastlingRights {
+            rooks: [[Some(File::H), Some(File::A)]; 2],
+        }
+    }
+
+    // The file of the rook white (or black) may castle with on the king side
+    // (or queen side), None without the right
+    pub fn rook(&self, white: bool, king_side: bool) -> Option<File> {
+        self.rooks[side(white)][wing(king_side)]
+    }
+
+    pub fn set(&mut self, white: bool, king_side: bool, rook: Option<File>) {
+        self.rooks[side(white)][wing(king_side)] = rook;
+    }
+
+    // Whether white (or black) may castle at all
+    pub fn any(&self, white: bool) -> bool {
+        self.rooks[side(white)].iter().any(Option::is_some)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.any(true) && !self.any(false)
+    }
+
+    // Whether the rights only use rooks on the a and h files
+    pub(crate) fn is_standard(&self) -> bool {
+        self.rooks
+            .iter()
+            .all(|rooks| matches!(rooks, [None | Some(File::H), None | Some(File::A)]))
+    }
+}
+
+fn side(white: bool) -> usize {
+    match white {
+        true => 0,
+        false => 1,
+    }
+}
+
+fn wing(king_side: bool) -> usize {
+    match king_side {
+        true => 0,
+        false => 1,
+    }
+}
+
+// Where the king and the rook of a castling move end up, given the squares
+// they start on
+pub(crate) fn castle_targets(king: Pos, rook: Pos) -> (Pos, Pos) {
+    let (king_file, rook_file) = match rook.file() > king.file() {
+        true => (File::G, File::F),
+        false => (File::C, File::D),
+    };
+    (
+        Pos::new(king_file, king.rank()),
+        Pos::new(rook_file, king.rank()),
+    )
+}
+
+// The squares from a to b on the same rank, both included
+fn span(a: Pos, b: Pos) -> impl Iterator<Item = Pos> {
+    let (low, high) = match a.file() <= b.file() {
+        true => (a.file().index(), b.file().index()),
+        false => (b.file().index(), a.file().index()),
+    };
+    let rank = a.rank();
+    (low..=high).map(move |i| Pos::new(File::new(i as u8).expect("a file index"), rank))
+}
+
+impl Game {
+    // The castling rights of both players
+    pub fn castling_rights(&self) -> CastlingRights {
+        self.castling
+    }
+
+    // Whether castling and FEN follow Chess960 conventions, set for games
+    // started with new_chess960 and for FENs whose castling rooks or king are
+    // not on the standard files
+    pub fn is_chess960(&self) -> bool {
+        self.chess960
+    }
+
+    // The rook squares the king of white (or black) can castle to
+    // Castling is legal when the king is not in check, every square between
+    // the king and its target and between the rook and its target is empty
+    // but for the two of them, the king passes no attacked square and does
+    // not end up in check
+    pub(crate) fn castling_moves(&self, white: bool) -> Vec<Pos> {
+        let board = &self.boards.board;
+        let king = self.king_pos(white);
+        let back_rank = Rank::back_rank(white);
+        if king.rank() != back_rank || attacks::is_attacked(board, king, !white) {
+            return Vec::new();
+        }
+
+        let mut moves: Vec<Pos> = Vec::new();
+        for king_side in [true, false] {
+            let Some(file) = self.castling.rook(white, king_side) else {
+                continue;
+            };
+            let rook = Pos::new(file, back_rank);
+            let square = self.square(rook);
+            if !square.occupied
+                || square.piece.piece_type != PieceType::Rook
+                || square.piece.white != white
+            {
+                continue;
+            }
+            let (king_to, rook_to) = castle_targets(king, rook);
+
+            let clear = span(king, king_to)
+                .chain(span(rook, rook_to))
+                .all(|pos| pos == king || pos == rook || !self.square(pos).occupied);
+            let safe = span(king, king_to)
+                .filter(|&pos| pos != king)
+                .all(|pos| !attacks::is_attacked(board, pos, !white));
+            if !clear || !safe {
+                continue;
+            }
+
+            // The rook may have been shielding the king's target square
+            let mut board_ = board.clone();
+            square_to_unoccupied(king, &mut board_);
+            square_to_unoccupied(rook, &mut board_);
+            board_[rook_to.rank().index()][rook_to.file().index()] =
+                new_square(rook_to, PieceType::Rook, white);
+            if !attacks::is_attacked(&board_, king_to, !white) {
+                moves.push(rook);
+            }
+        }
+        moves
+    }
+
+    // Moves the king and rook of a castling move to their targets
+    pub(crate) fn castle(&mut self, mv: &Move) {
+        let white = self.square(mv.from).piece.white;
+        let (king_to, rook_to) = castle_targets(mv.from, mv.to);
+        square_to_unoccupied(mv.from, &mut self.boards.board);
+        square_to_unoccupied(mv.to, &mut self.boards.board);
+        *self.boards.square_mut(king_to) = new_square(king_to, PieceType::King, white);
+        *self.boards.square_mut(rook_to) = new_square(rook_to, PieceType::Rook, white);
+    }
+
+    // Drops the rights mv takes away: all of them when the king moves, one
+    // when its rook moves or is captured
+    pub(crate) fn update_castling(&mut self, mv: &Move) {
+        if mv.piece == PieceType::King {
+            let white = self.square(mv.from).piece.white;
+            self.castling.set(white, true, None);
+            self.castling.set(white, false, None);
+        }
+        for white in [true, false] {
+            for king_side in [true, false] {
+                let Some(file) = self.castling.rook(white, king_side) else {
+                    continue;
+                };
+                let rook = Pos::new(file, Rank::back_rank(white));
+                if mv.from == rook || mv.to == rook {
+                    self.castling.set(white, king_side, None);
+                }
+            }
+        }
+    }
+}
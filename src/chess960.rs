@@ -0,0 +1,84 @@
+use crate::{CastlingRights, File, Game, PieceType};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+// Chess960 starting positions, numbered 0 to 959 as in the Scharnagl scheme
+// Number 518 is the standard starting position
+
+// Where the two knights go among the five squares left after the bishops and
+// the queen, for each value of the last digit
+const KNIGHTS: [(usize, usize); 10] = [
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (0, 4),
+    (1, 2),
+    (1, 3),
+    (1, 4),
+    (2, 3),
+    (2, 4),
+    (3, 4),
+];
+
+// The pieces of the back rank of starting position index, a file first,
+// None unless index is below 960
+pub fn back_rank(index: u16) -> Option<[PieceType; 8]> {
+    if index >= 960 {
+        return None;
+    }
+    let mut rank = [PieceType::Unoccupied; 8];
+    let mut n = index as usize;
+
+    // Bishops on the light squares b, d, f, h and the dark squares a, c, e, g
+    rank[n % 4 * 2 + 1] = PieceType::Bishop;
+    n /= 4;
+    rank[n % 4 * 2] = PieceType::Bishop;
+    n /= 4;
+
+    let mut place = |nth: usize, piece_type: PieceType| {
+        let file = (0..8)
+            .filter(|&file| rank[file] == PieceType::Unoccupied)
+            .nth(nth)
+            .expect("enough empty squares");
+        rank[file] = piece_type;
+    };
+    place(n % 6, PieceType::Queen);
+    n /= 6;
+    // The second knight is counted before the first one is placed
+    let (first, second) = KNIGHTS[n];
+    place(second, PieceType::Knight);
+    place(first, PieceType::Knight);
+    // The king goes between the rooks on the three squares left
+    place(0, PieceType::Rook);
+    place(0, PieceType::King);
+    place(0, PieceType::Rook);
+    Some(rank)
+}
+
+impl Game {
+    // Returns a Chess960 game from starting position index, None unless
+    // index is below 960
+    pub fn new_chess960(index: u16) -> Option<Game> {
+        let back_rank = back_rank(index)?;
+        let mut castling = CastlingRights::none();
+        let king = back_rank
+            .iter()
+            .position(|&piece_type| piece_type == PieceType::King)
+            .expect("every starting position has a king");
+        for (file, &piece_type) in File::all().zip(back_rank.iter()) {
+            if piece_type == PieceType::Rook {
+                castling.set(true, file.index() > king, Some(file));
+                castling.set(false, file.index() > king, Some(file));
+            }
+        }
+        Some(Game::from_back_rank(back_rank, castling, true))
+    }
+
+    // Returns a Chess960 game from a random starting position
+    pub fn new_chess960_random() -> Game {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u8(0);
+        let index = (hasher.finish() % 960) as u16;
+        Game::new_chess960(index).expect("index is below 960")
+    }
+}
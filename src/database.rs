@@ -0,0 +1,422 @@
+use crate::pgn::{parse_game, parse_tree, to_pgn, tree_to_pgn, PgnError};
+use crate::tree::GameTree;
+use crate::Game;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+// Reading and writing PGN files with many games
+//
+// PgnReader splits its input into games one at a time, so a file of any size
+// can be gone through without holding more than one game in memory. A game
+// that cannot be read is reported with where it starts and the reader moves
+// on to the next one.
+
+// Why a game, or the rest of the input, could not be read
+#[derive(Debug)]
+pub enum ReadError {
+    // Reading the input failed, nothing more is read after this
+    Io(io::Error),
+    // Game number (counted from 1), starting at line, is not valid PGN
+    Game {
+        number: usize,
+        line: usize,
+        error: PgnError,
+    },
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReadError::Io(e) => write!(f, "{}", e),
+            ReadError::Game {
+                number,
+                line,
+                error,
+            } => write!(f, "game {} at line {}: {}", number, line, error),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {}
+
+impl From<io::Error> for ReadError {
+    fn from(e: io::Error) -> ReadError {
+        ReadError::Io(e)
+    }
+}
+
+// The text of one game as found in the input
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GameText {
+    // The position of the game in the input, counted from 1
+    pub number: usize,
+    // The line the game starts at, counted from 1
+    pub line: usize,
+    pub text: String,
+}
+
+impl GameText {
+    // Reads the game into its tags and a game of its main line, see pgn::parse_game
+    pub fn parse(&self) -> Result<(Vec<(String, String)>, Game), ReadError> {
+        parse_game(&self.text).map_err(|error| self.error(error))
+    }
+
+    // Reads the game into its tags and a tree with its variations, see pgn::parse_tree
+    pub fn parse_tree(&self) -> Result<(Vec<(String, String)>, GameTree), ReadError> {
+        parse_tree(&self.text).map_err(|error| self.error(error))
+    }
+
+    fn error(&self, error: PgnError) -> ReadError {
+        ReadError::Game {
+            number: self.number,
+            line: self.line,
+            error,
+        }
+    }
+}
+
+// Reads the games of a PGN file one by one
+// As an iterator it returns the tags and main line of each game, next_text
+// returns the text of the next game for reading it some other way
+// Besides what parse_game accepts it copes with a byte order mark, CRLF line
+// ends, text that is not UTF-8, "%" escape lines, games without tags that
+// follow right after the result of the game before and games with tags but
+// no moves
+pub struct PgnReader<R: Read> {
+    input: BufReader<R>,
+    // A line read past the end of the last game, the first of the next one
+    peeked: Option<String>,
+    line: usize,
+    games: usize,
+    done: bool,
+}
+
+impl PgnReader<File> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<PgnReader<File>, ReadError> {
+        Ok(PgnReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> PgnReader<R> {
+    pub fn new(input: R) -> PgnReader<R> {
+        PgnReader {
+            input: BufReader::new(input),
+            peeked: None,
+            line: 0,
+            games: 0,
+            done: false,
+        }
+    }
+
+    // The text of the next game, None at the end of the input
+    pub fn next_text(&mut self) -> Option<Result<GameText, ReadError>> {
+        if self.done {
+            return None;
+        }
+        let mut text = String::new();
+        let mut start = 0;
+        let mut in_movetext = false;
+        // An empty line after the tags, so that a game with no movetext is
+        // not run together with the tags of the next one
+        let mut tags_ended = false;
+        // Open braces, so that lines inside a comment are not taken for tags
+        let mut in_comment = false;
+        let mut ended = false;
+
+        loop {
+            let line = match self.peeked.take() {
+                Some(line) => {
+                    self.line += 1;
+                    line
+                }
+                None => match self.read_line() {
+                    Ok(Some(line)) => line,
+                    Ok(None) => break,
+                    Err(e) => {
+                        self.done = true;
+                        return Some(Err(e.into()));
+                    }
+                },
+            };
+            let trimmed = line.trim();
+            if trimmed.is_empty() || (!in_comment && trimmed.starts_with('%')) {
+                if !text.is_empty() {
+                    text.push('\n');
+                }
+                tags_ended = trimmed.is_empty() && !text.trim().is_empty();
+                continue;
+            }
+
+            let new_game = !in_comment && (trimmed.starts_with('[') || ended);
+            if new_game && (in_movetext || tags_ended) {
+                self.peeked = Some(line);
+                self.line -= 1;
+                break;
+            }
+            if text.trim().is_empty() {
+                text.clear();
+                start = self.line;
+            }
+            if in_comment || !trimmed.starts_with('[') {
+                in_movetext = true;
+                in_comment = comment_open(trimmed, in_comment);
+                ended = !in_comment && ends_with_result(trimmed);
+            }
+            text.push_str(trimmed);
+            text.push('\n');
+        }
+
+        match text.trim().is_empty() {
+            true => {
+                self.done = true;
+                None
+            }
+            false => {
+                self.games += 1;
+                Some(Ok(GameText {
+                    number: self.games,
+                    line: start,
+                    text,
+                }))
+            }
+        }
+    }
+
+    // The next line without its line end and, on the first line, the byte
+    // order mark
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        let mut bytes: Vec<u8> = Vec::new();
+        if self.input.read_until(b'\n', &mut bytes)? == 0 {
+            return Ok(None);
+        }
+        self.line += 1;
+        let mut line = String::from_utf8_lossy(&bytes).into_owned();
+        if self.line == 1 {
+            if let Some(rest) = line.strip_prefix('\u{feff}') {
+                line = rest.to_string();
+            }
+        }
+        let len = line.trim_end_matches(['\r', '\n']).len();
+        line.truncate(len);
+        Ok(Some(line))
+    }
+}
+
+impl<R: Read> Iterator for PgnReader<R> {
+    type Item = Result<(Vec<(String, String)>, Game), ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_text()?.and_then(|text| text.parse()))
+    }
+}
+
+// Whether a comment is still open at the end of line, given whether one was
+// open at its start
+fn comment_open(line: &str, mut open: bool) -> bool {
+    for c in line.chars() {
+        match c {
+            '{' => open = true,
+            '}' => open = false,
+            ';' if !open => return false,
+            _ => (),
+        }
+    }
+    open
+}
+
+fn ends_with_result(line: &str) -> bool {
+    let line = match line.find(';') {
+        Some(i) if !line[..i].contains('{') => &line[..i],
+        _ => line,
+    };
+    matches!(
+        line.split_whitespace().last(),
+        Some("1-0" | "0-1" | "1/2-1/2" | "*")
+    )
+}
+
+// Writes games one after another as PGN, each followed by an empty line
+pub struct PgnWriter<W: Write> {
+    output: BufWriter<W>,
+}
+
+impl PgnWriter<File> {
+    // Adds games to the end of the file at path, creating it if needed
+    pub fn append<P: AsRef<Path>>(path: P) -> io::Result<PgnWriter<File>> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(PgnWriter::new(file))
+    }
+}
+
+impl<W: Write> PgnWriter<W> {
+    pub fn new(output: W) -> PgnWriter<W> {
+        PgnWriter {
+            output: BufWriter::new(output),
+        }
+    }
+
+    // Writes game with tags as pgn::to_pgn does
+    pub fn write_game(&mut self, game: &Game, tags: &[(&str, &str)]) -> io::Result<()> {
+        self.output.write_all(to_pgn(game, tags).as_bytes())?;
+        self.output.write_all(b"\n")
+    }
+
+    // Writes tree with its variations as pgn::tree_to_pgn does
+    pub fn write_tree(&mut self, tree: &GameTree, tags: &[(&str, &str)]) -> io::Result<()> {
+        self.output.write_all(tree_to_pgn(tree, tags).as_bytes())?;
+        self.output.write_all(b"\n")
+    }
+
+    // Writes out what is buffered, which also happens when the writer is dropped
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+
+    // Flushes and returns the output
+    pub fn into_inner(self) -> io::Result<W> {
+        self.output.into_inner().map_err(|e| e.into_error())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::san::parse_san;
+
+    fn play(mut game: Game, moves: &[&str]) -> Game {
+        for san in moves {
+            let mv = parse_san(&game, san).expect("legal move");
+            game = game.play_move(mv);
+        }
+        game
+    }
+
+    fn round_trip(games: &[Game]) -> Vec<Game> {
+        let mut writer = PgnWriter::new(Vec::new());
+        for game in games {
+            writer.write_game(game, &[("Event", "Test")]).unwrap();
+        }
+        let bytes = writer.into_inner().unwrap();
+        PgnReader::new(bytes.as_slice())
+            .map(|read| read.unwrap().1)
+            .collect()
+    }
+
+    #[test]
+    fn writes_and_reads_games() {
+        let games = [
+            play(Game::new(), &["e4", "e5", "Nf3", "Nc6"]),
+            play(Game::new(), &["d4", "d5"]),
+        ];
+        let read = round_trip(&games);
+        assert_eq!(read.len(), 2);
+        for (read, game) in read.iter().zip(&games) {
+            assert_eq!(read.move_history, game.move_history);
+        }
+    }
+
+    #[test]
+    fn game_from_fen_with_black_to_move() {
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1";
+        let mut game = play(Game::from_fen(fen).unwrap(), &["e5", "Nf3"]);
+        game.annotations[0].add_comment("good").unwrap();
+        let game = play(game, &["Nc6"]);
+
+        let text = crate::pgn::to_pgn(&game, &[]);
+        assert!(
+            text.contains("[FEN \"rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1\"]")
+        );
+        assert!(text.contains("1... e5 {good} 2. Nf3 Nc6"), "{}", text);
+
+        let read = round_trip(std::slice::from_ref(&game));
+        assert_eq!(read[0].move_history, game.move_history);
+        assert_eq!(read[0].start_fen(), fen);
+        assert_eq!(read[0].to_fen(), game.to_fen());
+    }
+
+    // The moves of each game read from bytes, or the game number and line of
+    // the error
+    fn read(bytes: &[u8]) -> Vec<Result<Vec<String>, (usize, usize)>> {
+        PgnReader::new(bytes)
+            .map(|read| match read {
+                Ok((_, game)) => Ok(game.move_history),
+                Err(ReadError::Game { number, line, .. }) => Err((number, line)),
+                Err(e) => panic!("{}", e),
+            })
+            .collect()
+    }
+
+    fn moves(moves: &[&str]) -> Result<Vec<String>, (usize, usize)> {
+        Ok(moves.iter().map(|san| san.to_string()).collect())
+    }
+
+    #[test]
+    fn byte_order_mark() {
+        let text = "\u{feff}[Event \"BOM\"]\n\n1. e4 e5 *\n";
+        assert_eq!(read(text.as_bytes()), vec![moves(&["e4", "e5"])]);
+        let (tags, _) = PgnReader::new(text.as_bytes()).next().unwrap().unwrap();
+        assert_eq!(tags[0], ("Event".to_string(), "BOM".to_string()));
+    }
+
+    #[test]
+    fn crlf_line_ends() {
+        let text = "[Event \"A\"]\r\n\r\n1. e4 e5 1-0\r\n\r\n[Event \"B\"]\r\n\r\n1. d4 0-1\r\n";
+        assert_eq!(
+            read(text.as_bytes()),
+            vec![moves(&["e4", "e5"]), moves(&["d4"])]
+        );
+    }
+
+    #[test]
+    fn text_that_is_not_utf8() {
+        // "Müller" in Latin-1
+        let mut bytes = b"[White \"M".to_vec();
+        bytes.push(0xfc);
+        bytes.extend_from_slice(b"ller\"]\n\n1. e4 *\n");
+        let (tags, game) = PgnReader::new(bytes.as_slice()).next().unwrap().unwrap();
+        assert_eq!(tags[0].1, "M\u{fffd}ller");
+        assert_eq!(game.move_history, vec!["e4"]);
+    }
+
+    #[test]
+    fn escape_lines() {
+        let text = "% exported by some program\n[Event \"A\"]\n\n1. e4\n%1. d4\ne5 *\n";
+        assert_eq!(read(text.as_bytes()), vec![moves(&["e4", "e5"])]);
+    }
+
+    #[test]
+    fn game_without_tags_after_result() {
+        let text = "[Event \"A\"]\n\n1. e4 e5 1-0\n1. d4 d5 *\n";
+        assert_eq!(
+            read(text.as_bytes()),
+            vec![moves(&["e4", "e5"]), moves(&["d4", "d5"])]
+        );
+    }
+
+    #[test]
+    fn game_without_moves() {
+        let text = "[Event \"A\"]\n[Result \"*\"]\n\n[Event \"B\"]\n\n1. e4 *\n";
+        let games: Vec<(Vec<(String, String)>, Game)> = PgnReader::new(text.as_bytes())
+            .map(|read| read.unwrap())
+            .collect();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].0[0].1, "A");
+        assert!(games[0].1.move_history.is_empty());
+        assert_eq!(games[1].0[0].1, "B");
+        assert_eq!(games[1].1.move_history, vec!["e4"]);
+    }
+
+    #[test]
+    fn bad_game_is_reported_and_skipped() {
+        let text =
+            "[Event \"A\"]\n\n1. e4 *\n\n[Event \"B\"]\n\n1. e5 *\n\n[Event \"C\"]\n\n1. d4 *\n";
+        assert_eq!(
+            read(text.as_bytes()),
+            vec![moves(&["e4"]), Err((2, 5)), moves(&["d4"])]
+        );
+        let error = PgnReader::new(text.as_bytes()).nth(1).unwrap().unwrap_err();
+        assert_eq!(error.to_string(), "game 2 at line 5: illegal move \"e5\"");
+    }
+}
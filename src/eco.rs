@@ -0,0 +1,589 @@
+// ECO (Encyclopaedia of Chess Openings) classification
+//
+// The table below covers the main lines of each opening family, matched on
+// the moves played in SAN. Check and mate marks are ignored, but transpositions
+// into a line by another move order are not recognised. Games set up from a
+// FEN are not classified, since their moves do not start from the initial
+// position the lines are written for.
+
+use crate::fen::START_FEN;
+use crate::Game;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Opening {
+    pub eco: &'static str,
+    pub name: &'static str,
+    // The moves of the line in SAN, separated by spaces
+    pub moves: &'static str,
+}
+
+impl Opening {
+    pub fn moves(&self) -> impl Iterator<Item = &'static str> {
+        self.moves.split_whitespace()
+    }
+}
+
+// The deepest opening line that the moves of game start with, None if not
+// even the first move is in the table or the game did not start from the
+// initial position
+// Works at any point of the game, so a UI can show the current opening
+pub fn classify(game: &Game) -> Option<Opening> {
+    if game.start_fen() != START_FEN {
+        return None;
+    }
+    classify_moves(&game.move_history)
+}
+
+// classify for a list of moves in SAN
+pub fn classify_moves<S: AsRef<str>>(history: &[S]) -> Option<Opening> {
+    let played: Vec<&str> = history
+        .iter()
+        .map(|san| san.as_ref().trim_end_matches(['+', '#', '!', '?']))
+        .collect();
+
+    OPENINGS
+        .iter()
+        .filter(|opening| {
+            let line: Vec<&str> = opening.moves().collect();
+            line.len() <= played.len() && line.iter().zip(&played).all(|(a, b)| a == b)
+        })
+        .max_by_key(|opening| opening.moves().count())
+        .copied()
+}
+
+// All openings of the table
+pub fn openings() -> &'static [Opening] {
+    OPENINGS
+}
+
+const fn opening(eco: &'static str, name: &'static str, moves: &'static str) -> Opening {
+    Opening { eco, name, moves }
+}
+
+const OPENINGS: &[Opening] = &[
+    // A: flank openings, d4 without d5 or Nf6 c4 ... and the Dutch
+    opening("A00", "Polish Opening", "b4"),
+    opening("A00", "Grob Opening", "g4"),
+    opening("A00", "Hungarian Opening", "g3"),
+    opening("A00", "Van't Kruijs Opening", "e3"),
+    opening("A00", "Mieses Opening", "d3"),
+    opening("A00", "Saragossa Opening", "c3"),
+    opening("A00", "Anderssen's Opening", "a3"),
+    opening("A00", "Ware Opening", "a4"),
+    opening("A00", "Clemenz Opening", "h3"),
+    opening("A00", "Kadas Opening", "h4"),
+    opening("A00", "Amar Opening", "Nh3"),
+    opening("A00", "Sodium Attack", "Na3"),
+    opening("A00", "Van Geet Opening", "Nc3"),
+    opening("A01", "Nimzo-Larsen Attack", "b3"),
+    opening("A02", "Bird's Opening", "f4"),
+    opening("A02", "Bird's Opening, From's Gambit", "f4 e5"),
+    opening("A03", "Bird's Opening, Dutch Variation", "f4 d5"),
+    opening("A04", "Zukertort Opening", "Nf3"),
+    opening("A05", "Zukertort Opening", "Nf3 Nf6"),
+    opening("A06", "Zukertort Opening", "Nf3 d5"),
+    opening("A09", "Reti Opening", "Nf3 d5 c4"),
+    opening("A10", "English Opening", "c4"),
+    opening("A13", "English Opening, Agincourt Defense", "c4 e6"),
+    opening("A15", "English Opening, Anglo-Indian Defense", "c4 Nf6"),
+    opening(
+        "A16",
+        "English Opening, Anglo-Indian Defense, Queen's Knight Variation",
+        "c4 Nf6 Nc3",
+    ),
+    opening("A20", "English Opening, King's English Variation", "c4 e5"),
+    opening(
+        "A21",
+        "English Opening, King's English Variation, Reversed Sicilian",
+        "c4 e5 Nc3",
+    ),
+    opening(
+        "A22",
+        "English Opening, King's English Variation, Two Knights Variation",
+        "c4 e5 Nc3 Nf6",
+    ),
+    opening(
+        "A25",
+        "English Opening, King's English Variation, Reversed Closed Sicilian",
+        "c4 e5 Nc3 Nc6",
+    ),
+    opening("A30", "English Opening, Symmetrical Variation", "c4 c5"),
+    opening("A40", "Queen's Pawn Game", "d4"),
+    opening("A40", "Englund Gambit", "d4 e5"),
+    opening("A40", "Modern Defense", "d4 g6"),
+    opening("A40", "Horwitz Defense", "d4 e6"),
+    opening("A43", "Old Benoni Defense", "d4 c5"),
+    opening("A45", "Indian Defense", "d4 Nf6"),
+    opening("A45", "Trompowsky Attack", "d4 Nf6 Bg5"),
+    opening("A46", "Indian Defense", "d4 Nf6 Nf3"),
+    opening("A50", "Indian Defense, Normal Variation", "d4 Nf6 c4"),
+    opening("A51", "Budapest Gambit", "d4 Nf6 c4 e5"),
+    opening("A52", "Budapest Gambit", "d4 Nf6 c4 e5 dxe5 Ng4"),
+    opening("A53", "Old Indian Defense", "d4 Nf6 c4 d6"),
+    opening("A56", "Benoni Defense", "d4 Nf6 c4 c5"),
+    opening("A57", "Benko Gambit", "d4 Nf6 c4 c5 d5 b5"),
+    opening("A60", "Modern Benoni", "d4 Nf6 c4 c5 d5 e6"),
+    opening("A80", "Dutch Defense", "d4 f5"),
+    opening("A82", "Dutch Defense, Staunton Gambit", "d4 f5 e4"),
+    opening("A84", "Dutch Defense", "d4 f5 c4"),
+    opening(
+        "A87",
+        "Dutch Defense, Leningrad Variation",
+        "d4 f5 c4 Nf6 g3 g6 Bg2 Bg7 Nf3",
+    ),
+    // B: e4 without e5 or e6
+    opening("B00", "King's Pawn Opening", "e4"),
+    opening("B00", "Nimzowitsch Defense", "e4 Nc6"),
+    opening("B00", "Owen Defense", "e4 b6"),
+    opening("B00", "St. George Defense", "e4 a6"),
+    opening("B01", "Scandinavian Defense", "e4 d5"),
+    opening(
+        "B01",
+        "Scandinavian Defense, Modern Variation",
+        "e4 d5 exd5 Nf6",
+    ),
+    opening(
+        "B01",
+        "Scandinavian Defense, Main Line",
+        "e4 d5 exd5 Qxd5 Nc3 Qa5",
+    ),
+    opening("B02", "Alekhine Defense", "e4 Nf6"),
+    opening(
+        "B03",
+        "Alekhine Defense, Four Pawns Attack",
+        "e4 Nf6 e5 Nd5 d4 d6 c4 Nb6 f4",
+    ),
+    opening(
+        "B04",
+        "Alekhine Defense, Modern Variation",
+        "e4 Nf6 e5 Nd5 d4 d6 Nf3",
+    ),
+    opening("B06", "Modern Defense", "e4 g6"),
+    opening("B07", "Pirc Defense", "e4 d6 d4 Nf6 Nc3"),
+    opening(
+        "B08",
+        "Pirc Defense, Classical Variation",
+        "e4 d6 d4 Nf6 Nc3 g6 Nf3 Bg7",
+    ),
+    opening(
+        "B09",
+        "Pirc Defense, Austrian Attack",
+        "e4 d6 d4 Nf6 Nc3 g6 f4",
+    ),
+    opening("B10", "Caro-Kann Defense", "e4 c6"),
+    opening(
+        "B12",
+        "Caro-Kann Defense, Advance Variation",
+        "e4 c6 d4 d5 e5",
+    ),
+    opening(
+        "B13",
+        "Caro-Kann Defense, Exchange Variation",
+        "e4 c6 d4 d5 exd5 cxd5",
+    ),
+    opening(
+        "B13",
+        "Caro-Kann Defense, Panov Attack",
+        "e4 c6 d4 d5 exd5 cxd5 c4",
+    ),
+    opening("B15", "Caro-Kann Defense", "e4 c6 d4 d5 Nc3"),
+    opening(
+        "B17",
+        "Caro-Kann Defense, Karpov Variation",
+        "e4 c6 d4 d5 Nc3 dxe4 Nxe4 Nd7",
+    ),
+    opening(
+        "B18",
+        "Caro-Kann Defense, Classical Variation",
+        "e4 c6 d4 d5 Nc3 dxe4 Nxe4 Bf5",
+    ),
+    opening("B20", "Sicilian Defense", "e4 c5"),
+    opening(
+        "B21",
+        "Sicilian Defense, Smith-Morra Gambit",
+        "e4 c5 d4 cxd4 c3",
+    ),
+    opening("B22", "Sicilian Defense, Alapin Variation", "e4 c5 c3"),
+    opening("B23", "Sicilian Defense, Closed", "e4 c5 Nc3"),
+    opening(
+        "B23",
+        "Sicilian Defense, Grand Prix Attack",
+        "e4 c5 Nc3 Nc6 f4",
+    ),
+    opening("B27", "Sicilian Defense", "e4 c5 Nf3"),
+    opening(
+        "B27",
+        "Sicilian Defense, Hyperaccelerated Dragon",
+        "e4 c5 Nf3 g6",
+    ),
+    opening("B30", "Sicilian Defense, Old Sicilian", "e4 c5 Nf3 Nc6"),
+    opening(
+        "B30",
+        "Sicilian Defense, Nyezhmetdinov-Rossolimo Attack",
+        "e4 c5 Nf3 Nc6 Bb5",
+    ),
+    opening(
+        "B32",
+        "Sicilian Defense, Open",
+        "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4",
+    ),
+    opening(
+        "B33",
+        "Sicilian Defense, Sveshnikov Variation",
+        "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4 Nf6 Nc3 e5",
+    ),
+    opening(
+        "B34",
+        "Sicilian Defense, Accelerated Dragon",
+        "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4 g6",
+    ),
+    opening("B40", "Sicilian Defense, French Variation", "e4 c5 Nf3 e6"),
+    opening(
+        "B41",
+        "Sicilian Defense, Kan Variation",
+        "e4 c5 Nf3 e6 d4 cxd4 Nxd4 a6",
+    ),
+    opening(
+        "B44",
+        "Sicilian Defense, Taimanov Variation",
+        "e4 c5 Nf3 e6 d4 cxd4 Nxd4 Nc6",
+    ),
+    opening("B50", "Sicilian Defense, Modern Variations", "e4 c5 Nf3 d6"),
+    opening(
+        "B51",
+        "Sicilian Defense, Moscow Variation",
+        "e4 c5 Nf3 d6 Bb5",
+    ),
+    opening("B54", "Sicilian Defense, Open", "e4 c5 Nf3 d6 d4 cxd4 Nxd4"),
+    opening(
+        "B56",
+        "Sicilian Defense, Open",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3",
+    ),
+    opening(
+        "B70",
+        "Sicilian Defense, Dragon Variation",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 g6",
+    ),
+    opening(
+        "B76",
+        "Sicilian Defense, Dragon Variation, Yugoslav Attack",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 g6 Be3 Bg7 f3 O-O",
+    ),
+    opening(
+        "B80",
+        "Sicilian Defense, Scheveningen Variation",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 e6",
+    ),
+    opening(
+        "B90",
+        "Sicilian Defense, Najdorf Variation",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6",
+    ),
+    opening(
+        "B90",
+        "Sicilian Defense, Najdorf Variation, English Attack",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 Be3",
+    ),
+    opening(
+        "B92",
+        "Sicilian Defense, Najdorf Variation, Opocensky Variation",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 Be2",
+    ),
+    opening(
+        "B94",
+        "Sicilian Defense, Najdorf Variation",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 Bg5",
+    ),
+    // C00-C19: French Defense
+    opening("C00", "French Defense", "e4 e6"),
+    opening(
+        "C01",
+        "French Defense, Exchange Variation",
+        "e4 e6 d4 d5 exd5",
+    ),
+    opening("C02", "French Defense, Advance Variation", "e4 e6 d4 d5 e5"),
+    opening(
+        "C03",
+        "French Defense, Tarrasch Variation",
+        "e4 e6 d4 d5 Nd2",
+    ),
+    opening(
+        "C10",
+        "French Defense, Paulsen Variation",
+        "e4 e6 d4 d5 Nc3",
+    ),
+    opening(
+        "C10",
+        "French Defense, Rubinstein Variation",
+        "e4 e6 d4 d5 Nc3 dxe4",
+    ),
+    opening(
+        "C11",
+        "French Defense, Classical Variation",
+        "e4 e6 d4 d5 Nc3 Nf6",
+    ),
+    opening(
+        "C15",
+        "French Defense, Winawer Variation",
+        "e4 e6 d4 d5 Nc3 Bb4",
+    ),
+    // C20-C99: open games
+    opening("C20", "King's Pawn Game", "e4 e5"),
+    opening("C21", "Center Game", "e4 e5 d4 exd4"),
+    opening("C21", "Danish Gambit", "e4 e5 d4 exd4 c3"),
+    opening("C23", "Bishop's Opening", "e4 e5 Bc4"),
+    opening("C25", "Vienna Game", "e4 e5 Nc3"),
+    opening("C29", "Vienna Game, Vienna Gambit", "e4 e5 Nc3 Nf6 f4"),
+    opening("C30", "King's Gambit", "e4 e5 f4"),
+    opening(
+        "C31",
+        "King's Gambit Declined, Falkbeer Countergambit",
+        "e4 e5 f4 d5",
+    ),
+    opening("C33", "King's Gambit Accepted", "e4 e5 f4 exf4"),
+    opening("C40", "King's Knight Opening", "e4 e5 Nf3"),
+    opening("C40", "Latvian Gambit", "e4 e5 Nf3 f5"),
+    opening("C40", "Elephant Gambit", "e4 e5 Nf3 d5"),
+    opening("C41", "Philidor Defense", "e4 e5 Nf3 d6"),
+    opening("C42", "Petrov's Defense", "e4 e5 Nf3 Nf6"),
+    opening(
+        "C42",
+        "Petrov's Defense, Stafford Gambit",
+        "e4 e5 Nf3 Nf6 Nxe5 Nc6",
+    ),
+    opening("C44", "King's Pawn Game", "e4 e5 Nf3 Nc6"),
+    opening("C44", "Ponziani Opening", "e4 e5 Nf3 Nc6 c3"),
+    opening("C44", "Scotch Game", "e4 e5 Nf3 Nc6 d4"),
+    opening("C44", "Scotch Gambit", "e4 e5 Nf3 Nc6 d4 exd4 Bc4"),
+    opening("C45", "Scotch Game", "e4 e5 Nf3 Nc6 d4 exd4 Nxd4"),
+    opening("C46", "Three Knights Opening", "e4 e5 Nf3 Nc6 Nc3"),
+    opening("C47", "Four Knights Game", "e4 e5 Nf3 Nc6 Nc3 Nf6"),
+    opening(
+        "C48",
+        "Four Knights Game, Spanish Variation",
+        "e4 e5 Nf3 Nc6 Nc3 Nf6 Bb5",
+    ),
+    opening("C50", "Italian Game", "e4 e5 Nf3 Nc6 Bc4"),
+    opening(
+        "C50",
+        "Italian Game, Hungarian Defense",
+        "e4 e5 Nf3 Nc6 Bc4 Be7",
+    ),
+    opening("C50", "Italian Game, Giuoco Piano", "e4 e5 Nf3 Nc6 Bc4 Bc5"),
+    opening(
+        "C51",
+        "Italian Game, Evans Gambit",
+        "e4 e5 Nf3 Nc6 Bc4 Bc5 b4",
+    ),
+    opening(
+        "C53",
+        "Italian Game, Classical Variation",
+        "e4 e5 Nf3 Nc6 Bc4 Bc5 c3",
+    ),
+    opening(
+        "C55",
+        "Italian Game, Two Knights Defense",
+        "e4 e5 Nf3 Nc6 Bc4 Nf6",
+    ),
+    opening(
+        "C57",
+        "Italian Game, Two Knights Defense, Knight Attack",
+        "e4 e5 Nf3 Nc6 Bc4 Nf6 Ng5",
+    ),
+    opening(
+        "C57",
+        "Italian Game, Two Knights Defense, Traxler Counterattack",
+        "e4 e5 Nf3 Nc6 Bc4 Nf6 Ng5 Bc5",
+    ),
+    opening(
+        "C57",
+        "Italian Game, Two Knights Defense, Fried Liver Attack",
+        "e4 e5 Nf3 Nc6 Bc4 Nf6 Ng5 d5 exd5 Nxd5 Nxf7",
+    ),
+    opening("C60", "Ruy Lopez", "e4 e5 Nf3 Nc6 Bb5"),
+    opening("C62", "Ruy Lopez, Steinitz Defense", "e4 e5 Nf3 Nc6 Bb5 d6"),
+    opening(
+        "C63",
+        "Ruy Lopez, Schliemann Defense",
+        "e4 e5 Nf3 Nc6 Bb5 f5",
+    ),
+    opening("C65", "Ruy Lopez, Berlin Defense", "e4 e5 Nf3 Nc6 Bb5 Nf6"),
+    opening(
+        "C67",
+        "Ruy Lopez, Berlin Defense, Rio de Janeiro Variation",
+        "e4 e5 Nf3 Nc6 Bb5 Nf6 O-O Nxe4 d4 Nd6 Bxc6 dxc6 dxe5 Nf5 Qxd8 Kxd8",
+    ),
+    opening(
+        "C68",
+        "Ruy Lopez, Exchange Variation",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Bxc6",
+    ),
+    opening(
+        "C70",
+        "Ruy Lopez, Morphy Defense",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Ba4",
+    ),
+    opening(
+        "C78",
+        "Ruy Lopez, Morphy Defense",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O",
+    ),
+    opening(
+        "C80",
+        "Ruy Lopez, Open Variation",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Nxe4",
+    ),
+    opening(
+        "C84",
+        "Ruy Lopez, Closed",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7",
+    ),
+    opening(
+        "C88",
+        "Ruy Lopez, Closed",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3",
+    ),
+    opening(
+        "C89",
+        "Ruy Lopez, Marshall Attack",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3 O-O c3 d5",
+    ),
+    // D: closed games and the Grunfeld
+    opening("D00", "Queen's Pawn Game", "d4 d5"),
+    opening("D00", "Blackmar-Diemer Gambit", "d4 d5 e4"),
+    opening("D02", "Queen's Pawn Game, Zukertort Variation", "d4 d5 Nf3"),
+    opening(
+        "D02",
+        "Queen's Pawn Game, London System",
+        "d4 d5 Nf3 Nf6 Bf4",
+    ),
+    opening("D06", "Queen's Gambit", "d4 d5 c4"),
+    opening(
+        "D07",
+        "Queen's Gambit Declined, Chigorin Defense",
+        "d4 d5 c4 Nc6",
+    ),
+    opening(
+        "D08",
+        "Queen's Gambit Declined, Albin Countergambit",
+        "d4 d5 c4 e5",
+    ),
+    opening("D10", "Slav Defense", "d4 d5 c4 c6"),
+    opening(
+        "D15",
+        "Slav Defense, Three Knights Variation",
+        "d4 d5 c4 c6 Nf3 Nf6 Nc3",
+    ),
+    opening("D20", "Queen's Gambit Accepted", "d4 d5 c4 dxc4"),
+    opening("D30", "Queen's Gambit Declined", "d4 d5 c4 e6"),
+    opening("D31", "Queen's Gambit Declined", "d4 d5 c4 e6 Nc3"),
+    opening("D32", "Tarrasch Defense", "d4 d5 c4 e6 Nc3 c5"),
+    opening(
+        "D35",
+        "Queen's Gambit Declined, Exchange Variation",
+        "d4 d5 c4 e6 Nc3 Nf6 cxd5",
+    ),
+    opening("D43", "Semi-Slav Defense", "d4 d5 c4 e6 Nc3 Nf6 Nf3 c6"),
+    opening("D80", "Grunfeld Defense", "d4 Nf6 c4 g6 Nc3 d5"),
+    opening(
+        "D85",
+        "Grunfeld Defense, Exchange Variation",
+        "d4 Nf6 c4 g6 Nc3 d5 cxd5 Nxd5",
+    ),
+    // E: Indian defences
+    opening("E00", "Indian Defense", "d4 Nf6 c4 e6"),
+    opening("E01", "Catalan Opening", "d4 Nf6 c4 e6 g3"),
+    opening("E10", "Indian Defense", "d4 Nf6 c4 e6 Nf3"),
+    opening("E11", "Bogo-Indian Defense", "d4 Nf6 c4 e6 Nf3 Bb4"),
+    opening("E12", "Queen's Indian Defense", "d4 Nf6 c4 e6 Nf3 b6"),
+    opening("E20", "Nimzo-Indian Defense", "d4 Nf6 c4 e6 Nc3 Bb4"),
+    opening(
+        "E32",
+        "Nimzo-Indian Defense, Classical Variation",
+        "d4 Nf6 c4 e6 Nc3 Bb4 Qc2",
+    ),
+    opening(
+        "E40",
+        "Nimzo-Indian Defense, Rubinstein System",
+        "d4 Nf6 c4 e6 Nc3 Bb4 e3",
+    ),
+    opening("E60", "King's Indian Defense", "d4 Nf6 c4 g6"),
+    opening("E61", "King's Indian Defense", "d4 Nf6 c4 g6 Nc3 Bg7"),
+    opening(
+        "E70",
+        "King's Indian Defense, Normal Variation",
+        "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6",
+    ),
+    opening(
+        "E76",
+        "King's Indian Defense, Four Pawns Attack",
+        "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 f4",
+    ),
+    opening(
+        "E80",
+        "King's Indian Defense, Samisch Variation",
+        "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 f3",
+    ),
+    opening(
+        "E90",
+        "King's Indian Defense, Normal Variation",
+        "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 Nf3",
+    ),
+    opening(
+        "E97",
+        "King's Indian Defense, Orthodox Variation, Aronin-Taimanov Defense",
+        "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 Nf3 O-O Be2 e5 O-O Nc6 d5 Ne7",
+    ),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::san::parse_san;
+
+    fn play(mut game: Game, moves: &str) -> Game {
+        for san in moves.split_whitespace() {
+            let mv = parse_san(&game, san).expect("legal move");
+            game = game.play_move(mv);
+        }
+        game
+    }
+
+    #[test]
+    fn deepest_line_wins() {
+        let game = play(Game::new(), "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 h3");
+        let opening = classify(&game).unwrap();
+        assert_eq!(opening.eco, "B90");
+        assert_eq!(opening.name, "Sicilian Defense, Najdorf Variation");
+
+        let opening = classify(&play(Game::new(), "e4 c5")).unwrap();
+        assert_eq!(opening.name, "Sicilian Defense");
+        assert_eq!(
+            classify_moves(&[
+                "e4", "c5", "Nf3", "d6", "d4", "cxd4", "Nxd4", "Nf6", "Nc3", "a6", "Bg5+"
+            ])
+            .map(|opening| opening.eco),
+            Some("B94")
+        );
+    }
+
+    #[test]
+    fn unknown_first_move() {
+        assert_eq!(classify(&play(Game::new(), "f3 e5")), None);
+        assert_eq!(classify(&Game::new()), None);
+    }
+
+    #[test]
+    fn games_from_fen_are_not_classified() {
+        let game = Game::from_fen(START_FEN).unwrap();
+        assert!(classify(&play(game, "e4 c5")).is_some());
+
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 5";
+        let game = play(Game::from_fen(fen).unwrap(), "e4 c5");
+        assert_eq!(classify(&game), None);
+        let fen = "4k3/pppppppp/8/8/8/8/PPPPPPPP/4K3 w - - 0 1";
+        let game = play(Game::from_fen(fen).unwrap(), "e4 c5");
+        assert_eq!(classify(&game), None);
+    }
+}
@@ -0,0 +1,507 @@
+use crate::{
+    build_check_board, Boards, CastlingRights, File, Game, Piece, PieceType, Pos, Rank, Square,
+};
+use std::fmt;
+
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+// Reasons a FEN string can be rejected by Game::from_fen, or a position by
+// BoardBuilder::build
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FenError {
+    // A FEN needs the placement, side to move, castling and en passant fields,
+    // the two clocks are optional
+    FieldCount(usize),
+    // The piece placement field does not describe 8 ranks of 8 squares
+    Placement(String),
+    SideToMove(String),
+    Castling(String),
+    EnPassant(String),
+    Clock(String),
+    // Each player needs exactly one king
+    KingCount { white: usize, black: usize },
+    // A pawn on the first or eighth rank
+    PawnOnBackRank(Pos),
+    // The player who just moved cannot be in check
+    OpponentInCheck,
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FenError::FieldCount(n) => write!(f, "expected 4 to 6 FEN fields, found {}", n),
+            FenError::Placement(s) => write!(f, "invalid piece placement \"{}\"", s),
+            FenError::SideToMove(s) => write!(f, "invalid side to move \"{}\"", s),
+            FenError::Castling(s) => write!(f, "invalid castling rights \"{}\"", s),
+            FenError::EnPassant(s) => write!(f, "invalid en passant square \"{}\"", s),
+            FenError::Clock(s) => write!(f, "invalid move counter \"{}\"", s),
+            FenError::KingCount { white, black } => write!(
+                f,
+                "each side needs exactly one king, found {} white and {} black",
+                white, black
+            ),
+            FenError::PawnOnBackRank(square) => write!(f, "pawn on back rank at {}", square),
+            FenError::OpponentInCheck => write!(f, "the side not to move is in check"),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+impl Game {
+    // Returns the position as a FEN string
+    // Castling rights are written as in X-FEN: KQkq, or the file of the rook
+    // when another rook stands further out on the same wing
+    // The en passant square is only written when the capture can be played
+    pub fn to_fen(&self) -> String {
+        self.fen(false)
+    }
+
+    // The position the game started from, before the first move of history
+    pub fn start_fen(&self) -> &str {
+        &self.start_fen
+    }
+
+    // Returns the position as a Shredder-FEN string, which always writes
+    // castling rights as the files of the rooks, e.g. "HAha"
+    pub fn to_shredder_fen(&self) -> String {
+        self.fen(true)
+    }
+
+    fn fen(&self, shredder: bool) -> String {
+        let white_to_move = self.side_to_move();
+        format!(
+            "{} {} {} {} {} {}",
+            placement_to_fen(&self.boards.board),
+            if white_to_move { "w" } else { "b" },
+            self.castling_to_fen(shredder),
+            match self.en_passant_target() {
+                Some(pos) => pos.to_string(),
+                None => "-".to_string(),
+            },
+            self.halfmove_clock,
+            self.fullmove_number
+        )
+    }
+
+    fn castling_to_fen(&self, shredder: bool) -> String {
+        let mut fen = String::new();
+        for white in [true, false] {
+            for king_side in [true, false] {
+                let Some(file) = self.castling.rook(white, king_side) else {
+                    continue;
+                };
+                let letter = match shredder || !self.outermost_rook(white, king_side, file) {
+                    true => file.to_char().to_ascii_uppercase(),
+                    false if king_side => 'K',
+                    false => 'Q',
+                };
+                match white {
+                    true => fen.push(letter),
+                    false => fen.push(letter.to_ascii_lowercase()),
+                }
+            }
+        }
+        match fen.is_empty() {
+            true => "-".to_string(),
+            false => fen,
+        }
+    }
+
+    // Whether no other rook of the player stands between the rook on file
+    // and the edge of the board
+    fn outermost_rook(&self, white: bool, king_side: bool, file: File) -> bool {
+        !File::all()
+            .filter(|&other| match king_side {
+                true => other > file,
+                false => other < file,
+            })
+            .any(|other| {
+                is_rook(
+                    &self.boards.board,
+                    Pos::new(other, Rank::back_rank(white)),
+                    white,
+                )
+            })
+    }
+
+    // The en passant square if a pawn of the player to move can take there
+    pub(crate) fn en_passant_target(&self) -> Option<Pos> {
+        let target = self.en_passant?;
+        Pos::all()
+            .any(|pos| {
+                let piece = &self.square(pos).piece;
+                piece.piece_type == PieceType::Pawn && piece.moves.contains(&target)
+            })
+            .then_some(target)
+    }
+
+    // Sets up a game from a FEN string, with all moves generated
+    // The position is rejected if it could not occur in a game: a missing or
+    // extra king, pawns on the back ranks or the side not to move in check
+    // Castling rights may be written as KQkq, in X-FEN or in Shredder-FEN;
+    // rights for rooks other than on the a and h files, or for a king off the
+    // e file, make the game a Chess960 game
+    pub fn from_fen(fen: &str) -> Result<Game, FenError> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.len() < 4 || fields.len() > 6 {
+            return Err(FenError::FieldCount(fields.len()));
+        }
+
+        let board = placement_from_fen(fields[0])?;
+        let white_to_move = match fields[1] {
+            "w" => true,
+            "b" => false,
+            other => return Err(FenError::SideToMove(other.to_string())),
+        };
+        let castling = castling_from_fen(fields[2], &board)?;
+        let en_passant = match fields[3] {
+            "-" => None,
+            s if is_en_passant_square(s) => s.parse::<Pos>().ok(),
+            s => return Err(FenError::EnPassant(s.to_string())),
+        };
+        let halfmove_clock = match fields.get(4) {
+            Some(s) => s
+                .parse::<u32>()
+                .map_err(|_| FenError::Clock(s.to_string()))?,
+            None => 0,
+        };
+        let fullmove_number = match fields.get(5) {
+            Some(s) => match s.parse::<u32>() {
+                Ok(n) if n > 0 => n,
+                _ => return Err(FenError::Clock(s.to_string())),
+            },
+            None => 1,
+        };
+
+        game_from_board(
+            board,
+            white_to_move,
+            halfmove_clock,
+            fullmove_number,
+            castling,
+            en_passant,
+        )
+    }
+
+    // The player to move in FEN terms
+    // After mate or stalemate the turn does not pass, so it is the other player
+    pub(crate) fn side_to_move(&self) -> bool {
+        match self.mate || self.stalemate {
+            true => !self.white_turn,
+            false => self.white_turn,
+        }
+    }
+}
+
+// Builds a game around board with white_to_move to play, generating all moves
+// and setting mate or stalemate if the player to move has no moves
+// Fails if the position could not occur in a game, see find_kings,
+// check_castling and check_en_passant
+pub(crate) fn game_from_board(
+    board: Vec<Vec<Square>>,
+    white_to_move: bool,
+    halfmove_clock: u32,
+    fullmove_number: u32,
+    castling: CastlingRights,
+    en_passant: Option<Pos>,
+) -> Result<Game, FenError> {
+    let (w_king_pos, b_king_pos) = find_kings(&board)?;
+    check_castling(&board, castling)?;
+    if let Some(target) = en_passant {
+        check_en_passant(&board, target, white_to_move)?;
+    }
+    let chess960 = !castling.is_standard()
+        || (castling.any(true) && w_king_pos.file() != File::E)
+        || (castling.any(false) && b_king_pos.file() != File::E);
+
+    let mut game = Game {
+        boards: Boards {
+            board,
+            white_check_board: build_check_board(),
+            black_check_board: build_check_board(),
+        },
+        // do_turn leaves white_turn as the player who just moved until mate and
+        // stalemate have been checked, set it up the same way
+        white_turn: !white_to_move,
+        move_history: Vec::new(),
+        history: Vec::new(),
+        annotations: Vec::new(),
+        w_king_pos,
+        b_king_pos,
+        move_from: None,
+        move_to: None,
+        mate: false,
+        stalemate: false,
+        halfmove_clock,
+        fullmove_number,
+        castling,
+        en_passant,
+        chess960,
+        start_fen: String::new(),
+    };
+    game = game.find_all_moves();
+    game = game.clear_self_checking_moves();
+
+    // gives_check looks at the player to move, swap to check the other one
+    game.white_turn = white_to_move;
+    if game.gives_check() {
+        return Err(FenError::OpponentInCheck);
+    }
+    game.white_turn = !white_to_move;
+
+    if game.check_for_mate() {
+        game.mate = true;
+    } else if game.check_for_stalemate() {
+        game.stalemate = true;
+    } else {
+        game.white_turn = white_to_move;
+    }
+    game.start_fen = game.to_fen();
+    Ok(game)
+}
+
+// Returns the white and black king positions of board
+// Fails unless each side has exactly one king and no pawn is on a back rank
+pub(crate) fn find_kings(board: &[Vec<Square>]) -> Result<(Pos, Pos), FenError> {
+    let mut white_kings = Vec::new();
+    let mut black_kings = Vec::new();
+    for line in board {
+        for square in line {
+            if !square.occupied {
+                continue;
+            }
+            let pos = square.pos;
+            match (&square.piece.piece_type, square.piece.white) {
+                (PieceType::King, true) => white_kings.push(pos),
+                (PieceType::King, false) => black_kings.push(pos),
+                (PieceType::Pawn, _) if pos.rank() == Rank::R1 || pos.rank() == Rank::R8 => {
+                    return Err(FenError::PawnOnBackRank(pos))
+                }
+                _ => (),
+            }
+        }
+    }
+    if white_kings.len() != 1 || black_kings.len() != 1 {
+        return Err(FenError::KingCount {
+            white: white_kings.len(),
+            black: black_kings.len(),
+        });
+    }
+    Ok((white_kings.remove(0), black_kings.remove(0)))
+}
+
+// The piece placement field of a FEN, rank 8 first
+pub fn placement_to_fen(board: &[Vec<Square>]) -> String {
+    let mut fen = String::new();
+    for i in (0..8).rev() {
+        let mut empty = 0;
+        for square in &board[i] {
+            if !square.occupied {
+                empty += 1;
+                continue;
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+                empty = 0;
+            }
+            fen.push_str(&square.piece.to_string());
+        }
+        if empty > 0 {
+            fen.push_str(&empty.to_string());
+        }
+        if i > 0 {
+            fen.push('/');
+        }
+    }
+    fen
+}
+
+// Parses the piece placement field of a FEN into a board
+// No moves are generated for the pieces
+pub fn placement_from_fen(placement: &str) -> Result<Vec<Vec<Square>>, FenError> {
+    let error = || FenError::Placement(placement.to_string());
+    let ranks: Vec<&str> = placement.split('/').collect();
+    if ranks.len() != 8 {
+        return Err(error());
+    }
+
+    let mut board: Vec<Vec<Square>> = Vec::new();
+    for (rank, fen_rank) in Rank::all().zip(ranks.iter().rev()) {
+        let mut line: Vec<Square> = Vec::new();
+        for c in fen_rank.chars() {
+            if let Some(n) = c.to_digit(10) {
+                if n == 0 || line.len() + n as usize > 8 {
+                    return Err(error());
+                }
+                for _ in 0..n {
+                    line.push(new_square(
+                        next_pos(&line, rank),
+                        PieceType::Unoccupied,
+                        true,
+                    ));
+                }
+                continue;
+            }
+            let piece_type = match c.to_ascii_uppercase() {
+                'P' => PieceType::Pawn,
+                'N' => PieceType::Knight,
+                'B' => PieceType::Bishop,
+                'R' => PieceType::Rook,
+                'Q' => PieceType::Queen,
+                'K' => PieceType::King,
+                _ => return Err(error()),
+            };
+            if line.len() == 8 {
+                return Err(error());
+            }
+            line.push(new_square(
+                next_pos(&line, rank),
+                piece_type,
+                c.is_ascii_uppercase(),
+            ));
+        }
+        if line.len() != 8 {
+            return Err(error());
+        }
+        board.push(line);
+    }
+    Ok(board)
+}
+
+// The square after the last one in line
+fn next_pos(line: &[Square], rank: Rank) -> Pos {
+    let file = File::new(line.len() as u8).expect("a rank has 8 squares");
+    Pos::new(file, rank)
+}
+
+// A square holding a piece without moves, empty if piece_type is Unoccupied
+pub(crate) fn new_square(pos: Pos, piece_type: PieceType, white: bool) -> Square {
+    Square {
+        pos,
+        occupied: piece_type != PieceType::Unoccupied,
+        piece: Piece {
+            piece_type,
+            // Empty squares are white by convention, see square_to_unoccupied
+            white: white || piece_type == PieceType::Unoccupied,
+            moves: Vec::new(),
+        },
+    }
+}
+
+// Parses the castling field of a FEN against board
+// K and Q stand for the outermost rook on either side of the king, a file
+// letter for the rook on that file; uppercase for white, lowercase for black
+fn castling_from_fen(field: &str, board: &[Vec<Square>]) -> Result<CastlingRights, FenError> {
+    let error = || FenError::Castling(field.to_string());
+    let mut castling = CastlingRights::none();
+    if field == "-" {
+        return Ok(castling);
+    }
+    if field.is_empty() {
+        return Err(error());
+    }
+
+    for c in field.chars() {
+        let white = c.is_ascii_uppercase();
+        let rank = Rank::back_rank(white);
+        let king = File::all()
+            .find(|&file| {
+                let square = &board[rank.index()][file.index()];
+                square.occupied
+                    && square.piece.piece_type == PieceType::King
+                    && square.piece.white == white
+            })
+            .ok_or_else(error)?;
+        let rook = |file: &File| is_rook(board, Pos::new(*file, rank), white);
+
+        let (file, king_side) = match c.to_ascii_uppercase() {
+            'K' => (
+                File::all().rev().take_while(|&file| file > king).find(rook),
+                true,
+            ),
+            'Q' => (
+                File::all().take_while(|&file| file < king).find(rook),
+                false,
+            ),
+            letter => {
+                let file = letter.to_string().parse::<File>().map_err(|_| error())?;
+                (Some(file).filter(rook), file > king)
+            }
+        };
+        match file {
+            Some(file) if castling.rook(white, king_side).is_none() => {
+                castling.set(white, king_side, Some(file))
+            }
+            _ => return Err(error()),
+        }
+    }
+    Ok(castling)
+}
+
+// Every castling right needs the king on its back rank and a rook of the
+// player on the rook's file, on the side of the king the right is for
+pub(crate) fn check_castling(
+    board: &[Vec<Square>],
+    castling: CastlingRights,
+) -> Result<(), FenError> {
+    for white in [true, false] {
+        let rank = Rank::back_rank(white);
+        let king = File::all().find(|&file| {
+            let square = &board[rank.index()][file.index()];
+            square.occupied
+                && square.piece.piece_type == PieceType::King
+                && square.piece.white == white
+        });
+        for king_side in [true, false] {
+            let Some(file) = castling.rook(white, king_side) else {
+                continue;
+            };
+            let beside_king = king.is_some_and(|king| (file > king) == king_side);
+            if !beside_king || !is_rook(board, Pos::new(file, rank), white) {
+                let letter = file.to_char();
+                return Err(FenError::Castling(match white {
+                    true => letter.to_ascii_uppercase().to_string(),
+                    false => letter.to_string(),
+                }));
+            }
+        }
+    }
+    Ok(())
+}
+
+// The en passant square must be right behind a pawn of the player not to
+// move that could just have moved two squares
+pub(crate) fn check_en_passant(
+    board: &[Vec<Square>],
+    target: Pos,
+    white_to_move: bool,
+) -> Result<(), FenError> {
+    let (rank, forward) = match white_to_move {
+        true => (Rank::R6, 1),
+        false => (Rank::R3, -1),
+    };
+    let square = |pos: Option<Pos>| pos.map(|pos| &board[pos.rank().index()][pos.file().index()]);
+    let pawn = square(target.offset(0, -forward)).is_some_and(|square| {
+        square.occupied
+            && square.piece.piece_type == PieceType::Pawn
+            && square.piece.white != white_to_move
+    });
+    let empty = [Some(target), target.offset(0, forward)]
+        .into_iter()
+        .all(|pos| square(pos).is_some_and(|square| !square.occupied));
+    match target.rank() == rank && pawn && empty {
+        true => Ok(()),
+        false => Err(FenError::EnPassant(target.to_string())),
+    }
+}
+
+fn is_rook(board: &[Vec<Square>], pos: Pos, white: bool) -> bool {
+    let square = &board[pos.rank().index()][pos.file().index()];
+    square.occupied && square.piece.piece_type == PieceType::Rook && square.piece.white == white
+}
+
+fn is_en_passant_square(s: &str) -> bool {
+    matches!(s.parse::<Pos>(), Ok(pos) if s.as_bytes()[0].is_ascii_lowercase()
+        && (pos.rank() == Rank::R3 || pos.rank() == Rank::R6))
+}
@@ -0,0 +1,80 @@
+use crate::{Game, Move, PieceType, Pos};
+
+// A legal move for the player to move, with what it does
+// For castling, to is the square of the rook the king takes, see Move
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LegalMove {
+    pub from: Pos,
+    pub to: Pos,
+    // Takes an opponent's piece
+    pub capture: bool,
+    // Puts the opponent's king in check
+    pub check: bool,
+    // A pawn reaching the last rank
+    pub promotion: bool,
+    pub castle: bool,
+    pub en_passant: bool,
+}
+
+impl Game {
+    // All legal moves of the player to move, empty once the game is over
+    // A promotion is listed once, to a queen
+    pub fn legal_moves(&self) -> Vec<LegalMove> {
+        self.legal_moves_where(|_| true)
+    }
+
+    // The legal moves of the piece on from
+    // Empty if the square is empty, holds a piece of the player not to move,
+    // or the game is over
+    // A promotion is listed once, to a queen
+    pub fn legal_moves_from(&self, from: Pos) -> Vec<LegalMove> {
+        self.legal_moves_where(|mv| mv.from == from)
+    }
+
+    // Generates the moves once and keeps those matching keep
+    fn legal_moves_where(&self, keep: impl Fn(&Move) -> bool) -> Vec<LegalMove> {
+        self.moves()
+            .into_iter()
+            .filter(|mv| keep(mv) && matches!(mv.promotion, None | Some(PieceType::Queen)))
+            .map(|mv| LegalMove {
+                from: mv.from,
+                to: mv.to,
+                capture: mv.captured.is_some() || mv.en_passant,
+                check: self.move_gives_check(&mv),
+                promotion: mv.promotion.is_some(),
+                castle: mv.castle,
+                en_passant: mv.en_passant,
+            })
+            .collect()
+    }
+
+    // Plays mv on a copy and looks at whether the opponent's king is attacked
+    fn move_gives_check(&self, mv: &Move) -> bool {
+        let after = self.clone().make_move(mv).find_all_moves();
+        after.gives_check()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Game;
+
+    #[test]
+    fn legal_moves_of_start_position() {
+        let game = Game::new();
+        assert_eq!(game.legal_moves().len(), 20);
+        assert_eq!(game.legal_moves_from("g1".parse().unwrap()).len(), 2);
+        assert!(game.legal_moves_from("e7".parse().unwrap()).is_empty());
+    }
+
+    #[test]
+    fn legal_moves_match_moves() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let game = Game::from_fen(fen).unwrap();
+        assert_eq!(game.legal_moves().len(), game.moves().len());
+        let by_square: usize = crate::Pos::all()
+            .map(|from| game.legal_moves_from(from).len())
+            .sum();
+        assert_eq!(by_square, game.legal_moves().len());
+    }
+}
@@ -1,163 +1,237 @@
+use annotation::Annotation;
 use std::clone::Clone;
 use std::cmp::PartialEq;
+use std::fmt;
 use std::io;
 
+pub mod annotation;
+mod attacks;
+pub mod book;
+mod builder;
+mod castling;
+pub mod chess960;
+pub mod database;
+pub mod eco;
+pub mod fen;
+mod legal;
+pub mod mate;
+mod moves;
+pub mod pgn;
+mod pos;
+pub mod puzzle;
+pub mod query;
+pub mod render;
+pub mod report;
+pub mod san;
+pub mod search;
+mod see;
+#[cfg(feature = "serde")]
+mod serialize;
+#[cfg(feature = "syzygy")]
+pub mod syzygy;
+mod transposition;
+pub mod tree;
+mod validate;
+mod zobrist;
+
+pub use builder::BoardBuilder;
+pub use castling::CastlingRights;
+pub use legal::LegalMove;
+pub use moves::{Move, PROMOTION_PIECES};
+pub use pos::{File, Pos, PosError, Rank};
+pub use validate::PositionError;
+
 #[derive(Clone)]
 
 //
-// move_history not yet implemented
+// move_history holds every move played so far in SAN, history the same moves as Move
+// and annotations the comments, NAGs, highlights and arrows of each move
 pub struct Game {
     pub boards: Boards,
     pub white_turn: bool,
     pub move_history: Vec<String>,
-    w_king_pos: Move,
-    b_king_pos: Move,
-    move_from: String,
-    move_to: String,
+    pub history: Vec<Move>,
+    pub annotations: Vec<Annotation>,
+    w_king_pos: Pos,
+    b_king_pos: Pos,
+    move_from: Option<Pos>,
+    move_to: Option<Pos>,
     pub mate: bool,
     pub stalemate: bool,
+    // Plies since the last capture or pawn move, and the FEN move number
+    halfmove_clock: u32,
+    fullmove_number: u32,
+    castling: CastlingRights,
+    // The square a pawn skipped over with the last move, if it moved two squares
+    en_passant: Option<Pos>,
+    chess960: bool,
+    // The position before the first move of history, as FEN
+    start_fen: String,
 }
 
-impl Game {
+impl Default for Game {
+    fn default() -> Game {
+        Game::new()
+    }
+}
 
+impl Game {
     // Returns an instance of game, ready to be played :)
     pub fn new() -> Game {
+        Game::from_back_rank(STANDARD_BACK_RANK, CastlingRights::standard(), false)
+    }
+
+    // A game from the starting position with the given pieces on the back ranks
+    pub(crate) fn from_back_rank(
+        back_rank: [PieceType; 8],
+        castling: CastlingRights,
+        chess960: bool,
+    ) -> Game {
+        let king = File::all()
+            .find(|file| back_rank[file.index()] == PieceType::King)
+            .expect("the back rank has a king");
         let mut game = Game {
             boards: Boards {
-                board: build_board(),
+                board: build_board(&back_rank),
                 white_check_board: build_check_board(),
                 black_check_board: build_check_board(),
             },
             white_turn: true,
             move_history: Vec::new(),
-            w_king_pos: Move { x: 0, y: 4 },
-            b_king_pos: Move { x: 7, y: 4 },
-            move_from: String::new(),
-            move_to: String::new(),
+            history: Vec::new(),
+            annotations: Vec::new(),
+            w_king_pos: Pos::new(king, Rank::R1),
+            b_king_pos: Pos::new(king, Rank::R8),
+            move_from: None,
+            move_to: None,
             mate: false,
             stalemate: false,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            castling,
+            en_passant: None,
+            chess960,
+            start_fen: String::new(),
         };
         game = game.find_all_moves();
         game = game.clear_self_checking_moves();
+        game.start_fen = game.to_fen();
         game
     }
 
-    // Takes one String as input, checks if it is formatted 
-    // correctly to use as a move, returns false if not and vice versa
-    fn check_input(input: &String) -> bool {
-
-        if input.len() != 2 {
-            return false;
-        }
-
-        let x = input.chars().nth(1).unwrap() as u8;
-        let y = input.chars().nth(0).unwrap() as u8;
-        if x < 49 || x > 56 || y < 65 || y > 72 {
-            return false;
-        }
-        true
+    // Takes one string as input, checks if it is a square name
+    // such as "E4" or "e4", returns false if not and vice versa
+    pub fn check_input(input: &str) -> bool {
+        input.parse::<Pos>().is_ok()
     }
 
-    // Takes two strings as input 
-    // Sets move_from (in self) to first input and move_to (in self) to second input
-    // Input should be formatted as board positions 
-    // i.e. first a capital letter from A to H (incluive)
-    // and then a number from 1 to 8 (inclusic)
-    // and of length 2
-    pub fn input_move(&mut self, move_from: String, move_to: String) {
-        self.move_from = move_from;
-        self.move_to = move_to;
+    // Sets move_from (in self) to the first square and move_to (in self) to the second
+    // Square names are turned into squares with parse, e.g. "E2".parse::<Pos>()
+    pub fn input_move(&mut self, move_from: Pos, move_to: Pos) {
+        self.move_from = Some(move_from);
+        self.move_to = Some(move_to);
     }
-    
+
     // Does a turn, updates the board and checks for mate, returns self
     // If the game is mate, whose turn it is will not change
-    // Returns self early without doing anything if move_from and move_to 
-    // are improperly formatted or if the move is not valid
-    pub fn do_turn(mut self) -> Game{
-
-        /*
-        for i in 0..8 {
-            for j in 0..8 {
-                if self.boards.board[i][j].occupied {
-                    if !self.boards.board[i][j].piece.white {
-                        for k in 0..self.boards.board[i][j].piece.moves.len() {
-                            println!("in do_turn 1: {}, {}: {}", i , j, self.boards.board[i][j].piece.moves[k].move_to_string());
-                        }
-                    }
-                        
-                }
-            }
-        }*/
-        if !Self::check_input(&self.move_from) || !Self::check_input(&self.move_from) {
-            self.reset_moves();
-            return self;
+    // Returns self early without doing anything if no move has been input
+    // or if the move is not valid
+    // A pawn reaching the last rank becomes a queen, use play_move to pick another piece
+    pub fn do_turn(mut self) -> Game {
+        let mv = match (self.move_from, self.move_to) {
+            (Some(from), Some(to)) => self.find_move(from, to, None),
+            _ => None,
+        };
+        self.reset_moves();
+        match mv {
+            Some(mv) => self.apply_move(mv),
+            None => self,
         }
+    }
 
-        if !&self.check_move_valid(){
-            self.reset_moves();
-            return self;
+    // Does a turn with mv like do_turn, returns self unchanged if mv is not
+    // one of the legal moves
+    pub fn play_move(mut self, mv: Move) -> Game {
+        self.reset_moves();
+        match self.find_move(mv.from, mv.to, mv.promotion) == Some(mv) {
+            true => self.apply_move(mv),
+            false => self,
         }
+    }
+
+    // The turn itself, mv has to be legal
+    pub(crate) fn apply_move(mut self, mv: Move) -> Game {
+        let mut san = san::move_to_san(&self, &mv);
 
-        self = self.do_move();
+        self = self.make_move(&mv);
+        self.history.push(mv);
+        self.annotations.push(Annotation::default());
 
         self = self.find_all_moves();
         self = self.clear_self_checking_moves();
         self.reset_moves();
 
-        /*
-        for i in 0..8 {
-            for j in 0..8 {
-                if self.boards.board[i][j].occupied {
-                    if !self.boards.board[i][j].piece.white {
-                        for k in 0..self.boards.board[i][j].piece.moves.len() {
-                            println!("in do_turn 2: {}, {}: {}", i , j, self.boards.board[i][j].piece.moves[k].move_to_string());
-                        }
-                    }
-                        
-                }
-            }
-        }*/
-        
-
-        if(self.check_for_mate()) {
+        if self.check_for_mate() {
             self.mate = true;
-            return self;
+            san.push('#');
+        } else {
+            if self.gives_check() {
+                san.push('+');
+            }
+            self.stalemate = self.check_for_stalemate();
         }
+        self.move_history.push(san);
 
-        if self.check_for_stalemate(){
-            self.stalemate = true;
-            return self;
+        if !self.mate && !self.stalemate {
+            self.white_turn = !self.white_turn;
         }
-
-        self.white_turn = !self.white_turn;
+        debug_assert_eq!(self.validate(), Ok(()), "after {}", mv);
         self
     }
 
-    fn reset_moves(&mut self){
-        self.move_from = String::from("");
-        self.move_to = String::from("2");
+    // Returns true if the player whose turn it is attacks the opponent's king
+    fn gives_check(&self) -> bool {
+        if self.white_turn {
+            self.boards.attacked_by(true, self.b_king_pos)
+        } else {
+            self.boards.attacked_by(false, self.w_king_pos)
+        }
+    }
+
+    fn reset_moves(&mut self) {
+        self.move_from = None;
+        self.move_to = None;
     }
 
     // Returns clone of board
     pub fn get_board(&self) -> Vec<Vec<Square>> {
-        return self.boards.board.clone();
+        self.boards.board.clone()
     }
 
-    pub fn check_move_valid(&self) -> bool {
-        let mv_from: Move = string_to_move(&self.move_from);
-        let mv_to: Move = string_to_move(&self.move_to);
+    // Returns the square at pos
+    pub fn square(&self, pos: Pos) -> &Square {
+        self.boards.square(pos)
+    }
 
+    // Returns the last move played, if any
+    pub fn last_move(&self) -> Option<Move> {
+        self.history.last().copied()
+    }
 
-        if !self.boards.board[mv_from.x as usize][mv_from.y as usize].occupied {
-            return false;
-        }
-        if self.boards.board[mv_from.x as usize][mv_from.y as usize].piece.white != self.white_turn {
+    pub fn check_move_valid(&self) -> bool {
+        let (from, to) = match (self.move_from, self.move_to) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return false,
+        };
+        let square = self.square(from);
+
+        if !square.occupied {
             return false;
         }
-        if !self.boards.board[mv_from.x as usize][mv_from.y as usize].piece.moves.contains(&mv_to){
+        if square.piece.white != self.white_turn {
             return false;
         }
-        true
+        square.piece.moves.contains(&to)
     }
 
     pub fn run_game(mut self) -> Game {
@@ -165,10 +239,9 @@ impl Game {
         self = self.clear_self_checking_moves();
         loop {
             self.print_board();
-            self = self.find_all_moves();
             self = self.take_turn();
 
-            if (self.check_for_mate()) {
+            if self.check_for_mate() {
                 return self;
             }
 
@@ -189,35 +262,36 @@ impl Game {
         self = self.clear_self_checking_moves();
 
         while !valid_move {
-            let from = Game::take_input();
-            let mv_from = string_to_move(&from);
-            if self.boards.board[mv_from.x as usize][mv_from.y as usize].occupied {
-                if self.boards.board[mv_from.x as usize][mv_from.y as usize]
-                    .piece
-                    .white
-                    == self.white_turn
-                {
-                    let to = Game::take_input();
-                    let mv_to = string_to_move(&to);
-                    let mv_vec = &self.boards.board[mv_from.x as usize][mv_from.y as usize]
-                        .piece
-                        .moves;
-
-                    if self.boards.board[mv_from.x as usize][mv_from.y as usize]
-                        .piece
-                        .moves
-                        .contains(&mv_to)
-                    {
-                        self = self.do_move();
-                        valid_move = true;
-                    } else {
-                        println!("This is not a valid move!");
-                    }
-                } else {
-                    println!("This is not your piece!");
+            let from = match Game::take_input().parse::<Pos>() {
+                Ok(from) => from,
+                Err(e) => {
+                    println!("{}", e);
+                    continue;
                 }
-            } else {
+            };
+            if !self.square(from).occupied {
                 println!("This square does not have a piece!");
+                continue;
+            }
+            if self.square(from).piece.white != self.white_turn {
+                println!("This is not your piece!");
+                continue;
+            }
+
+            let to = match Game::take_input().parse::<Pos>() {
+                Ok(to) => to,
+                Err(e) => {
+                    println!("{}", e);
+                    continue;
+                }
+            };
+            if self.square(from).piece.moves.contains(&to) {
+                self.input_move(from, to);
+                self = self.do_move();
+                self.reset_moves();
+                valid_move = true;
+            } else {
+                println!("This is not a valid move!");
             }
         }
         self.print_board();
@@ -227,199 +301,146 @@ impl Game {
         self
     }
 
+    // Returns true if the player whose turn it is has mated the opponent
     pub fn check_for_mate(&self) -> bool {
-        let mut mate = true;
-        if self.white_turn {
-            if self.boards.white_check_board[self.b_king_pos.x as usize][self.b_king_pos.y as usize] {
-                for i in 0..8 {
-                    for j in 0..8 {
-                        if self.boards.board[i][j].occupied {
-                            if !self.boards.board[i][j].piece.white {
-                                if self.boards.board[i][j].piece.moves.len() != 0 {
-                                    mate = false;
-                                }else {
-                                    
-                                }
-                            }
-                        }
-                    }
-                }
-            } else {
-                mate = false;
-            }
-        } else {
-            if self.boards.black_check_board[self.w_king_pos.x as usize][self.w_king_pos.y as usize] {
-                for i in 0..8 {
-                    for j in 0..8 {
-                        if self.boards.board[i][j].occupied {
-                            if self.boards.board[i][j].piece.white {
-                                if self.boards.board[i][j].piece.moves.len() != 0 {
-                                    mate = false;
-                                }
-                            }
-                        }
-                    }
-                }
-            } else {
-                mate = false;
-            }
-        }
-        return mate;
+        self.gives_check() && !self.opponent_has_moves()
     }
 
+    // Returns true if the opponent of the player whose turn it is
+    // is not in check but has no moves
     pub fn check_for_stalemate(&self) -> bool {
-        let mut stalemate = true;
-        if self.white_turn{
-            if !self.boards.white_check_board[self.b_king_pos.x as usize][self.b_king_pos.y as usize] {
-                for i in 0..8 {
-                    for j in 0..8 {
-                        if self.boards.board[i][j].occupied {
-                            if !self.boards.board[i][j].piece.white {
-                                if self.boards.board[i][j].piece.moves.len() != 0 {
-                                    return false;
-                                }
-                            }
-                        }
-                    }
-                }
-            } else {
-                stalemate = false;
-            }
-        }else {
-            if !self.boards.black_check_board[self.w_king_pos.x as usize][self.w_king_pos.y as usize] {
-                for i in 0..8 {
-                    for j in 0..8 {
-                        if self.boards.board[i][j].occupied {
-                            if self.boards.board[i][j].piece.white {
-                                if self.boards.board[i][j].piece.moves.len() != 0 {
-                                    return false;
-                                }
-                            }
-                        }
-                    }
-                }
-            } else {
-                stalemate = false;
-            }
-        }
-        stalemate
+        !self.gives_check() && !self.opponent_has_moves()
+    }
+
+    fn opponent_has_moves(&self) -> bool {
+        self.boards.board.iter().flatten().any(|square| {
+            square.occupied
+                && square.piece.white != self.white_turn
+                && !square.piece.moves.is_empty()
+        })
     }
 
+    // Reads a line from stdin, trimmed
     pub fn take_input() -> String {
         let mut user_in = String::new();
-        let stdin = io::stdin();
         let _ = io::stdin().read_line(&mut user_in);
         println!("You chose: {}", user_in);
-        user_in
+        user_in.trim().to_string()
     }
 
+    // Removes the moves that would leave the mover's own king attacked,
+    // including king moves along the line of a checking slider and next to
+    // the other king
+    // En passant captures and castling are added here, since they depend on
+    // more than the pieces on the board
     pub fn clear_self_checking_moves(mut self) -> Game {
-        let mut boards_ = self.boards.clone();
-
-        let mut w_king = Move { x: 0, y: 0 };
-        let mut b_king = Move { x: 0, y: 0 };
-
-        for i in (0..8) {
-            for j in (0..8) {
-                match boards_.board[i][j].piece.piece_type {
-                    PieceType::King => {
-                        if boards_.board[i][j].piece.white {
-                            w_king = Move {
-                                x: i as u8,
-                                y: j as u8,
-                            }
-                        } else {
-                            b_king = Move {
-                                x: i as u8,
-                                y: j as u8,
-                            }
-                        }
-                    }
-                    _ => (),
-                }
+        for from in Pos::all() {
+            let square = self.square(from);
+            if !square.occupied {
+                continue;
             }
-        }
-        let boards_clone = boards_.clone();
-        for i in (0..8) {
-            for j in (0..8) {
-                if i == b_king.x as usize&& j == b_king.y as usize {
-                    continue;
+            let white_ = square.piece.white;
+            let moves_king = square.piece.piece_type == PieceType::King;
+            let en_passant = self.en_passant_capture(from);
+
+            let mut legal: Vec<Pos> = Vec::new();
+            for &to in square.piece.moves.iter().chain(en_passant.iter()) {
+                let mut board_ = self.boards.board.clone();
+                square_to_square(from, to, &mut board_);
+                square_to_unoccupied(from, &mut board_);
+                if Some(to) == en_passant {
+                    square_to_unoccupied(Pos::new(to.file(), from.rank()), &mut board_);
                 }
-                if i == w_king.x as usize && j == w_king.y as usize{
-                    continue;
-                }
-                
-                if boards_.board[i][j].occupied {
-                    boards_ = self.boards.clone();
-                    let mut indexes_to_pop: Vec<usize> = Vec::new();
-
-                    let white_ = boards_.board[i][j].piece.white;
-                    for k in 0..boards_.board[i][j].piece.moves.len() {
-                        //boards_ = boards_clone.clone();
-                        boards_ = self.boards.clone();
-                        boards_.board = move_piece(
-                            &Move {
-                                x: i as u8,
-                                y: j as u8,
-                            }
-                            .move_to_string(),
-                            &boards_.board[i][j].piece.moves[k].move_to_string(),
-                            boards_.board,
-                        );
-
-                        boards_ = find_all_moves(
-                            boards_.board,
-                            boards_.white_check_board,
-                            boards_.black_check_board,
-                        );
-
-                        if white_ {
-                            if boards_.black_check_board[w_king.x as usize][w_king.y as usize] {
-                                indexes_to_pop.push(k);
-                            }
-                        } else {
-                            if boards_.white_check_board[b_king.x as usize][b_king.y as usize] {
-                                indexes_to_pop.push(k);
-                                if i == 7 && j == 4 {
-                                }
-                            }
-                        }
-                    }
-                    for h in 0..indexes_to_pop.len() {
-                        for l in &self.boards.board[i][j].piece.moves {
-                        }
-
-                        self.boards.board[i][j]
-                            .piece
-                            .moves
-                            .remove(indexes_to_pop[h] - h);
-                    }
+
+                let king = if moves_king {
+                    to
+                } else {
+                    self.king_pos(white_)
+                };
+                if !attacks::is_attacked(&board_, king, !white_) {
+                    legal.push(to);
                 }
             }
+            if moves_king {
+                legal.extend(self.castling_moves(white_));
+            }
+            self.boards.square_mut(from).piece.moves = legal;
         }
         self
     }
 
-    pub fn do_move(mut self) -> Game {
-        let mut from = string_to_move(&self.move_from);
-        let mut to = string_to_move(&self.move_to);
+    // The en passant square if the pawn on from can take there
+    fn en_passant_capture(&self, from: Pos) -> Option<Pos> {
+        let target = self.en_passant?;
+        let piece = &self.square(from).piece;
+        let (forward, rank) = match piece.white {
+            true => (1, Rank::R6),
+            false => (-1, Rank::R3),
+        };
+        let captures = piece.piece_type == PieceType::Pawn
+            && target.rank() == rank
+            && [from.offset(-1, forward), from.offset(1, forward)].contains(&Some(target));
+        captures.then_some(target)
+    }
+
+    // Moves the piece on move_from to move_to without checking the move
+    // Does nothing if no move has been input
+    pub fn do_move(self) -> Game {
+        match (self.move_from, self.move_to) {
+            (Some(from), Some(to)) => {
+                let promotion = match self.promotes(from, to) {
+                    true => Some(PieceType::Queen),
+                    false => None,
+                };
+                let mv = self.build_move(from, to, promotion);
+                self.make_move(&mv)
+            }
+            _ => self,
+        }
+    }
 
-        if (from == self.w_king_pos) {
-            self.w_king_pos = to.clone();
-        } else if (from == self.b_king_pos) {
-            self.b_king_pos = to.clone();
+    // Plays mv on the board and updates the clocks and king positions, without
+    // checking the move or generating moves for the new position
+    pub(crate) fn make_move(mut self, mv: &Move) -> Game {
+        let (from, to) = (mv.from, mv.to);
+        match mv.piece == PieceType::Pawn || mv.captured.is_some() {
+            true => self.halfmove_clock = 0,
+            false => self.halfmove_clock += 1,
+        }
+        if !self.white_turn {
+            self.fullmove_number += 1;
         }
 
-    
-        square_to_square(&from, to, &mut self.boards.board);
-        square_to_unoccupied(&from, &mut self.boards.board);
+        let destination = mv.destination();
+        if from == self.w_king_pos {
+            self.w_king_pos = destination;
+        } else if from == self.b_king_pos {
+            self.b_king_pos = destination;
+        }
+        self.update_castling(mv);
+        self.en_passant = match mv.double_push {
+            true => from.offset(0, (to.rank().index() as i8 - from.rank().index() as i8) / 2),
+            false => None,
+        };
 
+        if mv.castle {
+            self.castle(mv);
+            return self;
+        }
+        square_to_square(from, to, &mut self.boards.board);
+        square_to_unoccupied(from, &mut self.boards.board);
+        if mv.en_passant {
+            square_to_unoccupied(Pos::new(to.file(), from.rank()), &mut self.boards.board);
+        }
+        if let Some(piece_type) = mv.promotion {
+            self.boards.square_mut(to).piece.piece_type = piece_type;
+        }
 
         self
     }
 
     pub fn find_all_moves(mut self) -> Game {
-        let mut boards_ = self.boards;
+        let boards_ = self.boards;
         self.boards = find_all_moves(
             boards_.board,
             boards_.white_check_board,
@@ -431,47 +452,76 @@ impl Game {
     pub fn print_board(&self) {
         let board = &self.boards.board;
 
-        for i in (0..board.len()).rev() {
+        for (i, line) in board.iter().enumerate().rev() {
             print!("{} ", i + 1);
-            for j in (0..board[i].len()) {
-                board[i][j].print_square();
+            for square in line {
+                square.print_square();
             }
             println!();
         }
         print!("    ");
-        for i in (1..9) {
-            print!("{:<5}", ((i + 64) as u8) as char);
+        for file in File::all() {
+            print!("{:<5}", file.to_char().to_ascii_uppercase());
         }
-        println!("");
+        println!();
     }
 
     pub fn print_check_board(&self, white: bool) {
-        let mut check_board;
-        if white {
-            check_board = &self.boards.white_check_board;
+        let check_board = if white {
+            &self.boards.white_check_board
         } else {
-            check_board = &self.boards.black_check_board;
-        }
+            &self.boards.black_check_board
+        };
 
-        for i in (0..check_board.len()).rev() {
+        for (i, line) in check_board.iter().enumerate().rev() {
             print!("{:?} ", i + 1);
-            for j in (0..check_board[i].len()) {
-                print!("[{:<5}]", check_board[i][j]);
+            for attacked in line {
+                print!("[{:<5}]", attacked);
             }
-            println!("");
+            println!();
         }
         print!("    ");
-        for i in (0..check_board[0].len()) {
-            print!("{:<7}", ((i + 65) as u8) as char);
+        for file in File::all() {
+            print!("{:<7}", file.to_char().to_ascii_uppercase());
         }
-        println!("");
+        println!();
     }
 }
 
-#[derive(Clone)]
+// The board diagram, see Boards
+impl fmt::Display for Game {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.boards)
+    }
+}
+
+impl fmt::Debug for Game {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Game")
+            .field("boards", &self.boards)
+            .field("white_turn", &self.white_turn)
+            .field("move_history", &self.move_history)
+            .field("history", &self.history)
+            .field("annotations", &self.annotations)
+            .field("w_king_pos", &self.w_king_pos)
+            .field("b_king_pos", &self.b_king_pos)
+            .field("move_from", &self.move_from)
+            .field("move_to", &self.move_to)
+            .field("mate", &self.mate)
+            .field("stalemate", &self.stalemate)
+            .field("halfmove_clock", &self.halfmove_clock)
+            .field("fullmove_number", &self.fullmove_number)
+            .field("castling", &self.castling)
+            .field("en_passant", &self.en_passant)
+            .field("chess960", &self.chess960)
+            .field("start_fen", &self.start_fen)
+            .finish()
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Square {
-    pub x: u8,
-    pub y: u8,
+    pub pos: Pos,
     pub piece: Piece,
     pub occupied: bool,
 }
@@ -499,6 +549,13 @@ impl Square {
     }
 }
 
+// The square name in algebraic notation, e.g. "e4"
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.pos)
+    }
+}
+
 #[derive(Clone)]
 pub struct Boards {
     pub board: Vec<Vec<Square>>,
@@ -506,14 +563,101 @@ pub struct Boards {
     pub black_check_board: Vec<Vec<bool>>,
 }
 
-#[derive(Clone)]
+impl Boards {
+    // Returns the square at pos
+    // board is indexed by rank first, so this is board[rank][file]
+    pub fn square(&self, pos: Pos) -> &Square {
+        &self.board[pos.rank().index()][pos.file().index()]
+    }
+
+    fn square_mut(&mut self, pos: Pos) -> &mut Square {
+        &mut self.board[pos.rank().index()][pos.file().index()]
+    }
+
+    // Looks pos up on the check board of white or black
+    fn attacked_by(&self, white: bool, pos: Pos) -> bool {
+        let check_board = if white {
+            &self.white_check_board
+        } else {
+            &self.black_check_board
+        };
+        check_board[pos.rank().index()][pos.file().index()]
+    }
+}
+
+// Board diagram with rank 8 at the top, white pieces in uppercase and
+// black pieces in lowercase:
+// 8 r n b q k b n r
+// ...
+//   a b c d e f g h
+impl fmt::Display for Boards {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, line) in self.board.iter().enumerate().rev() {
+            write!(f, "{}", i + 1)?;
+            for square in line {
+                write!(f, " {}", square.piece)?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f, "  a b c d e f g h")
+    }
+}
+
+// Each board is shown as one string per rank, rank 8 first
+// Attacked squares are marked with 'x' on the check boards
+impl fmt::Debug for Boards {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let board: Vec<String> = self
+            .board
+            .iter()
+            .rev()
+            .map(|line| line.iter().map(|square| square.piece.to_string()).collect())
+            .collect();
+        f.debug_struct("Boards")
+            .field("board", &board)
+            .field(
+                "white_check_board",
+                &check_board_rows(&self.white_check_board),
+            )
+            .field(
+                "black_check_board",
+                &check_board_rows(&self.black_check_board),
+            )
+            .finish()
+    }
+}
+
+fn check_board_rows(check_board: &[Vec<bool>]) -> Vec<String> {
+    check_board
+        .iter()
+        .rev()
+        .map(|line| {
+            line.iter()
+                .map(|&attacked| if attacked { 'x' } else { '.' })
+                .collect()
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug)]
 pub struct Piece {
     pub piece_type: PieceType,
     pub white: bool,
-    pub moves: Vec<Move>,
+    pub moves: Vec<Pos>,
 }
 
-#[derive(Clone, PartialEq, Eq)]
+// The piece letter, uppercase for white and lowercase for black, '.' if unoccupied
+impl fmt::Display for Piece {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let letter = self.piece_type.letter();
+        match self.white {
+            true => write!(f, "{}", letter),
+            false => write!(f, "{}", letter.to_ascii_lowercase()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum PieceType {
     Pawn,
     Rook,
@@ -524,27 +668,35 @@ pub enum PieceType {
     Unoccupied,
 }
 
-#[derive(Clone, PartialEq, Eq)]
-pub struct Move {
-    pub x: u8,
-    pub y: u8,
+impl PieceType {
+    // Uppercase letter of the piece as used in SAN and FEN, '.' for Unoccupied
+    pub fn letter(&self) -> char {
+        match self {
+            PieceType::Pawn => 'P',
+            PieceType::Rook => 'R',
+            PieceType::Knight => 'N',
+            PieceType::Bishop => 'B',
+            PieceType::Queen => 'Q',
+            PieceType::King => 'K',
+            PieceType::Unoccupied => '.',
+        }
+    }
 }
 
-fn build_check_board() -> Vec<Vec<bool>> {
-    let mut check_board: Vec<Vec<bool>> = Vec::new();
-    for i in 0..8 {
-        check_board.push(Vec::new());
-        for j in 0..8 {
-            check_board[i].push(false);
-        }
+impl fmt::Display for PieceType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.letter())
     }
-    check_board
+}
+
+fn build_check_board() -> Vec<Vec<bool>> {
+    vec![vec![false; 8]; 8]
 }
 
 pub fn reset_check_board(mut check_board: Vec<Vec<bool>>) -> Vec<Vec<bool>> {
-    for i in 0..check_board.len() {
-        for j in 0..check_board[i].len() {
-            check_board[i][j] = false;
+    for line in check_board.iter_mut() {
+        for attacked in line.iter_mut() {
+            *attacked = false;
         }
     }
     check_board
@@ -552,77 +704,55 @@ pub fn reset_check_board(mut check_board: Vec<Vec<bool>>) -> Vec<Vec<bool>> {
 
 pub fn find_all_moves(
     mut board: Vec<Vec<Square>>,
-    mut white_check_board: Vec<Vec<bool>>,
-    mut black_check_board: Vec<Vec<bool>>,
+    white_check_board: Vec<Vec<bool>>,
+    black_check_board: Vec<Vec<bool>>,
 ) -> Boards {
-    white_check_board = reset_check_board(white_check_board.to_owned());
-    black_check_board = reset_check_board(black_check_board.to_owned());
-    let mut king1 = Move { x: 0, y: 0 };
-    let mut king2 = Move { x: 0, y: 0 };
-    let mut king_found = false;
-
-    for i in 0..board.len() {
-        for j in 0..board[i].len() {
-            if board[i][j].occupied {
-                if board[i][j].piece.piece_type == PieceType::King {
-                    if !king_found {
-                        king1 = Move {
-                            x: i as u8,
-                            y: j as u8,
-                        };
-                        king_found = true;
-                    } else {
-                        king2 = Move {
-                            x: i as u8,
-                            y: j as u8,
-                        };
-                    }
-                } else {
-                    generate_moves(
-                        i,
-                        j,
-                        &mut board,
-                        &mut white_check_board,
-                        &mut black_check_board,
-                    );
-                }
-            }
+    let mut white_check_board = reset_check_board(white_check_board);
+    let mut black_check_board = reset_check_board(black_check_board);
+    let mut kings: Vec<Pos> = Vec::new();
+
+    for pos in Pos::all() {
+        let square = &board[pos.rank().index()][pos.file().index()];
+        if !square.occupied {
+            continue;
+        }
+        // Kings go last so they know every square the opponent attacks
+        if square.piece.piece_type == PieceType::King {
+            kings.push(pos);
+        } else {
+            generate_moves(
+                pos,
+                &mut board,
+                &mut white_check_board,
+                &mut black_check_board,
+            );
         }
     }
-    generate_moves(
-        king1.x as usize,
-        king1.y as usize,
-        &mut board,
-        &mut white_check_board,
-        &mut black_check_board,
-    );
-    generate_moves(
-        king2.x as usize,
-        king2.y as usize,
-        &mut board,
-        &mut white_check_board,
-        &mut black_check_board,
-    );
+    for king in kings {
+        generate_moves(
+            king,
+            &mut board,
+            &mut white_check_board,
+            &mut black_check_board,
+        );
+    }
 
-    let mut boards = Boards {
-        board: board,
-        white_check_board: white_check_board,
-        black_check_board: black_check_board,
-    };
-    boards
+    Boards {
+        board,
+        white_check_board,
+        black_check_board,
+    }
 }
 
-pub fn move_piece(from: &String, to: &String, mut board: Vec<Vec<Square>>) -> Vec<Vec<Square>> {
-    let mut move_from = string_to_move(&from);
-    let mut move_to = string_to_move(&to);
-
-    if board[move_from.x as usize][move_from.y as usize]
+// Moves the piece on from to to if to is one of its moves
+pub fn move_piece(from: Pos, to: Pos, mut board: Vec<Vec<Square>>) -> Vec<Vec<Square>> {
+    if board[from.rank().index()][from.file().index()]
         .piece
         .moves
-        .contains(&move_to)
+        .contains(&to)
     {
-        square_to_square(&move_from, move_to, &mut board);
-        square_to_unoccupied(&move_from, &mut board);
+        square_to_square(from, to, &mut board);
+        square_to_unoccupied(from, &mut board);
     } else {
         println!("Move not found");
     }
@@ -630,362 +760,225 @@ pub fn move_piece(from: &String, to: &String, mut board: Vec<Vec<Square>>) -> Ve
     board
 }
 
-pub fn square_to_unoccupied(pos: &Move, board: &mut Vec<Vec<Square>>) {
-    board[pos.x as usize][pos.y as usize].piece.piece_type = PieceType::Unoccupied;
-    board[pos.x as usize][pos.y as usize].piece.white = true;
-    board[pos.x as usize][pos.y as usize].piece.moves = Vec::new();
-    board[pos.x as usize][pos.y as usize].occupied = false;
+pub fn square_to_unoccupied(pos: Pos, board: &mut [Vec<Square>]) {
+    let square = &mut board[pos.rank().index()][pos.file().index()];
+    square.piece.piece_type = PieceType::Unoccupied;
+    square.piece.white = true;
+    square.piece.moves = Vec::new();
+    square.occupied = false;
 }
 
 pub fn generate_moves(
-    x: usize,
-    y: usize,
-    board: &mut Vec<Vec<Square>>,
-    mut white_check_board: &mut Vec<Vec<bool>>,
-    mut black_check_board: &mut Vec<Vec<bool>>,
+    pos: Pos,
+    board: &mut [Vec<Square>],
+    white_check_board: &mut [Vec<bool>],
+    black_check_board: &mut [Vec<bool>],
 ) {
-    match board[x as usize][y as usize].piece.piece_type {
-        PieceType::Pawn => {
-            board[x as usize][y as usize].piece.moves = moves_pawn(
-                &board[x as usize][y as usize],
-                board,
-                &mut white_check_board,
-                &mut black_check_board,
-            )
-        }
-        PieceType::Rook => {
-            board[x as usize][y as usize].piece.moves = moves_rook(
-                &board[x as usize][y as usize],
-                board,
-                &mut white_check_board,
-                &mut black_check_board,
-            )
-        }
-        PieceType::Knight => {
-            board[x as usize][y as usize].piece.moves = moves_knight(
-                &board[x as usize][y as usize],
-                board,
-                &mut white_check_board,
-                &mut black_check_board,
-            )
-        }
-        PieceType::Bishop => {
-            board[x as usize][y as usize].piece.moves = moves_bishop(
-                &board[x as usize][y as usize],
-                board,
-                &mut white_check_board,
-                &mut black_check_board,
-            )
-        }
-        PieceType::Queen => {
-            board[x as usize][y as usize].piece.moves = moves_queen(
-                &board[x as usize][y as usize],
-                board,
-                &mut white_check_board,
-                &mut black_check_board,
-            )
-        }
-        PieceType::King => {
-            board[x as usize][y as usize].piece.moves = moves_king(
-                &board[x as usize][y as usize],
-                board,
-                &mut white_check_board,
-                &mut black_check_board,
-            )
-        }
-        _ => (),
-    }
+    let square = &board[pos.rank().index()][pos.file().index()];
+    let moves = match square.piece.piece_type {
+        PieceType::Pawn => moves_pawn(square, board, white_check_board, black_check_board),
+        PieceType::Rook => moves_rook(square, board, white_check_board, black_check_board),
+        PieceType::Knight => moves_knight(square, board, white_check_board, black_check_board),
+        PieceType::Bishop => moves_bishop(square, board, white_check_board, black_check_board),
+        PieceType::Queen => moves_queen(square, board, white_check_board, black_check_board),
+        PieceType::King => moves_king(square, board, white_check_board, black_check_board),
+        PieceType::Unoccupied => return,
+    };
+    board[pos.rank().index()][pos.file().index()].piece.moves = moves;
 }
 
-pub fn print_check_board(mut check_board: &Vec<Vec<bool>>) {
-    for i in (0..check_board.len()).rev() {
-        for j in 0..check_board[i].len() {
-            print!("{:<7}", check_board[i][j]);
+pub fn print_check_board(check_board: &[Vec<bool>]) {
+    for line in check_board.iter().rev() {
+        for attacked in line {
+            print!("{:<7}", attacked);
         }
-        println!("");
+        println!();
     }
 }
 
-pub fn square_to_square(move_from: &Move, move_to: Move, board: &mut Vec<Vec<Square>>) {
-    match board[move_from.x as usize][move_from.y as usize]
-        .piece
-        .piece_type
-    {
-        PieceType::Pawn => {
-            board[move_to.x as usize][move_to.y as usize]
-                .piece
-                .piece_type = PieceType::Pawn
-        }
-        PieceType::Rook => {
-            board[move_to.x as usize][move_to.y as usize]
-                .piece
-                .piece_type = PieceType::Rook
-        }
-        PieceType::Knight => {
-            board[move_to.x as usize][move_to.y as usize]
-                .piece
-                .piece_type = PieceType::Knight
-        }
-        PieceType::Bishop => {
-            board[move_to.x as usize][move_to.y as usize]
-                .piece
-                .piece_type = PieceType::Bishop
-        }
-        PieceType::Queen => {
-            board[move_to.x as usize][move_to.y as usize]
-                .piece
-                .piece_type = PieceType::Queen
-        }
-        PieceType::King => {
-            board[move_to.x as usize][move_to.y as usize]
-                .piece
-                .piece_type = PieceType::King
-        }
-        PieceType::Unoccupied => {
-            board[move_to.x as usize][move_to.y as usize]
-                .piece
-                .piece_type = PieceType::Unoccupied
-        }
-    }
-    board[move_to.x as usize][move_to.y as usize].piece.white = board[move_from.x as usize]
-        [move_from.y as usize]
-        .piece
-        .white;
-    board[move_to.x as usize][move_to.y as usize].occupied =
-        board[move_from.x as usize][move_from.y as usize].occupied;
+// Copies the piece on move_from to move_to, move_from is left as it is
+pub fn square_to_square(move_from: Pos, move_to: Pos, board: &mut [Vec<Square>]) {
+    let from = board[move_from.rank().index()][move_from.file().index()].clone();
+    let to = &mut board[move_to.rank().index()][move_to.file().index()];
+    to.piece.piece_type = from.piece.piece_type;
+    to.piece.white = from.piece.white;
+    to.occupied = from.occupied;
 }
 
-pub fn print_board(mut board: &Vec<Vec<Square>>) {
-    for i in (0..board.len()).rev() {
-        for j in 0..board[i].len() {
-            board[i][j].print_square();
+pub fn print_board(board: &[Vec<Square>]) {
+    for line in board.iter().rev() {
+        for square in line {
+            square.print_square();
         }
-        println!("");
-    }
-}
-
-impl Move {
-    pub fn move_to_string(&self) -> String {
-        let mut s = String::new();
-        s.push((&self.y + 65) as char);
-        s.push((&self.x + 49) as char);
-
-        s
-    }
-
-    pub fn print_move(&self) {
-        print!("{:?},", &self.x);
-        println!("{:?}", &self.y);
+        println!();
     }
 }
 
-pub fn string_to_move(input: &String) -> Move {
-    let mut x: &char = &input.chars().nth(1).unwrap();
-    let mut y: &char = &input.chars().nth(0).unwrap();
-
-    let mut x = x.to_owned() as u8;
-    let mut y = y.to_owned() as u8;
-
-    x -= 49;
-    y -= 65;
-
-    Move { x: x, y: y }
+// Takes a square name such as "E4" or "e4" and returns it as a Pos
+pub fn string_to_move(input: &str) -> Result<Pos, PosError> {
+    input.parse::<Pos>()
 }
 
+// Checks whether the piece on square can move file_offset files and
+// rank_offset ranks, and marks the target on the player's check board
+// if the piece attacks it
+// Returns the target square if the move is possible, otherwise None
 pub fn check_move(
     square: &Square,
-    x: i8,
-    y: i8,
-    board: &Vec<Vec<Square>>,
-    white_check_board: &mut Vec<Vec<bool>>,
-    black_check_board: &mut Vec<Vec<bool>>,
-) -> bool {
-    let x_: i8 = (square.x as i8) + x;
-    let y_: i8 = (square.y as i8) + y;
-
-    if x_ <= 7 && x_ >= 0 && y_ >= 0 && y_ <= 7 {
-        match square.piece.piece_type {
-            PieceType::Pawn => {
-                if (y != 0 && square.piece.white) {
-                    white_check_board[x_ as usize][y_ as usize] = true;
-                } else if (y != 0) {
-                    black_check_board[x_ as usize][y_ as usize] = true;
-                }
-                let check_square: &Square = &board[x_ as usize][y_ as usize];
-                if (y == 0 && !check_square.occupied) {
-                    return true;
-                }else if y == 0 {
-                    return false;
-                }
-                if board[x_ as usize][y_ as usize].occupied {
-                    if square.piece.white == check_square.piece.white {
-                        return false;
-                    }
+    file_offset: i8,
+    rank_offset: i8,
+    board: &[Vec<Square>],
+    white_check_board: &mut [Vec<bool>],
+    black_check_board: &mut [Vec<bool>],
+) -> Option<Pos> {
+    let target = square.pos.offset(file_offset, rank_offset)?;
+    let (rank, file) = (target.rank().index(), target.file().index());
+    let check_square = &board[rank][file];
+    let white = square.piece.white;
+    let (check_board, opponent_check_board) = match white {
+        true => (white_check_board, black_check_board),
+        false => (black_check_board, white_check_board),
+    };
 
-                    match check_square.piece.piece_type {
-                        PieceType::King => return false,
-                        _ => return true,
-                    }
-                }
-                return false;
+    match square.piece.piece_type {
+        PieceType::Pawn => {
+            // Pawns only move straight ahead onto empty squares and only attack diagonally
+            if file_offset == 0 {
+                return match check_square.occupied {
+                    true => None,
+                    false => Some(target),
+                };
             }
-            PieceType::King => {
-                if square.piece.white && black_check_board[x_ as usize][y_ as usize] {
-                    return false;
-                }
-                if !square.piece.white && white_check_board[x_ as usize][y_ as usize] {
-                    return false;
-                }
-                if square.piece.white {
-                    white_check_board[x_ as usize][y_ as usize] = true;
-                } else {
-                    black_check_board[x_ as usize][y_ as usize] = true;
-                }
-                let check_square: &Square = &board[x_ as usize][y_ as usize];
-                if board[x_ as usize][y_ as usize].occupied {
-                    if square.piece.white == check_square.piece.white {
-                        return false;
-                    }
-
-                    match check_square.piece.piece_type {
-                        PieceType::King => return false,
-                        _ => return true,
-                    }
-                }
-                return true;
+            check_board[rank][file] = true;
+            if !check_square.occupied {
+                return None;
             }
-
-            _ => {
-                if square.piece.white {
-                    white_check_board[x_ as usize][y_ as usize] = true;
-                } else {
-                    black_check_board[x_ as usize][y_ as usize] = true;
-                }
-                let check_square: &Square = &board[x_ as usize][y_ as usize];
-                if board[x_ as usize][y_ as usize].occupied {
-                    if square.piece.white == check_square.piece.white {
-                        return false;
-                    }
-
-                    match check_square.piece.piece_type {
-                        PieceType::King => return false,
-                        _ => return true,
-                    }
-                }
-                return true;
+        }
+        PieceType::King => {
+            if opponent_check_board[rank][file] {
+                return None;
             }
+            check_board[rank][file] = true;
         }
+        _ => check_board[rank][file] = true,
     }
-    false
+
+    if check_square.occupied
+        && (check_square.piece.white == white || check_square.piece.piece_type == PieceType::King)
+    {
+        return None;
+    }
+    Some(target)
 }
 
-fn moves_pawn(
+// Moves along each direction until the edge of the board or a piece is reached
+// A piece of the opponent's ends the line as a capture
+fn sliding_moves(
     square: &Square,
-    board: &Vec<Vec<Square>>,
-    mut white_check_board: &mut Vec<Vec<bool>>,
-    mut black_check_board: &mut Vec<Vec<bool>>,
-) -> Vec<Move> {
-    let mut moves: Vec<Move> = Vec::new();
-    let x = square.x;
-    let y = square.y;
-
-    if square.piece.white {
-        if x == 1 && !board[2 as usize][y as usize].occupied{
-            if check_move(
-                &square,
-                2,
-                0,
+    directions: &[(i8, i8)],
+    board: &[Vec<Square>],
+    white_check_board: &mut [Vec<bool>],
+    black_check_board: &mut [Vec<bool>],
+) -> Vec<Pos> {
+    let mut moves: Vec<Pos> = Vec::new();
+    for &(file_step, rank_step) in directions {
+        for k in 1..8 {
+            match check_move(
+                square,
+                file_step * k,
+                rank_step * k,
                 board,
-                &mut white_check_board,
-                &mut black_check_board,
+                white_check_board,
+                black_check_board,
             ) {
-                moves.push(Move { x: x + 2, y: y });
+                Some(target) => {
+                    moves.push(target);
+                    if board[target.rank().index()][target.file().index()].occupied {
+                        break;
+                    }
+                }
+                None => break,
             }
         }
-        if check_move(
-            &square,
-            1,
-            -1,
-            board,
-            &mut white_check_board,
-            &mut black_check_board,
-        ) {
-            moves.push(Move { x: x + 1, y: y - 1 });
-        }
-        if check_move(
-            &square,
-            1,
-            0,
-            board,
-            &mut white_check_board,
-            &mut black_check_board,
-        ) {
-            moves.push(Move { x: x + 1, y: y });
-        }
-        if check_move(
-            &square,
-            1,
-            1,
-            board,
-            &mut white_check_board,
-            &mut black_check_board,
-        ) {
-            moves.push(Move { x: x + 1, y: y + 1 });
-        }
-    } else {
-        if x == 6 && !board[5 as usize][y as usize].occupied {
-            if check_move(
-                &square,
-                -2,
-                0,
+    }
+    moves
+}
+
+// Steps once to each of the offsets
+fn stepping_moves(
+    square: &Square,
+    offsets: &[(i8, i8)],
+    board: &[Vec<Square>],
+    white_check_board: &mut [Vec<bool>],
+    black_check_board: &mut [Vec<bool>],
+) -> Vec<Pos> {
+    offsets
+        .iter()
+        .filter_map(|&(file_offset, rank_offset)| {
+            check_move(
+                square,
+                file_offset,
+                rank_offset,
                 board,
-                &mut white_check_board,
-                &mut black_check_board,
-            ) {
-                moves.push(Move {
-                    x: (x - 2) as u8,
-                    y: y,
-                });
-            }
-        }
-        if check_move(
-            &square,
-            -1,
-            -1,
-            board,
-            &mut white_check_board,
-            &mut black_check_board,
-        ) {
-            moves.push(Move {
-                x: (x - 1) as u8,
-                y: (y - 1) as u8,
-            });
-        }
-        if check_move(
-            &square,
-            -1,
+                white_check_board,
+                black_check_board,
+            )
+        })
+        .collect()
+}
+
+const ROOK_DIRECTIONS: [(i8, i8); 4] = [(0, 1), (0, -1), (1, 0), (-1, 0)];
+const BISHOP_DIRECTIONS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+const KING_OFFSETS: [(i8, i8); 8] = [
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+    (-1, 0),
+    (1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+const KNIGHT_OFFSETS: [(i8, i8); 8] = [
+    (1, 2),
+    (-1, 2),
+    (1, -2),
+    (-1, -2),
+    (2, 1),
+    (-2, 1),
+    (2, -1),
+    (-2, -1),
+];
+
+fn moves_pawn(
+    square: &Square,
+    board: &[Vec<Square>],
+    white_check_board: &mut [Vec<bool>],
+    black_check_board: &mut [Vec<bool>],
+) -> Vec<Pos> {
+    let (forward, start_rank) = match square.piece.white {
+        true => (1, Rank::R2),
+        false => (-1, Rank::R7),
+    };
+    let mut moves = stepping_moves(
+        square,
+        &[(-1, forward), (0, forward), (1, forward)],
+        board,
+        white_check_board,
+        black_check_board,
+    );
+
+    // Two squares ahead from the starting rank, if the square in between is empty too
+    if square.pos.rank() == start_rank && moves.iter().any(|to| to.file() == square.pos.file()) {
+        if let Some(to) = check_move(
+            square,
             0,
+            2 * forward,
             board,
-            &mut white_check_board,
-            &mut black_check_board,
-        ) {
-            moves.push(Move {
-                x: (x - 1) as u8,
-                y: y,
-            });
-        }
-        if check_move(
-            &square,
-            -1,
-            1,
-            board,
-            &mut white_check_board,
-            &mut black_check_board,
+            white_check_board,
+            black_check_board,
         ) {
-            moves.push(Move {
-                x: (x - 1) as u8,
-                y: (y + 1) as u8,
-            });
+            moves.push(to);
         }
     }
     moves
@@ -993,527 +986,166 @@ fn moves_pawn(
 
 fn moves_rook(
     square: &Square,
-    board: &Vec<Vec<Square>>,
-    mut white_check_board: &mut Vec<Vec<bool>>,
-    mut black_check_board: &mut Vec<Vec<bool>>,
-) -> Vec<Move> {
-    let mut moves: Vec<Move> = Vec::new();
-    let x: u8 = square.x;
-    let y: u8 = square.y;
-    let p: i8 = -1;
-
-    for i in 0..2 {
-        let mut boolx = true;
-        let mut booly = true;
-        for j in 1..8 {
-            let k = j * p.pow(i);
-
-            if boolx
-                && check_move(
-                    &square,
-                    k,
-                    0 as i8,
-                    &board,
-                    &mut white_check_board,
-                    &mut black_check_board,
-                )
-            {
-                moves.push(Move {
-                    x: (x as i8 + k) as u8,
-                    y: y,
-                });
-            } else {
-                boolx = false;
-            }
-            if x as i8 + k <= 7 && x as i8 + k >= 0 {
-                if board[(x as i8 + k) as usize][y as usize].occupied {
-                    boolx = false;
-                }
-            }
-            if booly
-                && check_move(
-                    &square,
-                    0,
-                    k,
-                    &board,
-                    &mut white_check_board,
-                    &mut black_check_board,
-                )
-            {
-                moves.push(Move {
-                    x: x,
-                    y: (y as i8 + k) as u8,
-                });
-            } else {
-                booly = false;
-            }
-            if y as i8 + k <= 7 && y as i8 + k >= 0 {
-                if board[(x as usize) as usize][(y as i8 + k) as usize].occupied {
-                    booly = false;
-                }
-            }
-        }
-    }
-
-    moves
+    board: &[Vec<Square>],
+    white_check_board: &mut [Vec<bool>],
+    black_check_board: &mut [Vec<bool>],
+) -> Vec<Pos> {
+    sliding_moves(
+        square,
+        &ROOK_DIRECTIONS,
+        board,
+        white_check_board,
+        black_check_board,
+    )
 }
 
 fn moves_knight(
     square: &Square,
-    board: &Vec<Vec<Square>>,
-    mut white_check_board: &mut Vec<Vec<bool>>,
-    mut black_check_board: &mut Vec<Vec<bool>>,
-) -> Vec<Move> {
-    let mut moves: Vec<Move> = Vec::new();
-    let x = square.x;
-    let y = square.y;
-    let p: i8 = -1;
-
-    for i in 0..2 {
-        for j in 0..2 {
-            let x_: i8 = 2 * p.pow(i);
-            let y_: i8 = p.pow(j);
-            if check_move(
-                &square,
-                x_,
-                y_,
-                &board,
-                &mut white_check_board,
-                &mut black_check_board,
-            ) {
-                moves.push(Move {
-                    x: (x as i8 + x_) as u8,
-                    y: (y as i8 + y_) as u8,
-                });
-            }
-        }
-    }
-
-    for i in 0..2 {
-        for j in 0..2 {
-            let x_: i8 = p.pow(i);
-            let y_: i8 = 2 * p.pow(j);
-            if check_move(
-                &square,
-                x_,
-                y_,
-                &board,
-                &mut white_check_board,
-                &mut black_check_board,
-            ) {
-                moves.push(Move {
-                    x: (x as i8 + x_) as u8,
-                    y: (y as i8 + y_) as u8,
-                });
-            }
-        }
-    }
-    moves
+    board: &[Vec<Square>],
+    white_check_board: &mut [Vec<bool>],
+    black_check_board: &mut [Vec<bool>],
+) -> Vec<Pos> {
+    stepping_moves(
+        square,
+        &KNIGHT_OFFSETS,
+        board,
+        white_check_board,
+        black_check_board,
+    )
 }
 
 fn moves_bishop(
     square: &Square,
-    board: &Vec<Vec<Square>>,
-    mut white_check_board: &mut Vec<Vec<bool>>,
-    mut black_check_board: &mut Vec<Vec<bool>>,
-) -> Vec<Move> {
-    let mut moves: Vec<Move> = Vec::new();
-    let x = square.x;
-    let y = square.y;
-    let p: i8 = -1;
-
-    for i in 0..2 {
-        for j in 0..2 {
-            for k in 1..8 {
-                let x_ = k * p.pow(i);
-                let y_ = k * p.pow(j);
-                if check_move(
-                    &square,
-                    x_,
-                    y_,
-                    &board,
-                    &mut white_check_board,
-                    &mut black_check_board,
-                ) {
-                    moves.push(Move {
-                        x: (x as i8 + x_) as u8,
-                        y: (y as i8 + y_) as u8,
-                    });
-                } else {
-                    break;
-                }
-                if x as i8 + x_ <= 7 && x as i8 + k >= 0 && y as i8 + y_ >= 0 && y as i8 + y_ <= 7 {
-                    if board[(x as i8 + x_) as usize][(y as i8 + y_) as usize].occupied {
-                        break;
-                    }
-                }
-            }
-        }
-    }
-    moves
+    board: &[Vec<Square>],
+    white_check_board: &mut [Vec<bool>],
+    black_check_board: &mut [Vec<bool>],
+) -> Vec<Pos> {
+    sliding_moves(
+        square,
+        &BISHOP_DIRECTIONS,
+        board,
+        white_check_board,
+        black_check_board,
+    )
 }
 
 fn moves_queen(
     square: &Square,
-    board: &Vec<Vec<Square>>,
-    mut white_check_board: &mut Vec<Vec<bool>>,
-    mut black_check_board: &mut Vec<Vec<bool>>,
-) -> Vec<Move> {
-    let mut moves: Vec<Move> = Vec::new();
-    let x = square.x;
-    let y = square.y;
-    let p: i8 = -1;
-
-    for i in 0..2 {
-        for j in 0..2 {
-            for k in 1..8 {
-                let x_ = k * p.pow(i);
-                let y_ = k * p.pow(j);
-                if check_move(
-                    &square,
-                    x_,
-                    y_,
-                    &board,
-                    &mut white_check_board,
-                    &mut black_check_board,
-                ) {
-                    moves.push(Move {
-                        x: (x as i8 + x_) as u8,
-                        y: (y as i8 + y_) as u8,
-                    });
-                } else {
-                    break;
-                }
-                if x as i8 + x_ <= 7 && x as i8 + x_ >= 0 && y as i8 + y_ >= 0 && y as i8 + y_ <= 7
-                {
-                    if board[(x as i8 + x_) as usize][(y as i8 + y_) as usize].occupied {
-                        break;
-                    }
-                }
-            }
-        }
-    }
-
-    for i in 0..2 {
-        let mut boolx = true;
-        let mut booly = true;
-        for j in 1..8 {
-            let k_ = j * p.pow(i);
-            if boolx
-                && check_move(
-                    &square,
-                    k_,
-                    0,
-                    &board,
-                    &mut white_check_board,
-                    &mut black_check_board,
-                )
-            {
-                moves.push(Move {
-                    x: (x as i8 + k_) as u8,
-                    y: y,
-                });
-            } else {
-                boolx = false;
-            }
-            if booly
-                && check_move(
-                    &square,
-                    0,
-                    k_,
-                    &board,
-                    &mut white_check_board,
-                    &mut black_check_board,
-                )
-            {
-                if board[square.x as usize][(square.y as i8 + k_) as usize].occupied {
-                    booly = false;
-                }
-                moves.push(Move {
-                    x: x,
-                    y: (y as i8 + k_) as u8,
-                });
-            } else {
-                booly = false;
-            }
-        }
-    }
+    board: &[Vec<Square>],
+    white_check_board: &mut [Vec<bool>],
+    black_check_board: &mut [Vec<bool>],
+) -> Vec<Pos> {
+    let mut moves = sliding_moves(
+        square,
+        &BISHOP_DIRECTIONS,
+        board,
+        white_check_board,
+        black_check_board,
+    );
+    moves.extend(sliding_moves(
+        square,
+        &ROOK_DIRECTIONS,
+        board,
+        white_check_board,
+        black_check_board,
+    ));
     moves
 }
 
 fn moves_king(
     square: &Square,
-    board: &Vec<Vec<Square>>,
-    mut white_check_board: &mut Vec<Vec<bool>>,
-    mut black_check_board: &mut Vec<Vec<bool>>,
-) -> Vec<Move> {
-    let mut moves: Vec<Move> = Vec::new();
-    let x = square.x;
-    let y = square.y;
-
-    for i in -1i8..2 {
-        if check_move(
-            &square,
-            i,
-            1,
-            &board,
-            &mut white_check_board,
-            &mut black_check_board,
-        ) {
-            moves.push(Move {
-                x: (x as i8 + i) as u8,
-                y: y + 1,
-            });
-        }
-        if check_move(
-            &square,
-            i,
-            -1,
-            &board,
-            &mut white_check_board,
-            &mut black_check_board,
-        ) {
-            moves.push(Move {
-                x: (x as i8 + i) as u8,
-                y: y + 1,
-            });
-        }
-    }
-
-    if check_move(
-        &square,
-        1,
-        0,
-        &board,
-        &mut white_check_board,
-        &mut black_check_board,
-    ) {
-        moves.push(Move { x: x + 1, y: y });
-    }
-    if check_move(
-        &square,
-        -1,
-        0,
-        &board,
-        &mut white_check_board,
-        &mut black_check_board,
-    ) {
-        moves.push(Move { x: x - 1, y: y });
-    }
-    moves
+    board: &[Vec<Square>],
+    white_check_board: &mut [Vec<bool>],
+    black_check_board: &mut [Vec<bool>],
+) -> Vec<Pos> {
+    stepping_moves(
+        square,
+        &KING_OFFSETS,
+        board,
+        white_check_board,
+        black_check_board,
+    )
 }
 
-fn build_board() -> Vec<Vec<Square>> {
-    let mut board: Vec<Vec<Square>> = Vec::new();
-    for i in 0..8 {
-        board.push(build_line(i));
-    }
-    board
+// The back rank of the standard starting position
+const STANDARD_BACK_RANK: [PieceType; 8] = [
+    PieceType::Rook,
+    PieceType::Knight,
+    PieceType::Bishop,
+    PieceType::Queen,
+    PieceType::King,
+    PieceType::Bishop,
+    PieceType::Knight,
+    PieceType::Rook,
+];
+
+fn build_board(back_rank: &[PieceType; 8]) -> Vec<Vec<Square>> {
+    Rank::all()
+        .map(|rank| build_line(rank, back_rank))
+        .collect()
 }
 
-fn build_line(line: u8) -> Vec<Square> {
-    let mut line_vec: Vec<Square> = Vec::new();
-    if line == 0 {
-        line_vec.push(Square {
-            x: 0,
-            y: 0,
-            piece: Piece {
-                piece_type: PieceType::Rook,
-                white: true,
-                moves: Vec::new(),
-            },
-            occupied: true,
-        });
-        line_vec.push(Square {
-            x: 0,
-            y: 1,
-            piece: Piece {
-                piece_type: PieceType::Knight,
-                white: true,
-                moves: Vec::new(),
-            },
-            occupied: true,
-        });
-        line_vec.push(Square {
-            x: 0,
-            y: 2,
-            piece: Piece {
-                piece_type: PieceType::Bishop,
-                white: true,
-                moves: Vec::new(),
-            },
-            occupied: true,
-        });
-        line_vec.push(Square {
-            x: 0,
-            y: 3,
-            piece: Piece {
-                piece_type: PieceType::Queen,
-                white: true,
-                moves: Vec::new(),
-            },
-            occupied: true,
-        });
-        line_vec.push(Square {
-            x: 0,
-            y: 4,
-            piece: Piece {
-                piece_type: PieceType::King,
-                white: true,
-                moves: Vec::new(),
-            },
-            occupied: true,
-        });
-        line_vec.push(Square {
-            x: 0,
-            y: 5,
-            piece: Piece {
-                piece_type: PieceType::Bishop,
-                white: true,
-                moves: Vec::new(),
-            },
-            occupied: true,
-        });
-        line_vec.push(Square {
-            x: 0,
-            y: 6,
-            piece: Piece {
-                piece_type: PieceType::Knight,
-                white: true,
-                moves: Vec::new(),
-            },
-            occupied: true,
-        });
-        line_vec.push(Square {
-            x: 0,
-            y: 7,
-            piece: Piece {
-                piece_type: PieceType::Rook,
-                white: true,
-                moves: Vec::new(),
-            },
-            occupied: true,
-        });
-    } else if line == 1 {
-        for i in 0..8 {
-            line_vec.push(Square {
-                x: 1,
-                y: i,
+fn build_line(rank: Rank, back_rank: &[PieceType; 8]) -> Vec<Square> {
+    Pos::rank_squares(rank)
+        .map(|pos| {
+            let piece_type = match rank {
+                Rank::R1 | Rank::R8 => back_rank[pos.file().index()],
+                Rank::R2 | Rank::R7 => PieceType::Pawn,
+                _ => PieceType::Unoccupied,
+            };
+            Square {
+                pos,
+                occupied: piece_type != PieceType::Unoccupied,
                 piece: Piece {
-                    piece_type: PieceType::Pawn,
-                    white: true,
+                    piece_type,
+                    white: rank <= Rank::R4,
                     moves: Vec::new(),
                 },
-                occupied: true,
-            });
-        }
-    } else if line >= 2 && line <= 5 {
-        for i in 0..8 {
-            line_vec.push(Square {
-                x: line,
-                y: i,
-                piece: Piece {
-                    piece_type: PieceType::Unoccupied,
-                    white: true,
-                    moves: Vec::new(),
-                },
-                occupied: false,
-            });
-        }
-    } else if line == 6 {
-        for i in 0..8 {
-            line_vec.push(Square {
-                x: 6,
-                y: i,
-                piece: Piece {
-                    piece_type: PieceType::Pawn,
-                    white: false,
-                    moves: Vec::new(),
-                },
-                occupied: true,
-            });
-        }
-    } else if line == 7 {
-        line_vec.push(Square {
-            x: 7,
-            y: 0,
-            piece: Piece {
-                piece_type: PieceType::Rook,
-                white: false,
-                moves: Vec::new(),
-            },
-            occupied: true,
-        });
-        line_vec.push(Square {
-            x: 7,
-            y: 1,
-            piece: Piece {
-                piece_type: PieceType::Knight,
-                white: false,
-                moves: Vec::new(),
-            },
-            occupied: true,
-        });
-        line_vec.push(Square {
-            x: 7,
-            y: 2,
-            piece: Piece {
-                piece_type: PieceType::Bishop,
-                white: false,
-                moves: Vec::new(),
-            },
-            occupied: true,
-        });
-        line_vec.push(Square {
-            x: 7,
-            y: 3,
-            piece: Piece {
-                piece_type: PieceType::Queen,
-                white: false,
-                moves: Vec::new(),
-            },
-            occupied: true,
-        });
-        line_vec.push(Square {
-            x: 7,
-            y: 4,
-            piece: Piece {
-                piece_type: PieceType::King,
-                white: false,
-                moves: Vec::new(),
-            },
-            occupied: true,
-        });
-        line_vec.push(Square {
-            x: 7,
-            y: 5,
-            piece: Piece {
-                piece_type: PieceType::Bishop,
-                white: false,
-                moves: Vec::new(),
-            },
-            occupied: true,
-        });
-        line_vec.push(Square {
-            x: 7,
-            y: 6,
-            piece: Piece {
-                piece_type: PieceType::Knight,
-                white: false,
-                moves: Vec::new(),
-            },
-            occupied: true,
-        });
-        line_vec.push(Square {
-            x: 7,
-            y: 7,
-            piece: Piece {
-                piece_type: PieceType::Rook,
-                white: false,
-                moves: Vec::new(),
-            },
-            occupied: true,
-        });
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The squares the piece on from can move to, sorted
+    fn destinations(fen: &str, from: &str) -> Vec<String> {
+        let game = Game::from_fen(fen).unwrap();
+        let mut squares: Vec<String> = game
+            .legal_moves_from(from.parse().unwrap())
+            .iter()
+            .map(|mv| mv.to.to_string())
+            .collect();
+        squares.sort();
+        squares
+    }
+
+    // King moves one file to the left were once recorded one file to the right
+    #[test]
+    fn king_moves_every_way() {
+        assert_eq!(
+            destinations("8/8/8/8/4K3/8/8/k7 w - - 0 1", "e4"),
+            ["d3", "d4", "d5", "e3", "e5", "f3", "f4", "f5"]
+        );
+        assert_eq!(
+            destinations("7k/8/8/8/8/8/8/K7 w - - 0 1", "a1"),
+            ["a2", "b1", "b2"]
+        );
+    }
+
+    // Queens once slid vertically through the pieces in their way
+    #[test]
+    fn queen_stops_at_pieces() {
+        assert!(destinations(crate::fen::START_FEN, "d1").is_empty());
+        assert_eq!(
+            destinations("4k3/8/3p4/8/3Q4/8/3P4/4K3 w - - 0 1", "d4")
+                .iter()
+                .filter(|square| square.starts_with('d'))
+                .collect::<Vec<_>>(),
+            ["d3", "d5", "d6"]
+        );
+        assert_eq!(Game::new().perft(3), 8902);
     }
-    return line_vec;
 }
@@ -1,178 +1,208 @@
-mod lib;
-use lib::*;
+use simonsev_chess::render::{render_board, RenderOptions};
+use simonsev_chess::search::{self, Limit};
+use simonsev_chess::{pgn, Game, PieceType, Pos};
+use std::io::{self, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Usage: simonsev-chess [file.pgn]
+// The game is written to the given file (game.pgn by default) on exit
 fn main() {
-    let mut game = Game::new();
-
+    let pgn_path = std::env::args().nth(1).unwrap_or(String::from("game.pgn"));
+
+    println!("1) Two players");
+    println!("2) Play against the computer");
+    let against_computer = loop {
+        match prompt("Choose a mode: ").as_str() {
+            "1" => break false,
+            "2" => break true,
+            _ => println!("Please enter 1 or 2"),
+        }
+    };
+
+    // The colour the computer plays, if any, and how hard it thinks
+    let mut computer: Option<(bool, Limit)> = None;
+    if against_computer {
+        let human_white = loop {
+            match prompt("Play as (w)hite or (b)lack? ")
+                .to_lowercase()
+                .as_str()
+            {
+                "w" | "white" => break true,
+                "b" | "black" => break false,
+                _ => println!("Please enter w or b"),
+            }
+        };
+        let limit = loop {
+            let input = prompt("Engine strength, 'depth N' plies or 'time N' seconds per move: ");
+            match parse_limit(&input) {
+                Some(limit) => break limit,
+                None => println!("Please enter e.g. 'depth 3' or 'time 5'"),
+            }
+        };
+        computer = Some((!human_white, limit));
+    }
+
+    // Seen from the human's side when playing the computer
+    let options = RenderOptions {
+        unicode: true,
+        color: true,
+        flipped: matches!(computer, Some((true, _))),
+        ..RenderOptions::default()
+    };
 
-    game.print_board();
-    let from = String::from("E2");
-    let to = String::from("E3");
-    game.input_move(from , to);
-    game = game.do_turn();
-
-    /*
-    game.print_board();
-    let from = String::from("F7");
-    let to = String::from("F6");
-    game.input_move(from , to);
-    game = game.do_turn();
-
-    game.print_board();
-    let from = String::from("E3");
-    let to = String::from("E4");
-    game.input_move(from , to);
-    game = game.do_turn();
-
-    game.print_board();
-    let from = String::from("E8");
-    let to = String::from("F7");
-    game.input_move(from , to);
-    game = game.do_turn();
-    */
-
-    game.print_board();
-    let from = String::from("A7");
-    let to = String::from("A5");
-    game.input_move(from , to);
-    game = game.do_turn();
-
-    
-    game.print_board();
-    let from = String::from("D1");
-    let to = String::from("H5");
-    game.input_move(from , to);
-    game = game.do_turn(); 
-
-    game.print_board();
-    let from = String::from("A8");
-    let to = String::from("A6");
-    game.input_move(from , to);
-    game = game.do_turn(); 
-
-    game.print_board();
-    let from = String::from("H5");
-    let to = String::from("A5");
-    game.input_move(from , to);
-    game = game.do_turn(); 
-    
-    game.print_board();
-    let from = String::from("H7");
-    let to = String::from("H5");
-    game.input_move(from , to);
-    game = game.do_turn();
-
-    game.print_board();
-    let from = String::from("H2");
-    let to = String::from("H4");
-    game.input_move(from , to);
-    game = game.do_turn();
-
-    game.print_board();
-    let from = String::from("A6");
-    let to = String::from("H6");
-    game.input_move(from , to);
-    game = game.do_turn();
-
-    game.print_board();
-    let from = String::from("A5");
-    let to = String::from("C7");
-    game.input_move(from , to);
-    game = game.do_turn();
-
-    game.print_board();
-    let from = String::from("F7");
-    let to = String::from("F6");
-    game.input_move(from , to);
-    game = game.do_turn();
-
-    game.print_board();
-    let from = String::from("C7");
-    let to = String::from("D7");
-    game.input_move(from , to);
-    game = game.do_turn();
-    game.print_board();
-
-    let from = String::from("E8");
-    let to = String::from("F7");
-    game.input_move(from , to);
-    game = game.do_turn();
-
-    game.print_board();
-    
-    let from = String::from("H7");
-    let to = String::from("H5");
-    game.input_move(from , to);
-    game = game.do_turn();
-
-    game.print_board();
-    let from = String::from("D7");
-    let to = String::from("B7");
-    game.input_move(from , to);
-    game = game.do_turn();
-
-    game.print_board();
-    let from = String::from("D8");
-    let to = String::from("D3");
-    game.input_move(from , to);
-    game = game.do_turn();
-
-    game.print_board();
-    let from = String::from("B7");
-    let to = String::from("B8");
-    game.input_move(from , to);
-    game = game.do_turn();
-
-    game.print_board();
-    let from = String::from("D3");
-    let to = String::from("H7");
-    game.input_move(from , to);
-    game = game.do_turn();
-
-    game.print_board();
-    let from = String::from("B8");
-    let to = String::from("C8");
-    game.input_move(from , to);
-    game = game.do_turn();
-
-    game.print_board();
-    let from = String::from("F7");
-    let to = String::from("G6");
-    game.input_move(from , to);
-    game = game.do_turn();
-
-    game.print_board();
-    let from = String::from("C8");
-    let to = String::from("E6");
-    game.input_move(from , to);
-    game = game.do_turn();
-
-    //game.print_board();
-    //game.print_check_board(true);
-    /*
-    game.print_board();
-    let from = String::from("H7");
-    let to = String::from("H5");
-    game.input_move(from , to);
-    game = game.do_turn();*/
-
-    //game.print_board();
-    /*
-    let from = String::from("E2");
-    let to = String::from("E4");
-    game.input_move(from , to);
-    game.do_turn(); 
-    
-    game.print_board();
-    let from = String::from("E2");
-    let to = String::from("E4");
-    game.input_move(from , to);
-    game.do_turn(); 
-    
-    game.print_board();
-    let from = String::from("E2");
-    let to = String::from("E4");
-    game.input_move(from , to);
-    game.do_turn();*/
-
-
-}
\ No newline at end of file
+    let mut game = Game::new();
+    loop {
+        print!("{}", render_board(&game, &options));
+
+        if game.mate {
+            match game.white_turn {
+                true => println!("Checkmate, white wins!"),
+                false => println!("Checkmate, black wins!"),
+            }
+            break;
+        }
+        if game.stalemate {
+            println!("Stalemate!");
+            break;
+        }
+
+        if let Some((computer_white, limit)) = computer {
+            if computer_white == game.white_turn {
+                println!("The computer is thinking...");
+                let mv = match search::best_move(&game, limit) {
+                    Some(mv) => mv,
+                    None => break,
+                };
+                game = game.play_move(mv);
+                if let Some(san) = game.move_history.last() {
+                    println!("The computer plays {}", san);
+                }
+                continue;
+            }
+        }
+
+        let input = match game.white_turn {
+            true => prompt("White's move (e.g. E2 E4, or 'quit'): "),
+            false => prompt("Black's move (e.g. E7 E5, or 'quit'): "),
+        };
+        if input.eq_ignore_ascii_case("quit") {
+            break;
+        }
+
+        // Two squares, optionally followed by the promotion piece, e.g. E7 E8 N
+        let squares: String = input
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect();
+        let parsed = match squares.len() {
+            4 | 5 => (
+                squares[0..2].parse::<Pos>(),
+                squares[2..4].parse::<Pos>(),
+                promotion_piece(&squares[4..]),
+            ),
+            _ => {
+                println!("Please enter two squares, e.g. E2 E4");
+                continue;
+            }
+        };
+        let (from, to, promotion) = match parsed {
+            (Ok(from), Ok(to), Some(promotion)) => (from, to, promotion),
+            _ => {
+                println!("Please enter two squares, e.g. E2 E4");
+                continue;
+            }
+        };
+
+        match game.find_move(from, to, promotion) {
+            Some(mv) => game = game.play_move(mv),
+            None => println!("This is not a valid move!"),
+        }
+    }
+
+    let (white, black) = match computer {
+        Some((true, _)) => ("simonsev-chess", "Human"),
+        Some((false, _)) => ("Human", "simonsev-chess"),
+        None => ("White", "Black"),
+    };
+    let date = today();
+    let text = pgn::to_pgn(
+        &game,
+        &[
+            ("Event", "Casual game"),
+            ("Site", "simonsev-chess"),
+            ("Date", &date),
+            ("Round", "-"),
+            ("White", white),
+            ("Black", black),
+        ],
+    );
+    match std::fs::write(&pgn_path, text) {
+        Ok(()) => println!("Game saved to {}", pgn_path),
+        Err(e) => println!("Could not save the game to {}: {}", pgn_path, e),
+    }
+}
+
+// Prints message and returns the next line from stdin, trimmed
+// Returns "quit" if stdin is closed
+fn prompt(message: &str) -> String {
+    print!("{}", message);
+    let _ = io::stdout().flush();
+    let mut input = String::new();
+    match io::stdin().read_line(&mut input) {
+        Ok(0) | Err(_) => String::from("quit"),
+        Ok(_) => input.trim().to_string(),
+    }
+}
+
+// Parses "depth N" or "time N" (seconds, may be fractional)
+fn parse_limit(input: &str) -> Option<Limit> {
+    let mut words = input.split_whitespace();
+    let kind = words.next()?.to_lowercase();
+    let value = words.next()?;
+    match kind.as_str() {
+        "depth" | "d" => match value.parse::<u32>() {
+            Ok(depth) if depth > 0 => Some(Limit::Depth(depth)),
+            _ => None,
+        },
+        "time" | "t" => match value.parse::<f64>() {
+            Ok(secs) if secs > 0.0 => Some(Limit::Time(Duration::from_secs_f64(secs))),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// The piece letter after the squares of a move, Some(None) if there is none
+// and None if the letter is not a piece a pawn can promote to
+fn promotion_piece(letter: &str) -> Option<Option<PieceType>> {
+    match letter.to_ascii_uppercase().as_str() {
+        "" => Some(None),
+        "Q" => Some(Some(PieceType::Queen)),
+        "R" => Some(Some(PieceType::Rook)),
+        "B" => Some(Some(PieceType::Bishop)),
+        "N" => Some(Some(PieceType::Knight)),
+        _ => None,
+    }
+}
+
+// Today's date formatted as PGN wants it, "YYYY.MM.DD"
+fn today() -> String {
+    let days = match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(elapsed) => (elapsed.as_secs() / 86_400) as i64,
+        Err(_) => return String::from("????.??.??"),
+    };
+
+    // Days since 1970-01-01 to a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
@@ -0,0 +1,250 @@
+use crate::{Game, Move};
+use std::collections::HashMap;
+
+// Solving "play and mate in n" problems
+//
+// The player to move is the attacker. A move mates when the game it leads to
+// has mate set, which apply_move decides with check_for_mate; a stalemate
+// never counts. The attacker mates in n if some move mates at once, or if
+// n > 1 and every defence to it still leaves a mate in n - 1.
+
+// A move of the attacker that forces mate, with every defence to it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Solution {
+    pub mv: Move,
+    // In SAN with its check or mate mark
+    pub san: String,
+    // The fewest moves, this one included, in which it forces mate
+    pub mate_in: u32,
+    // Empty when the move mates at once
+    pub defences: Vec<Defence>,
+}
+
+// A defence to an attacker's move, with every way to go on mating after it
+// More than one continuation is a dual, which is allowed in a problem
+#[derive(Clone, Debug, PartialEq)]
+pub struct Defence {
+    pub mv: Move,
+    pub san: String,
+    pub continuations: Vec<Solution>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MateSolution {
+    // The number of moves the problem asks for
+    pub moves: u32,
+    // Every first move that forces mate in at most that many moves, the
+    // quickest first
+    pub keys: Vec<Solution>,
+}
+
+impl MateSolution {
+    // Whether there is a mate in the number of moves at all
+    pub fn is_solved(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    // Whether there is exactly one key move, as a sound problem has
+    pub fn is_sound(&self) -> bool {
+        self.keys.len() == 1
+    }
+
+    // The solutions other than the one starting with key, the intended key
+    // move of the problem
+    // Without an intended key every key is an alternative to the others, so
+    // a problem is cooked whenever this is not empty
+    pub fn cooks(&self, key: Move) -> Vec<&Solution> {
+        self.keys
+            .iter()
+            .filter(|solution| solution.mv != key)
+            .collect()
+    }
+}
+
+// Finds every way the player to move can force mate in at most moves moves
+// and the full tree of each, see MateSolution
+// Returns no keys for a game that is already over or for moves = 0
+pub fn solve(game: &Game, moves: u32) -> MateSolution {
+    let mut solver = Solver {
+        known: HashMap::new(),
+    };
+    let mut keys = match game.mate || game.stalemate {
+        true => Vec::new(),
+        false => solver.solutions(game, moves),
+    };
+    keys.sort_by_key(|solution| solution.mate_in);
+    MateSolution { moves, keys }
+}
+
+// Whether the player to move can force mate in at most moves moves, without
+// building the tree
+pub fn has_mate_in(game: &Game, moves: u32) -> bool {
+    let mut solver = Solver {
+        known: HashMap::new(),
+    };
+    !game.mate && !game.stalemate && solver.mates_in(game, moves)
+}
+
+struct Solver {
+    // Whether the attacker to move in a position, by its hash, mates in the
+    // number of moves
+    known: HashMap<(u64, u32), bool>,
+}
+
+impl Solver {
+    fn mates_in(&mut self, game: &Game, moves: u32) -> bool {
+        if moves == 0 {
+            return false;
+        }
+        let key = (game.position_hash(), moves);
+        if let Some(&mates) = self.known.get(&key) {
+            return mates;
+        }
+        let mates = game
+            .moves()
+            .into_iter()
+            .any(|mv| self.move_mates_in(game, mv, moves));
+        self.known.insert(key, mates);
+        mates
+    }
+
+    // Whether mv by the attacker forces mate in at most moves moves
+    fn move_mates_in(&mut self, game: &Game, mv: Move, moves: u32) -> bool {
+        if moves == 0 {
+            return false;
+        }
+        let after = game.clone().apply_move(mv);
+        if after.mate {
+            return true;
+        }
+        if after.stalemate || moves == 1 {
+            return false;
+        }
+        after
+            .moves()
+            .into_iter()
+            .all(|defence| self.mates_in(&after.clone().apply_move(defence), moves - 1))
+    }
+
+    // Every attacker move from game that forces mate in at most moves
+    // moves, with its tree
+    fn solutions(&mut self, game: &Game, moves: u32) -> Vec<Solution> {
+        let mut solutions: Vec<Solution> = Vec::new();
+        for mv in game.moves() {
+            if self.move_mates_in(game, mv, moves) {
+                solutions.push(self.solution(game, mv, moves));
+            }
+        }
+        solutions
+    }
+
+    fn solution(&mut self, game: &Game, mv: Move, moves: u32) -> Solution {
+        let after = game.clone().apply_move(mv);
+        let mate_in = (1..=moves)
+            .find(|&n| self.move_mates_in(game, mv, n))
+            .expect("mv mates in moves");
+        let defences = match after.mate {
+            true => Vec::new(),
+            false => after
+                .moves()
+                .into_iter()
+                .map(|defence| {
+                    let reply = after.clone().apply_move(defence);
+                    Defence {
+                        mv: defence,
+                        san: last_san(&reply),
+                        continuations: self.solutions(&reply, mate_in - 1),
+                    }
+                })
+                .collect(),
+        };
+        Solution {
+            mv,
+            san: last_san(&after),
+            mate_in,
+            defences,
+        }
+    }
+}
+
+fn last_san(game: &Game) -> String {
+    game.move_history.last().cloned().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::san::parse_san;
+
+    fn game(fen: &str) -> Game {
+        Game::from_fen(fen).unwrap()
+    }
+
+    // Morphy's mate in 2: 1. Ra6, and 1... bxa6 2. b7# or any bishop move
+    // 2. Rxa7#
+    #[test]
+    fn unique_key_with_its_tree() {
+        let game = game("kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1");
+        let solution = solve(&game, 2);
+        assert!(solution.is_solved());
+        assert!(solution.is_sound());
+
+        let key = &solution.keys[0];
+        assert_eq!(key.san, "Ra6");
+        assert_eq!(key.mate_in, 2);
+        assert!(solution.cooks(key.mv).is_empty());
+
+        let after = game.clone().apply_move(key.mv);
+        assert_eq!(key.defences.len(), after.moves().len());
+        for defence in &key.defences {
+            assert!(!defence.continuations.is_empty(), "{}", defence.san);
+            for continuation in &defence.continuations {
+                assert_eq!(continuation.mate_in, 1);
+                assert!(continuation.san.ends_with('#'));
+                assert!(continuation.defences.is_empty());
+            }
+        }
+        let capture = key.defences.iter().find(|d| d.san == "bxa6").unwrap();
+        let mates: Vec<&str> = capture
+            .continuations
+            .iter()
+            .map(|c| c.san.as_str())
+            .collect();
+        assert_eq!(mates, ["b7#"]);
+    }
+
+    #[test]
+    fn cooked_problem() {
+        // Either rook mates on the back rank
+        let game = game("6k1/5ppp/8/8/8/8/5PPP/R2R2K1 w - - 0 1");
+        let solution = solve(&game, 1);
+        assert!(solution.is_solved());
+        assert!(!solution.is_sound());
+
+        let intended = parse_san(&game, "Rd8").unwrap();
+        let cooks: Vec<&str> = solution
+            .cooks(intended)
+            .iter()
+            .map(|cook| cook.san.as_str())
+            .collect();
+        assert_eq!(cooks, ["Ra8#"]);
+    }
+
+    #[test]
+    fn no_mate_in_fewer_moves() {
+        let game = game("kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1");
+        assert!(!has_mate_in(&game, 1));
+        assert!(has_mate_in(&game, 2));
+        assert!(!solve(&game, 1).is_solved());
+        assert!(!solve(&game, 0).is_solved());
+    }
+
+    #[test]
+    fn quicker_keys_come_first() {
+        let game = game("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1");
+        let solution = solve(&game, 2);
+        assert_eq!(solution.keys[0].san, "Rd8#");
+        assert_eq!(solution.keys[0].mate_in, 1);
+        assert!(solution.keys.iter().all(|key| key.mate_in <= 2));
+    }
+}
@@ -0,0 +1,308 @@
+use crate::castling::castle_targets;
+use crate::{Game, PieceType, Pos, Rank};
+use std::fmt;
+
+// A move of the player to move, as generated by Game::moves and accepted by
+// Game::play_move
+// Castling is the king taking its own rook, to is the rook's square
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Move {
+    pub from: Pos,
+    pub to: Pos,
+    // The piece that moves, a pawn when promoting
+    pub piece: PieceType,
+    // The opponent's piece that is taken, if any, a pawn for en passant
+    pub captured: Option<PieceType>,
+    // What a pawn reaching the last rank becomes
+    pub promotion: Option<PieceType>,
+    pub castle: bool,
+    pub en_passant: bool,
+    // A pawn moving two squares from its starting rank
+    pub double_push: bool,
+}
+
+// The pieces a pawn can promote to, best first
+pub const PROMOTION_PIECES: [PieceType; 4] = [
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+];
+
+impl Move {
+    // The square the moving piece ends up on, for castling the king's
+    // square on the g or c file
+    pub fn destination(&self) -> Pos {
+        match self.castle {
+            true => castle_targets(self.from, self.to).0,
+            false => self.to,
+        }
+    }
+
+    // The move in UCI notation, castling as the king taking its rook in
+    // Chess960 and as the king moving two squares otherwise
+    pub fn to_uci(&self, chess960: bool) -> String {
+        let to = match chess960 {
+            true => self.to,
+            false => self.destination(),
+        };
+        let mut uci = format!("{}{}", self.from, to);
+        if let Some(piece_type) = self.promotion {
+            uci.push(piece_type.letter().to_ascii_lowercase());
+        }
+        uci
+    }
+}
+
+// The move in UCI notation, e.g. "e2e4", "e7e8q" or "e1g1"
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_uci(false))
+    }
+}
+
+impl Game {
+    // All legal moves of the player to move, empty once the game is over
+    // A pawn reaching the last rank gives one move per promotion piece
+    pub fn moves(&self) -> Vec<Move> {
+        if self.mate || self.stalemate {
+            return Vec::new();
+        }
+        let mut moves: Vec<Move> = Vec::new();
+        for from in Pos::all() {
+            let square = self.square(from);
+            if !square.occupied || square.piece.white != self.white_turn {
+                continue;
+            }
+            for &to in &square.piece.moves {
+                match self.promotes(from, to) {
+                    true => moves.extend(
+                        PROMOTION_PIECES
+                            .iter()
+                            .map(|&piece_type| self.build_move(from, to, Some(piece_type))),
+                    ),
+                    false => moves.push(self.build_move(from, to, None)),
+                }
+            }
+        }
+        moves
+    }
+
+    // The number of move sequences depth plies long from this position, to
+    // check move generation against published counts
+    pub fn perft(&self, depth: u32) -> u64 {
+        let moves = self.moves();
+        match depth {
+            0 => 1,
+            1 => moves.len() as u64,
+            _ => moves
+                .into_iter()
+                .map(|mv| self.clone().apply_move(mv).perft(depth - 1))
+                .sum(),
+        }
+    }
+
+    // Returns the legal move from -> to, or None if there is none
+    // promotion is the piece a pawn reaching the last rank becomes, a queen
+    // if left out, and must be None for every other move
+    // Castling is the king taking its own rook, or the king moving to the g
+    // or c file when that is not a king move of its own
+    pub fn find_move(&self, from: Pos, to: Pos, promotion: Option<PieceType>) -> Option<Move> {
+        if self.mate || self.stalemate {
+            return None;
+        }
+        let square = self.square(from);
+        if !square.occupied || square.piece.white != self.white_turn {
+            return None;
+        }
+        if !square.piece.moves.contains(&to) {
+            let rook = self.castling_rook(from, to)?;
+            return match promotion {
+                None => Some(self.build_move(from, rook, None)),
+                Some(_) => None,
+            };
+        }
+        let promotion = match (self.promotes(from, to), promotion) {
+            (true, None) => Some(PieceType::Queen),
+            (true, Some(piece_type)) if PROMOTION_PIECES.contains(&piece_type) => Some(piece_type),
+            (false, None) => None,
+            _ => return None,
+        };
+        Some(self.build_move(from, to, promotion))
+    }
+
+    // Returns the legal move written in UCI notation, e.g. "e2e4" or "e7e8q"
+    // Castling may be written either way, "e1g1" or "e1h1"
+    pub fn parse_uci(&self, uci: &str) -> Option<Move> {
+        if !uci.is_ascii() || (uci.len() != 4 && uci.len() != 5) {
+            return None;
+        }
+        let from = uci[0..2].parse::<Pos>().ok()?;
+        let to = uci[2..4].parse::<Pos>().ok()?;
+        let promotion = match uci[4..].to_ascii_lowercase().as_str() {
+            "" => None,
+            "q" => Some(PieceType::Queen),
+            "r" => Some(PieceType::Rook),
+            "b" => Some(PieceType::Bishop),
+            "n" => Some(PieceType::Knight),
+            _ => return None,
+        };
+        // "e7e8" without a piece is not a complete promotion in UCI
+        if promotion.is_none() && self.promotes(from, to) {
+            return None;
+        }
+        self.find_move(from, to, promotion)
+    }
+
+    // The rook square of the castling move that takes the king on from to
+    // to, if the king can castle that way
+    fn castling_rook(&self, from: Pos, to: Pos) -> Option<Pos> {
+        let piece = &self.square(from).piece;
+        if piece.piece_type != PieceType::King || to.rank() != from.rank() {
+            return None;
+        }
+        piece.moves.iter().copied().find(|&rook| {
+            let target = self.square(rook);
+            target.occupied
+                && target.piece.white == piece.white
+                && castle_targets(from, rook).0 == to
+        })
+    }
+
+    // Fills in the move from -> to from the current position, without checking it
+    pub(crate) fn build_move(&self, from: Pos, to: Pos, promotion: Option<PieceType>) -> Move {
+        let moving = &self.square(from).piece;
+        let piece = moving.piece_type;
+        let target = self.square(to);
+        let castle =
+            piece == PieceType::King && target.occupied && target.piece.white == moving.white;
+        let en_passant = piece == PieceType::Pawn && from.file() != to.file() && !target.occupied;
+        let double_push = piece == PieceType::Pawn
+            && (from.rank().index() as i8 - to.rank().index() as i8).abs() == 2;
+        Move {
+            from,
+            to,
+            piece,
+            captured: match (target.occupied, castle, en_passant) {
+                (true, false, _) => Some(target.piece.piece_type),
+                (false, _, true) => Some(PieceType::Pawn),
+                _ => None,
+            },
+            promotion,
+            castle,
+            en_passant,
+            double_push,
+        }
+    }
+
+    // Whether moving the piece on from to to is a pawn reaching its last rank
+    pub(crate) fn promotes(&self, from: Pos, to: Pos) -> bool {
+        let piece = &self.square(from).piece;
+        piece.piece_type == PieceType::Pawn && to.rank() == Rank::back_rank(!piece.white)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Game;
+
+    // Checks the perft counts of fen from depth 1 up
+    fn assert_perft(fen: &str, counts: &[u64]) {
+        let game = Game::from_fen(fen).unwrap();
+        for (depth, &count) in (1..).zip(counts) {
+            assert_eq!(game.perft(depth), count, "{} at depth {}", fen, depth);
+        }
+    }
+
+    #[test]
+    fn perft_start_position() {
+        let game = Game::new();
+        assert_eq!(game.perft(0), 1);
+        assert_eq!(game.perft(1), 20);
+        assert_eq!(game.perft(2), 400);
+        assert_eq!(game.perft(3), 8902);
+    }
+
+    #[test]
+    fn perft_kiwipete() {
+        assert_perft(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            &[48, 2039],
+        );
+    }
+
+    #[test]
+    fn perft_en_passant_and_pins() {
+        assert_perft(
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            &[14, 191, 2812],
+        );
+    }
+
+    #[test]
+    fn perft_promotions() {
+        assert_perft(
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            &[6, 264, 9467],
+        );
+        assert_perft(
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            &[44, 1486],
+        );
+    }
+
+    #[test]
+    fn perft_chess960_start() {
+        // Position 518 is the standard starting position
+        let game = Game::new_chess960(518).unwrap();
+        assert_eq!(game.perft(1), 20);
+        assert_eq!(game.perft(2), 400);
+        assert_eq!(game.perft(3), 8902);
+    }
+
+    // Positions from the Chess960 perft results, castling rights in
+    // Shredder-FEN
+    #[test]
+    fn perft_chess960() {
+        assert_perft(
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+            &[21, 528, 12189],
+        );
+        assert_perft(
+            "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+            &[21, 807, 18002],
+        );
+        assert_perft(
+            "qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9",
+            &[22, 593, 13440],
+        );
+        assert_perft(
+            "1nbbnrkr/p1p1ppp1/3p4/1p3P1p/3Pq2P/8/PPP1P1P1/QNBBNRKR w HFhf - 0 9",
+            &[28, 1120, 31058],
+        );
+        assert_perft(
+            "qnbnr1kr/ppp1b1pp/4p3/3p1p2/8/2NPP3/PPP1BPPP/QNB1R1KR w HEhe - 1 9",
+            &[29, 899, 26578],
+        );
+    }
+
+    // The same position as the first of perft_chess960, with the castling
+    // rights in X-FEN
+    #[test]
+    fn perft_chess960_x_fen() {
+        assert_perft(
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w KQkq - 2 9",
+            &[21, 528, 12189],
+        );
+    }
+
+    // The king stands next to the rook it castles with, so castling is the
+    // king taking its own rook
+    #[test]
+    fn perft_chess960_king_next_to_rook() {
+        assert_perft(
+            "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+            &[20, 479, 10471],
+        );
+    }
+}
@@ -0,0 +1,450 @@
+use crate::annotation::{nag_from_glyph, Annotation, CommentError};
+use crate::fen::{FenError, START_FEN};
+use crate::san::parse_san;
+use crate::tree::{GameTree, Node};
+use crate::Game;
+use std::fmt;
+
+// Reasons a PGN game can be rejected
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PgnError {
+    // A tag pair that is not [Name "value"]
+    Tag(String),
+    // The FEN tag does not hold a valid position
+    Fen(FenError),
+    // A move that is not legal in its position
+    IllegalMove(String),
+    // A "(" without a matching ")" or the other way round
+    Variation,
+    // A comment after ';' that holds a '}', see Annotation::add_comment
+    Comment(CommentError),
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PgnError::Tag(s) => write!(f, "invalid tag pair \"{}\"", s),
+            PgnError::Fen(e) => write!(f, "invalid FEN tag: {}", e),
+            PgnError::IllegalMove(san) => write!(f, "illegal move \"{}\"", san),
+            PgnError::Variation => write!(f, "unbalanced parentheses around a variation"),
+            PgnError::Comment(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+impl From<FenError> for PgnError {
+    fn from(e: FenError) -> PgnError {
+        PgnError::Fen(e)
+    }
+}
+
+impl From<CommentError> for PgnError {
+    fn from(e: CommentError) -> PgnError {
+        PgnError::Comment(e)
+    }
+}
+
+// Returns the PGN result token for game
+// "1-0" or "0-1" after mate, "1/2-1/2" after stalemate and "*" otherwise
+pub fn result(game: &Game) -> &'static str {
+    if game.mate {
+        // When mate is found the turn does not pass, so white_turn is the winner
+        if game.white_turn {
+            "1-0"
+        } else {
+            "0-1"
+        }
+    } else if game.stalemate {
+        "1/2-1/2"
+    } else {
+        "*"
+    }
+}
+
+// Writes game as PGN text
+// tags are written in the given order (e.g. Event, Site, Date, Round, White, Black),
+// the Result tag is added from the state of game unless tags has one, e.g.
+// for a resignation, and SetUp and FEN tags are added when the game did not
+// start from the initial position
+// The movetext comes from move_history and annotations, numbered from the
+// position the game started in, and is wrapped at 80 columns
+pub fn to_pgn(game: &Game, tags: &[(&str, &str)]) -> String {
+    let result = tag_result(tags).unwrap_or(result(game));
+    let mut pgn = String::new();
+
+    push_tags(&mut pgn, tags);
+    push_fen_tags(&mut pgn, game.start_fen(), tags);
+    pgn.push_str(&format!("[Result \"{}\"]\n\n", result));
+
+    let start = Game::from_fen(game.start_fen()).expect("start_fen is valid");
+    let mut white = start.side_to_move();
+    let mut number = start.fullmove_number;
+    // Black's move needs its number at the start and after a comment
+    let mut numbered = true;
+    let mut tokens: Vec<String> = Vec::new();
+    for (i, san) in game.move_history.iter().enumerate() {
+        if white {
+            tokens.push(format!("{}.", number));
+        } else if numbered {
+            tokens.push(format!("{}...", number));
+        }
+        tokens.push(san.clone());
+        let annotation = game.annotations.get(i).map(Annotation::pgn_tokens);
+        numbered = annotation
+            .as_ref()
+            .is_some_and(|tokens| tokens.iter().any(|t| t.starts_with('{')));
+        tokens.extend(annotation.into_iter().flatten());
+        if !white {
+            number += 1;
+        }
+        white = !white;
+    }
+    tokens.push(result.to_string());
+    pgn.push_str(&wrap(tokens));
+    pgn
+}
+
+// Writes tree as PGN text with its variations and annotations
+// tags are written as for to_pgn, with SetUp and FEN tags added when the
+// tree does not start from the initial position; without a Result tag the
+// result is taken from the end of the main line
+pub fn tree_to_pgn(tree: &GameTree, tags: &[(&str, &str)]) -> String {
+    let root = tree.root();
+    let result = tag_result(tags).unwrap_or(result(tree.mainline_game()));
+    let mut pgn = String::new();
+
+    push_tags(&mut pgn, tags);
+    push_fen_tags(&mut pgn, &root.game().to_fen(), tags);
+    pgn.push_str(&format!("[Result \"{}\"]\n\n", result));
+
+    let mut tokens: Vec<String> = root.annotation.pgn_tokens();
+    line_tokens(root, &mut tokens, true);
+    tokens.push(result.to_string());
+
+    // Parentheses go right next to the moves they enclose
+    let mut glued: Vec<String> = Vec::new();
+    let mut open = false;
+    for token in tokens {
+        match token.as_str() {
+            "(" => open = true,
+            ")" => glued.last_mut().expect("a variation has a move").push(')'),
+            _ if open => {
+                glued.push(format!("({}", token));
+                open = false;
+            }
+            _ => glued.push(token),
+        }
+    }
+    pgn.push_str(&wrap(glued));
+    pgn
+}
+
+// Writes every tag but Result, which goes last
+fn push_tags(pgn: &mut String, tags: &[(&str, &str)]) {
+    for (name, value) in tags.iter().filter(|(name, _)| *name != "Result") {
+        pgn.push_str(&format!("[{} \"{}\"]\n", name, escape(value)));
+    }
+}
+
+// SetUp and FEN tags for a game starting from fen, unless it is the initial
+// position or tags already has a FEN tag
+fn push_fen_tags(pgn: &mut String, fen: &str, tags: &[(&str, &str)]) {
+    if fen != START_FEN && !tags.iter().any(|(name, _)| *name == "FEN") {
+        pgn.push_str(&format!("[SetUp \"1\"]\n[FEN \"{}\"]\n", fen));
+    }
+}
+
+// The value of a Result tag in tags, if it is one of the four results
+fn tag_result<'a>(tags: &[(&str, &'a str)]) -> Option<&'a str> {
+    tags.iter()
+        .find(|(name, _)| *name == "Result")
+        .map(|&(_, value)| value)
+        .filter(|value| matches!(*value, "1-0" | "0-1" | "1/2-1/2" | "*"))
+}
+
+// The tokens of the line that follows node, with the variations of each move
+// right after it
+// number forces a move number before a black move, as needed at the start of
+// a line and after a comment or variation
+fn line_tokens(node: &Node, tokens: &mut Vec<String>, mut number: bool) {
+    let mut node = node;
+    while let Some(main) = node.children().first() {
+        move_tokens(node.game(), main, tokens, number);
+        for variation in &node.children()[1..] {
+            tokens.push("(".to_string());
+            move_tokens(node.game(), variation, tokens, true);
+            line_tokens(variation, tokens, false);
+            tokens.push(")".to_string());
+        }
+        number = node.children().len() > 1 || !main.annotation.comments.is_empty();
+        node = main;
+    }
+}
+
+fn move_tokens(before: &Game, node: &Node, tokens: &mut Vec<String>, number: bool) {
+    if before.side_to_move() {
+        tokens.push(format!("{}.", before.fullmove_number));
+    } else if number {
+        tokens.push(format!("{}...", before.fullmove_number));
+    }
+    tokens.push(node.san().unwrap_or_default().to_string());
+    tokens.extend(node.annotation.pgn_tokens());
+}
+
+// Joins tokens with spaces, wrapping lines at 80 columns
+fn wrap(tokens: Vec<String>) -> String {
+    let mut text = String::new();
+    let mut line_len = 0;
+    for token in tokens {
+        if line_len > 0 && line_len + 1 + token.len() > 80 {
+            text.push('\n');
+            line_len = 0;
+        } else if line_len > 0 {
+            text.push(' ');
+            line_len += 1;
+        }
+        line_len += token.len();
+        text.push_str(&token);
+    }
+    text.push('\n');
+    text
+}
+
+// Reads one PGN game into a tree with its variations and annotations
+// Returns the tag pairs in the order they were written and the tree
+// A FEN tag sets the starting position, move suffixes such as "!?" are
+// turned into NAGs and everything after the result is ignored
+// Without a Result tag, the result ending the movetext is added to the tags
+// as one, so that writing the game again keeps it
+pub fn parse_tree(pgn: &str) -> Result<(Vec<(String, String)>, GameTree), PgnError> {
+    let mut tags: Vec<(String, String)> = Vec::new();
+    let mut lines = pgn.lines().peekable();
+    while let Some(line) = lines.peek() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            tags.push(parse_tag(line)?);
+        } else if !line.is_empty() {
+            break;
+        }
+        lines.next();
+    }
+    let movetext: Vec<&str> = lines.collect();
+
+    let game = match tags.iter().find(|(name, _)| name == "FEN") {
+        Some((_, fen)) => Game::from_fen(fen)?,
+        None => Game::new(),
+    };
+    let mut tree = GameTree::new(game);
+    // Where to go back to when each open variation ends
+    let mut stack: Vec<Vec<usize>> = Vec::new();
+    // Comments at the start of a variation go with its first move
+    let mut pending: Vec<String> = Vec::new();
+    let mut variation_start = false;
+
+    for token in tokenize(&movetext.join("\n")) {
+        match token {
+            Token::Move(san) => {
+                let mv = parse_san(tree.game(), &san).ok_or(PgnError::IllegalMove(san))?;
+                tree.play(mv);
+                for text in pending.drain(..) {
+                    tree.current_mut().annotation.add_comment(&text)?;
+                }
+                variation_start = false;
+            }
+            Token::Nag(nag) => tree.current_mut().annotation.nags.push(nag),
+            Token::Comment(text) => match variation_start {
+                true => pending.push(text),
+                false => tree.current_mut().annotation.add_comment(&text)?,
+            },
+            Token::Open => {
+                stack.push(tree.path().to_vec());
+                if !tree.back() {
+                    return Err(PgnError::Variation);
+                }
+                variation_start = true;
+            }
+            Token::Close => {
+                let path = stack.pop().ok_or(PgnError::Variation)?;
+                tree.go_to(&path);
+                pending.clear();
+                variation_start = false;
+            }
+            Token::Result(result) => {
+                if !tags.iter().any(|(name, _)| name == "Result") {
+                    tags.push(("Result".to_string(), result));
+                }
+                break;
+            }
+        }
+    }
+    if !stack.is_empty() {
+        return Err(PgnError::Variation);
+    }
+    tree.to_start();
+    Ok((tags, tree))
+}
+
+// Reads one PGN game into its tags and a game of its main line, with the
+// annotations of each move
+// Variations are left out, see parse_tree to keep them
+pub fn parse_game(pgn: &str) -> Result<(Vec<(String, String)>, Game), PgnError> {
+    let (tags, tree) = parse_tree(pgn)?;
+    let mut game = tree.mainline_game().clone();
+    game.annotations.clear();
+    let mut node = tree.root();
+    while let Some(child) = node.children().first() {
+        game.annotations.push(child.annotation.clone());
+        node = child;
+    }
+    Ok((tags, game))
+}
+
+// Parses [Name "value"]
+fn parse_tag(line: &str) -> Result<(String, String), PgnError> {
+    let error = || PgnError::Tag(line.to_string());
+    let inner = line
+        .strip_prefix('[')
+        .and_then(|s| s.strip_suffix(']'))
+        .ok_or_else(error)?
+        .trim();
+    let (name, value) = inner.split_once(char::is_whitespace).ok_or_else(error)?;
+    let value = value
+        .trim()
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .ok_or_else(error)?;
+    Ok((name.to_string(), unescape(value)))
+}
+
+fn unescape(value: &str) -> String {
+    let mut text = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => text.extend(chars.next()),
+            _ => text.push(c),
+        }
+    }
+    text
+}
+
+enum Token {
+    Move(String),
+    Nag(u8),
+    Comment(String),
+    Open,
+    Close,
+    Result(String),
+}
+
+// Splits movetext into moves, NAGs, comments, parentheses and the result
+// Move numbers are dropped, also when written together with the move as in "1.e4"
+fn tokenize(movetext: &str) -> Vec<Token> {
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut chars = movetext.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            '{' => {
+                chars.next();
+                let text: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                tokens.push(Token::Comment(text.trim().to_string()));
+            }
+            ';' => {
+                chars.next();
+                let text: String = chars.by_ref().take_while(|&c| c != '\n').collect();
+                tokens.push(Token::Comment(text.trim().to_string()));
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "{};()".contains(c) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                word_tokens(&word, &mut tokens);
+            }
+        }
+    }
+    tokens
+}
+
+fn word_tokens(word: &str, tokens: &mut Vec<Token>) {
+    if matches!(word, "1-0" | "0-1" | "1/2-1/2" | "*") {
+        tokens.push(Token::Result(word.to_string()));
+        return;
+    }
+    if let Some(nag) = word.strip_prefix('$') {
+        tokens.extend(nag.parse::<u8>().ok().map(Token::Nag));
+        return;
+    }
+    // A move number, possibly followed by the move
+    let word = match word.starts_with(|c: char| c.is_ascii_digit()) && word.contains('.') {
+        true => word.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.'),
+        false => word,
+    };
+    let san = word.trim_end_matches(['!', '?']);
+    if !san.is_empty() {
+        tokens.push(Token::Move(san.to_string()));
+    }
+    tokens.extend(nag_from_glyph(&word[san.len()..]).map(Token::Nag));
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The tags as parse_tree returns them, as tree_to_pgn takes them
+    fn tag_refs(tags: &[(String, String)]) -> Vec<(&str, &str)> {
+        tags.iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn movetext_result_without_tag_is_kept() {
+        let (tags, tree) = parse_tree("1. e4 e5 2. Qh5 Nc6 1-0\n").unwrap();
+        assert_eq!(tags, vec![("Result".to_string(), "1-0".to_string())]);
+
+        let pgn = tree_to_pgn(&tree, &tag_refs(&tags));
+        assert!(pgn.contains("[Result \"1-0\"]"), "{}", pgn);
+        assert!(pgn.trim_end().ends_with("1-0"), "{}", pgn);
+
+        let (again, _) = parse_tree(&pgn).unwrap();
+        assert_eq!(again, tags);
+    }
+
+    #[test]
+    fn result_tag_wins_over_movetext() {
+        let (tags, _) = parse_tree("[Result \"0-1\"]\n\n1. e4 e5 *\n").unwrap();
+        assert_eq!(tags, vec![("Result".to_string(), "0-1".to_string())]);
+    }
+
+    #[test]
+    fn tree_round_trip_keeps_variations() {
+        let text = "[Event \"Test\"]\n[Result \"*\"]\n\n1. e4 {best} (1. d4 d5) 1... e5 2. Nf3 *\n";
+        let (tags, tree) = parse_tree(text).unwrap();
+        let pgn = tree_to_pgn(&tree, &tag_refs(&tags));
+        let (_, again) = parse_tree(&pgn).unwrap();
+        assert_eq!(tree_to_pgn(&again, &tag_refs(&tags)), pgn);
+        assert_eq!(again.root().children().len(), 2);
+    }
+}
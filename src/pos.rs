@@ -0,0 +1,247 @@
+use std::fmt;
+use std::str::FromStr;
+
+// Returned when a file, rank or square cannot be parsed
+// Holds the rejected input
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PosError(pub String);
+
+impl fmt::Display for PosError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid square \"{}\"", self.0)
+    }
+}
+
+impl std::error::Error for PosError {}
+
+// A column of the board, from the a-file to the h-file
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct File(u8);
+
+impl File {
+    pub const A: File = File(0);
+    pub const B: File = File(1);
+    pub const C: File = File(2);
+    pub const D: File = File(3);
+    pub const E: File = File(4);
+    pub const F: File = File(5);
+    pub const G: File = File(6);
+    pub const H: File = File(7);
+
+    // 0 for the a-file up to 7 for the h-file, None for anything else
+    pub fn new(index: u8) -> Option<File> {
+        match index < 8 {
+            true => Some(File(index)),
+            false => None,
+        }
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    // The files from a to h
+    pub fn all() -> impl DoubleEndedIterator<Item = File> {
+        (0..8).map(File)
+    }
+
+    pub fn to_char(self) -> char {
+        (b'a' + self.0) as char
+    }
+}
+
+impl fmt::Display for File {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_char())
+    }
+}
+
+// Accepts "a" to "h" in either case
+impl FromStr for File {
+    type Err = PosError;
+
+    fn from_str(s: &str) -> Result<File, PosError> {
+        match s.as_bytes() {
+            [c @ b'a'..=b'h'] => Ok(File(c - b'a')),
+            [c @ b'A'..=b'H'] => Ok(File(c - b'A')),
+            _ => Err(PosError(s.to_string())),
+        }
+    }
+}
+
+// A row of the board, from the first rank (white's back rank) to the eighth
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Rank(u8);
+
+impl Rank {
+    pub const R1: Rank = Rank(0);
+    pub const R2: Rank = Rank(1);
+    pub const R3: Rank = Rank(2);
+    pub const R4: Rank = Rank(3);
+    pub const R5: Rank = Rank(4);
+    pub const R6: Rank = Rank(5);
+    pub const R7: Rank = Rank(6);
+    pub const R8: Rank = Rank(7);
+
+    // 0 for the first rank up to 7 for the eighth, None for anything else
+    pub fn new(index: u8) -> Option<Rank> {
+        match index < 8 {
+            true => Some(Rank(index)),
+            false => None,
+        }
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    // The ranks from 1 to 8
+    pub fn all() -> impl DoubleEndedIterator<Item = Rank> {
+        (0..8).map(Rank)
+    }
+
+    // The back rank of white (first) or black (eighth)
+    pub fn back_rank(white: bool) -> Rank {
+        match white {
+            true => Rank(0),
+            false => Rank(7),
+        }
+    }
+
+    pub fn to_char(self) -> char {
+        (b'1' + self.0) as char
+    }
+}
+
+impl fmt::Display for Rank {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_char())
+    }
+}
+
+// Accepts "1" to "8"
+impl FromStr for Rank {
+    type Err = PosError;
+
+    fn from_str(s: &str) -> Result<Rank, PosError> {
+        match s.as_bytes() {
+            [c @ b'1'..=b'8'] => Ok(Rank(c - b'1')),
+            _ => Err(PosError(s.to_string())),
+        }
+    }
+}
+
+// A square on the board as a single index, 0 = a1, 1 = b1, ..., 8 = a2, ..., 63 = h8
+// file() is the column (a to h) and rank() the row (1 to 8)
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Pos(u8);
+
+impl Pos {
+    pub fn new(file: File, rank: Rank) -> Pos {
+        Pos(rank.0 * 8 + file.0)
+    }
+
+    // Returns None unless index is below 64
+    pub fn from_index(index: u8) -> Option<Pos> {
+        match index < 64 {
+            true => Some(Pos(index)),
+            false => None,
+        }
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    pub fn file(self) -> File {
+        File(self.0 % 8)
+    }
+
+    pub fn rank(self) -> Rank {
+        Rank(self.0 / 8)
+    }
+
+    // The square file_offset files to the right and rank_offset ranks up
+    // (seen from white), or None if that is off the board
+    pub fn offset(self, file_offset: i8, rank_offset: i8) -> Option<Pos> {
+        let file = self.file().0 as i8 + file_offset;
+        let rank = self.rank().0 as i8 + rank_offset;
+        match (0..8).contains(&file) && (0..8).contains(&rank) {
+            true => Some(Pos((rank * 8 + file) as u8)),
+            false => None,
+        }
+    }
+
+    // All 64 squares, a1, b1, ..., h1, a2, ..., h8
+    pub fn all() -> impl DoubleEndedIterator<Item = Pos> {
+        (0..64).map(Pos)
+    }
+
+    // The squares of one rank from the a-file to the h-file
+    pub fn rank_squares(rank: Rank) -> impl DoubleEndedIterator<Item = Pos> {
+        File::all().map(move |file| Pos::new(file, rank))
+    }
+
+    // The squares of one file from the first rank to the eighth
+    pub fn file_squares(file: File) -> impl DoubleEndedIterator<Item = Pos> {
+        Rank::all().map(move |rank| Pos::new(file, rank))
+    }
+}
+
+// The square name, e.g. "e4"
+impl fmt::Display for Pos {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.file(), self.rank())
+    }
+}
+
+// Accepts square names such as "e4" and "E4"
+impl FromStr for Pos {
+    type Err = PosError;
+
+    fn from_str(s: &str) -> Result<Pos, PosError> {
+        let error = || PosError(s.to_string());
+        if !s.is_ascii() || s.len() != 2 {
+            return Err(error());
+        }
+        let file = s[0..1].parse::<File>().map_err(|_| error())?;
+        let rank = s[1..2].parse::<Rank>().map_err(|_| error())?;
+        Ok(Pos::new(file, rank))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_squares() {
+        let cases = [
+            ("a1", Some((File::A, 0))),
+            ("e4", Some((File::E, 3))),
+            ("E4", Some((File::E, 3))),
+            ("h8", Some((File::H, 7))),
+            ("i1", None),
+            ("a9", None),
+            ("a0", None),
+            ("", None),
+            ("e", None),
+            ("e44", None),
+            ("4e", None),
+            ("é4", None),
+        ];
+        for (input, expected) in cases {
+            let expected = expected.map(|(file, rank)| Pos::new(file, Rank::new(rank).unwrap()));
+            match expected {
+                Some(pos) => assert_eq!(input.parse::<Pos>(), Ok(pos), "{}", input),
+                None => assert_eq!(
+                    input.parse::<Pos>(),
+                    Err(PosError(input.to_string())),
+                    "{}",
+                    input
+                ),
+            }
+        }
+        assert_eq!("E4".parse::<Pos>().unwrap().to_string(), "e4");
+    }
+}
@@ -0,0 +1,365 @@
+use crate::fen::FenError;
+use crate::san::parse_san;
+use crate::{Game, Move, Pos};
+use std::fmt;
+
+// Tactics puzzles: a starting position and the line that solves it, played
+// through a PuzzleSession that checks the user's moves
+//
+// The solution alternates between the player's moves and the opponent's
+// replies, starting with the player, who is the side to move at the start.
+// A move other than the one in the solution is still accepted when it mates.
+
+// Why a puzzle could not be made
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PuzzleError {
+    Fen(FenError),
+    // The move at index in the solution, counted from 0, is not legal there
+    IllegalMove { index: usize, text: String },
+    // A puzzle needs at least one move to find
+    NoMoves,
+}
+
+impl fmt::Display for PuzzleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PuzzleError::Fen(e) => write!(f, "{}", e),
+            PuzzleError::IllegalMove { index, text } => {
+                write!(
+                    f,
+                    "move {} of the solution \"{}\" is not legal",
+                    index + 1,
+                    text
+                )
+            }
+            PuzzleError::NoMoves => write!(f, "the solution has no moves"),
+        }
+    }
+}
+
+impl std::error::Error for PuzzleError {}
+
+impl From<FenError> for PuzzleError {
+    fn from(e: FenError) -> PuzzleError {
+        PuzzleError::Fen(e)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Puzzle {
+    start: Game,
+    solution: Vec<Move>,
+}
+
+impl Puzzle {
+    // A puzzle from the position in fen and its solution, each move in UCI
+    // (e.g. "e2e4") or SAN (e.g. "Nf3+")
+    pub fn new(fen: &str, solution: &[&str]) -> Result<Puzzle, PuzzleError> {
+        let start = Game::from_fen(fen)?;
+        let mut game = start.clone();
+        let mut moves: Vec<Move> = Vec::new();
+        for (index, text) in solution.iter().enumerate() {
+            let mv = game
+                .parse_uci(text)
+                .or_else(|| parse_san(&game, text))
+                .ok_or_else(|| PuzzleError::IllegalMove {
+                    index,
+                    text: text.to_string(),
+                })?;
+            game = game.apply_move(mv);
+            moves.push(mv);
+        }
+        if moves.is_empty() {
+            return Err(PuzzleError::NoMoves);
+        }
+        Ok(Puzzle {
+            start,
+            solution: moves,
+        })
+    }
+
+    // A puzzle as in the Lichess puzzle database: the FEN is the position
+    // before the opponent's move that sets the puzzle up, and moves holds
+    // that move and then the solution in UCI, separated by spaces
+    pub fn from_lichess(fen: &str, moves: &str) -> Result<Puzzle, PuzzleError> {
+        let moves: Vec<&str> = moves.split_whitespace().collect();
+        let puzzle = Puzzle::new(fen, &moves)?;
+        let Some((&setup, solution)) = puzzle.solution.split_first() else {
+            return Err(PuzzleError::NoMoves);
+        };
+        if solution.is_empty() {
+            return Err(PuzzleError::NoMoves);
+        }
+        Ok(Puzzle {
+            start: puzzle.start.apply_move(setup),
+            solution: solution.to_vec(),
+        })
+    }
+
+    // The position the player has to find the first move in
+    pub fn start(&self) -> &Game {
+        &self.start
+    }
+
+    // The player's moves and the opponent's replies, the player's first
+    pub fn solution(&self) -> &[Move] {
+        &self.solution
+    }
+
+    // Whether the player is white
+    pub fn white(&self) -> bool {
+        self.start.side_to_move()
+    }
+}
+
+// What became of a move played in a PuzzleSession
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Verdict {
+    // The move is right and the puzzle goes on, reply is the opponent's move
+    // that was played after it
+    Correct { reply: Move },
+    // The move is right and finishes the puzzle, or mates
+    Solved,
+    // The move is legal but not the solution, the position stays as it was
+    // so the player can try again
+    Wrong,
+    // The move is not legal, or the puzzle is already finished
+    Illegal,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Status {
+    Playing,
+    // Finished without a wrong move
+    Solved,
+    // A wrong move was played; the puzzle can still be finished but does not
+    // count as solved
+    Failed,
+}
+
+// A hint for the next move, each hint at the same move tells more
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Hint {
+    // The square of the piece to move
+    Piece(Pos),
+    // The whole move
+    Move(Move),
+}
+
+// One attempt at a puzzle
+#[derive(Clone, Debug)]
+pub struct PuzzleSession {
+    puzzle: Puzzle,
+    game: Game,
+    // How many moves of the solution have been played
+    ply: usize,
+    finished: bool,
+    mistakes: usize,
+    hints: usize,
+    // Hints given for the current move
+    move_hints: usize,
+}
+
+impl PuzzleSession {
+    pub fn new(puzzle: Puzzle) -> PuzzleSession {
+        PuzzleSession {
+            game: puzzle.start.clone(),
+            puzzle,
+            ply: 0,
+            finished: false,
+            mistakes: 0,
+            hints: 0,
+            move_hints: 0,
+        }
+    }
+
+    pub fn puzzle(&self) -> &Puzzle {
+        &self.puzzle
+    }
+
+    // The position the player is to move in, or the final one
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    // Checks mv against the solution and plays it, and the opponent's reply
+    // after it, if it is right
+    pub fn play(&mut self, mv: Move) -> Verdict {
+        if self.finished || self.game.find_move(mv.from, mv.to, mv.promotion) != Some(mv) {
+            return Verdict::Illegal;
+        }
+        let expected = self.puzzle.solution[self.ply];
+        let after = self.game.clone().apply_move(mv);
+        if mv != expected && !after.mate {
+            self.mistakes += 1;
+            return Verdict::Wrong;
+        }
+
+        self.game = after;
+        self.ply += 1;
+        self.move_hints = 0;
+        match self.puzzle.solution.get(self.ply) {
+            Some(&reply) if !self.game.mate => {
+                self.game = self.game.clone().apply_move(reply);
+                self.ply += 1;
+                match self.ply == self.puzzle.solution.len() {
+                    // A solution that ends with the opponent's move has
+                    // nothing left for the player to find
+                    true => self.finish(),
+                    false => Verdict::Correct { reply },
+                }
+            }
+            _ => self.finish(),
+        }
+    }
+
+    fn finish(&mut self) -> Verdict {
+        self.finished = true;
+        Verdict::Solved
+    }
+
+    // The square of the piece to move first, the whole move when asked
+    // again, None once the puzzle is finished
+    pub fn hint(&mut self) -> Option<Hint> {
+        if self.finished {
+            return None;
+        }
+        let mv = self.puzzle.solution[self.ply];
+        self.hints += 1;
+        self.move_hints += 1;
+        match self.move_hints {
+            1 => Some(Hint::Piece(mv.from)),
+            _ => Some(Hint::Move(mv)),
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        match (self.mistakes, self.finished) {
+            (0, true) => Status::Solved,
+            (0, false) => Status::Playing,
+            _ => Status::Failed,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    // The number of wrong moves played
+    pub fn mistakes(&self) -> usize {
+        self.mistakes
+    }
+
+    // The number of hints given
+    pub fn hints(&self) -> usize {
+        self.hints
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::START_FEN;
+
+    const MORPHY: &str = "kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1";
+
+    fn morphy() -> PuzzleSession {
+        PuzzleSession::new(Puzzle::new(MORPHY, &["Ra6", "bxa6", "b7#"]).unwrap())
+    }
+
+    fn san(session: &PuzzleSession, san: &str) -> Move {
+        parse_san(session.game(), san).unwrap()
+    }
+
+    #[test]
+    fn solving() {
+        let mut session = morphy();
+        let reply = session.puzzle().solution()[1];
+        let key = san(&session, "Ra6");
+        assert_eq!(session.play(key), Verdict::Correct { reply });
+        assert_eq!(session.status(), Status::Playing);
+        let mate = san(&session, "b7");
+        assert_eq!(session.play(mate), Verdict::Solved);
+        assert_eq!(session.status(), Status::Solved);
+        assert!(session.is_finished());
+        assert!(session.game().mate);
+        assert_eq!(session.play(mate), Verdict::Illegal);
+    }
+
+    #[test]
+    fn wrong_move() {
+        let mut session = morphy();
+        let wrong = san(&session, "Ra2");
+        assert_eq!(session.play(wrong), Verdict::Wrong);
+        assert_eq!(session.mistakes(), 1);
+        assert_eq!(session.status(), Status::Failed);
+        // The position stays as it was, so the right move still works
+        assert_eq!(session.game().to_fen(), MORPHY);
+        let key = san(&session, "Ra6");
+        assert!(matches!(session.play(key), Verdict::Correct { .. }));
+        let mate = san(&session, "b7");
+        assert_eq!(session.play(mate), Verdict::Solved);
+        assert_eq!(session.status(), Status::Failed);
+    }
+
+    #[test]
+    fn illegal_move() {
+        let mut session = morphy();
+        let mut mv = san(&session, "Ra6");
+        mv.to = "h8".parse().unwrap();
+        assert_eq!(session.play(mv), Verdict::Illegal);
+        assert_eq!(session.mistakes(), 0);
+        assert_eq!(session.status(), Status::Playing);
+    }
+
+    #[test]
+    fn another_mate_is_accepted() {
+        // Either rook mates on the back rank
+        let puzzle = Puzzle::new("6k1/5ppp/8/8/8/8/5PPP/R2R2K1 w - - 0 1", &["Rd8#"]).unwrap();
+        let mut session = PuzzleSession::new(puzzle);
+        let other = san(&session, "Ra8");
+        assert_eq!(session.play(other), Verdict::Solved);
+        assert_eq!(session.status(), Status::Solved);
+    }
+
+    #[test]
+    fn hints() {
+        let mut session = morphy();
+        let key = san(&session, "Ra6");
+        assert_eq!(session.hint(), Some(Hint::Piece(key.from)));
+        assert_eq!(session.hint(), Some(Hint::Move(key)));
+        assert_eq!(session.hints(), 2);
+        session.play(key);
+        let mate = san(&session, "b7");
+        assert_eq!(session.hint(), Some(Hint::Piece(mate.from)));
+        session.play(mate);
+        assert_eq!(session.hint(), None);
+        assert_eq!(session.hints(), 3);
+    }
+
+    #[test]
+    fn lichess_format() {
+        // Fool's mate, set up by 1. f3 and found by black
+        let puzzle = Puzzle::from_lichess(START_FEN, "f2f3 e7e5 g2g4 d8h4").unwrap();
+        assert!(!puzzle.white());
+        assert_eq!(puzzle.solution().len(), 3);
+        let mut session = PuzzleSession::new(puzzle);
+        let first = san(&session, "e5");
+        assert!(matches!(session.play(first), Verdict::Correct { .. }));
+        let mate = san(&session, "Qh4");
+        assert_eq!(session.play(mate), Verdict::Solved);
+    }
+
+    #[test]
+    fn bad_puzzles() {
+        assert_eq!(Puzzle::new(MORPHY, &[]).unwrap_err(), PuzzleError::NoMoves);
+        assert_eq!(
+            Puzzle::new(MORPHY, &["Ra6", "Ka7"]).unwrap_err(),
+            PuzzleError::IllegalMove {
+                index: 1,
+                text: "Ka7".to_string()
+            }
+        );
+        assert!(Puzzle::from_lichess(START_FEN, "e2e4").is_err());
+    }
+}
@@ -0,0 +1,387 @@
+use crate::database::{PgnReader, ReadError};
+use crate::fen::FenError;
+use crate::tree::GameTree;
+use crate::{Game, Move, PieceType, Pos};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Read;
+use std::str::FromStr;
+
+// Finding games in a collection by the positions they reached
+//
+// PositionIndex goes through the main line of every game once and keeps the
+// position hash and the material of each position, so a query is a single
+// lookup. Games are numbered in the order they are added, starting at 0, and
+// positions by their ply: 0 for the starting position and n for the position
+// after the nth move.
+
+// A position reached in a game
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Hit {
+    pub game: usize,
+    pub ply: usize,
+}
+
+// The pieces each player has, not counting the kings
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Material {
+    // [white, black][pawn, knight, bishop, rook, queen]
+    counts: [[u8; 5]; 2],
+}
+
+const MATERIAL_PIECES: [PieceType; 5] = [
+    PieceType::Pawn,
+    PieceType::Knight,
+    PieceType::Bishop,
+    PieceType::Rook,
+    PieceType::Queen,
+];
+
+impl Material {
+    // Bare kings
+    pub fn new() -> Material {
+        Material::default()
+    }
+
+    // The material on the board of game
+    pub fn of(game: &Game) -> Material {
+        let mut material = Material::new();
+        for pos in Pos::all() {
+            let piece = &game.square(pos).piece;
+            if let Some(i) = piece_index(piece.piece_type) {
+                material.counts[side(piece.white)][i] += 1;
+            }
+        }
+        material
+    }
+
+    // How many pieces of piece_type white (or black) has, 1 for a king
+    pub fn count(&self, white: bool, piece_type: PieceType) -> u8 {
+        match piece_type {
+            PieceType::King => 1,
+            _ => piece_index(piece_type).map_or(0, |i| self.counts[side(white)][i]),
+        }
+    }
+
+    // Adds a piece for white (or black), kings are ignored
+    pub fn with(mut self, white: bool, piece_type: PieceType) -> Material {
+        if let Some(i) = piece_index(piece_type) {
+            self.counts[side(white)][i] += 1;
+        }
+        self
+    }
+
+    // The same material with the colours swapped
+    pub fn flipped(&self) -> Material {
+        Material {
+            counts: [self.counts[1], self.counts[0]],
+        }
+    }
+}
+
+// A material signature that could not be read
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MaterialError(pub String);
+
+impl fmt::Display for MaterialError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid material \"{}\"", self.0)
+    }
+}
+
+impl std::error::Error for MaterialError {}
+
+// Returned by PositionIndex::add_game with the index of the first move that
+// is not legal in its position
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IllegalMoveError(pub usize);
+
+impl fmt::Display for IllegalMoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "move {} of the game is not legal", self.0)
+    }
+}
+
+impl std::error::Error for IllegalMoveError {}
+
+// Reads material as written for endgames, white first, e.g. "KRPvKR" or
+// "KRP vs KR"; the kings may be left out and case does not matter
+impl FromStr for Material {
+    type Err = MaterialError;
+
+    fn from_str(s: &str) -> Result<Material, MaterialError> {
+        let error = || MaterialError(s.to_string());
+        let lower = s.to_ascii_lowercase();
+        let (white, black) = lower
+            .split_once("vs")
+            .or_else(|| lower.split_once('v'))
+            .ok_or_else(error)?;
+
+        let mut material = Material::new();
+        for (is_white, pieces) in [(true, white), (false, black)] {
+            for letter in pieces.chars().filter(|c| !c.is_whitespace()) {
+                let piece_type = match letter {
+                    'k' => PieceType::King,
+                    'q' => PieceType::Queen,
+                    'r' => PieceType::Rook,
+                    'b' => PieceType::Bishop,
+                    'n' => PieceType::Knight,
+                    'p' => PieceType::Pawn,
+                    _ => return Err(error()),
+                };
+                material = material.with(is_white, piece_type);
+            }
+        }
+        Ok(material)
+    }
+}
+
+// Writes material the way FromStr reads it, e.g. "KRPvKR"
+impl fmt::Display for Material {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, counts) in self.counts.iter().enumerate() {
+            if i == 1 {
+                write!(f, "v")?;
+            }
+            write!(f, "K")?;
+            for (piece_type, &count) in MATERIAL_PIECES.iter().zip(counts).rev() {
+                for _ in 0..count {
+                    write!(f, "{}", piece_type)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn piece_index(piece_type: PieceType) -> Option<usize> {
+    MATERIAL_PIECES.iter().position(|&p| p == piece_type)
+}
+
+fn side(white: bool) -> usize {
+    match white {
+        true => 0,
+        false => 1,
+    }
+}
+
+// An index of the positions reached in a collection of games
+#[derive(Clone, Debug, Default)]
+pub struct PositionIndex {
+    positions: HashMap<u64, Vec<Hit>>,
+    // Only the first ply of each game with the material
+    materials: HashMap<Material, Vec<Hit>>,
+    games: usize,
+}
+
+impl PositionIndex {
+    pub fn new() -> PositionIndex {
+        PositionIndex::default()
+    }
+
+    // The number of games added
+    pub fn len(&self) -> usize {
+        self.games
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.games == 0
+    }
+
+    // Adds the main line of tree and returns the number of the game
+    pub fn add_tree(&mut self, tree: &GameTree) -> usize {
+        let mut positions = vec![tree.root().game()];
+        let mut node = tree.root();
+        while let Some(child) = node.children().first() {
+            positions.push(child.game());
+            node = child;
+        }
+        self.add_positions(positions)
+    }
+
+    // Adds the game that starts at start and goes on with moves and returns
+    // the number of the game
+    // Fails with the index of the first move that is not legal, and then
+    // nothing is added
+    pub fn add_game(&mut self, start: &Game, moves: &[Move]) -> Result<usize, IllegalMoveError> {
+        let mut positions = vec![start.clone()];
+        for (i, &mv) in moves.iter().enumerate() {
+            let game = positions.last().expect("starts with start");
+            if game.find_move(mv.from, mv.to, mv.promotion) != Some(mv) {
+                return Err(IllegalMoveError(i));
+            }
+            positions.push(game.clone().apply_move(mv));
+        }
+        Ok(self.add_positions(positions.iter()))
+    }
+
+    // Adds every game read by reader and returns the errors of those that
+    // could not be read
+    // Games that could not be read still take up a number, so the games of a
+    // file added to an empty index are numbered in the order of the file
+    // from 0
+    pub fn add_pgn<R: Read>(&mut self, mut reader: PgnReader<R>) -> Vec<ReadError> {
+        let mut errors: Vec<ReadError> = Vec::new();
+        while let Some(text) = reader.next_text() {
+            match text.and_then(|text| text.parse_tree()) {
+                Ok((_, tree)) => {
+                    self.add_tree(&tree);
+                }
+                Err(e @ ReadError::Io(_)) => errors.push(e),
+                Err(e) => {
+                    self.games += 1;
+                    errors.push(e);
+                }
+            }
+        }
+        errors
+    }
+
+    fn add_positions<'a>(&mut self, positions: impl IntoIterator<Item = &'a Game>) -> usize {
+        let game = self.games;
+        self.games += 1;
+        for (ply, position) in positions.into_iter().enumerate() {
+            let hit = Hit { game, ply };
+            self.positions
+                .entry(position.position_hash())
+                .or_default()
+                .push(hit);
+            let hits = self.materials.entry(Material::of(position)).or_default();
+            if hits.last().is_none_or(|last| last.game != game) {
+                hits.push(hit);
+            }
+        }
+        game
+    }
+
+    // Every time a game reached the current position of game, with the same
+    // player to move, castling rights and en passant capture
+    // Positions are compared by their hash, see Game::position_hash
+    pub fn find_position(&self, game: &Game) -> &[Hit] {
+        self.find_hash(game.position_hash())
+    }
+
+    pub fn find_fen(&self, fen: &str) -> Result<&[Hit], FenError> {
+        Ok(self.find_position(&Game::from_fen(fen)?))
+    }
+
+    pub fn find_hash(&self, hash: u64) -> &[Hit] {
+        self.positions.get(&hash).map_or(&[], Vec::as_slice)
+    }
+
+    // The first ply at which each game had exactly material on the board,
+    // e.g. "KRPvKR".parse() for white rook and pawn against rook
+    pub fn find_material(&self, material: &Material) -> &[Hit] {
+        self.materials.get(material).map_or(&[], Vec::as_slice)
+    }
+
+    // The games that reached any position matching either colour of
+    // material, e.g. rook and pawn against rook whichever side has the pawn
+    pub fn find_material_either_side(&self, material: &Material) -> Vec<Hit> {
+        let mut hits = self.find_material(material).to_vec();
+        if material.flipped() != *material {
+            hits.extend_from_slice(self.find_material(&material.flipped()));
+            hits.sort();
+        }
+        hits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::san::parse_san;
+
+    // The moves of a game from the initial position, given in SAN
+    fn moves(line: &str) -> Vec<Move> {
+        let mut game = Game::new();
+        let mut moves = Vec::new();
+        for san in line.split_whitespace() {
+            let mv = parse_san(&game, san).expect("legal move");
+            game = game.play_move(mv);
+            moves.push(mv);
+        }
+        moves
+    }
+
+    #[test]
+    fn material_round_trip() {
+        let material: Material = "KQPvKR".parse().unwrap();
+        assert_eq!(material.count(true, PieceType::Queen), 1);
+        assert_eq!(material.count(true, PieceType::Pawn), 1);
+        assert_eq!(material.count(false, PieceType::Rook), 1);
+        assert_eq!(material.count(false, PieceType::Pawn), 0);
+        assert_eq!(material.to_string(), "KQPvKR");
+        assert_eq!(material.flipped().to_string(), "KRvKQP");
+        assert_eq!("qp vs r".parse::<Material>(), Ok(material));
+        assert_eq!(
+            Material::of(&Game::new()).to_string(),
+            "KQRRBBNNPPPPPPPPvKQRRBBNNPPPPPPPP"
+        );
+
+        for bad in ["KQP", "KXvK", "KQvK1", ""] {
+            assert_eq!(bad.parse::<Material>(), Err(MaterialError(bad.to_string())));
+        }
+    }
+
+    #[test]
+    fn transpositions_are_found() {
+        let mut index = PositionIndex::new();
+        let start = Game::new();
+        assert_eq!(index.add_game(&start, &moves("e4 e5 Nf3 Nc6")), Ok(0));
+        assert_eq!(index.add_game(&start, &moves("Nf3 Nc6 e4 e5 Bb5")), Ok(1));
+        assert_eq!(index.len(), 2);
+
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+        assert_eq!(
+            index.find_fen(fen).unwrap(),
+            [Hit { game: 0, ply: 4 }, Hit { game: 1, ply: 4 }]
+        );
+        assert_eq!(
+            index.find_position(&Game::new()),
+            [Hit { game: 0, ply: 0 }, Hit { game: 1, ply: 0 }]
+        );
+        // After 1. e4 e5 and 1. Nf3 Nc6 the games have not met yet
+        let after_e4_e5 = Game::new().play_move(moves("e4")[0]);
+        assert_eq!(index.find_position(&after_e4_e5), [Hit { game: 0, ply: 1 }]);
+    }
+
+    #[test]
+    fn illegal_move_adds_nothing() {
+        let mut index = PositionIndex::new();
+        let mut line = moves("e4 e5 Nf3");
+        // Nf3 again, from a square the knight has left
+        line.push(line[2]);
+        assert_eq!(
+            index.add_game(&Game::new(), &line),
+            Err(IllegalMoveError(3))
+        );
+        assert!(index.is_empty());
+        assert!(index.find_position(&Game::new()).is_empty());
+    }
+
+    #[test]
+    fn material_hits() {
+        let mut index = PositionIndex::new();
+        // Black takes a pawn with dxe4, white takes it back with Nxe4
+        let line = moves("e4 d5 Nc3 dxe4 Nxe4 Qd4 Nc3 Qxd2+");
+        index.add_game(&Game::new(), &line).unwrap();
+        index.add_game(&Game::new(), &moves("d4 d5")).unwrap();
+
+        let start = Material::of(&Game::new());
+        assert_eq!(
+            index.find_material(&start),
+            [Hit { game: 0, ply: 0 }, Hit { game: 1, ply: 0 }]
+        );
+        let pawn_down = "KQRRBBNNPPPPPPPvKQRRBBNNPPPPPPPP".parse().unwrap();
+        assert_eq!(index.find_material(&pawn_down), [Hit { game: 0, ply: 4 }]);
+        // Only the first ply with the material counts
+        let pawn_each: Material = "KQRRBBNNPPPPPPPvKQRRBBNNPPPPPPP".parse().unwrap();
+        assert_eq!(index.find_material(&pawn_each), [Hit { game: 0, ply: 5 }]);
+        assert_eq!(
+            index.find_material_either_side(&pawn_down.flipped()),
+            [Hit { game: 0, ply: 4 }]
+        );
+        assert!(index.find_material(&"KvK".parse().unwrap()).is_empty());
+    }
+}
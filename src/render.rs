@@ -0,0 +1,279 @@
+use crate::{File, Game, PieceType, Pos, Rank, Square};
+
+const RESET: &str = "\x1b[0m";
+const LIGHT: &str = "\x1b[48;5;180m";
+const DARK: &str = "\x1b[48;5;137m";
+const LIGHT_LAST_MOVE: &str = "\x1b[48;5;186m";
+const DARK_LAST_MOVE: &str = "\x1b[48;5;143m";
+const DESTINATION: &str = "\x1b[48;5;71m";
+const CHECK: &str = "\x1b[48;5;160m";
+const WHITE_PIECE: &str = "\x1b[1;97m";
+const BLACK_PIECE: &str = "\x1b[1;30m";
+
+// Options for render_board, RenderOptions::default() gives the plain ASCII
+// board seen from white's side with coordinates on all sides
+#[derive(Clone)]
+pub struct RenderOptions {
+    // Unicode chess glyphs instead of letters (uppercase white, lowercase black)
+    pub unicode: bool,
+    // ANSI colours for light/dark squares, pieces and highlights
+    // Without colour, highlighted squares are marked with brackets instead
+    pub color: bool,
+    // Draw the board from black's side
+    pub flipped: bool,
+    // File letters above and below and rank numbers on both sides
+    pub coordinates: bool,
+    // Highlight the from and to squares of the last move
+    pub last_move: bool,
+    // Highlight the squares the piece on this square can legally move to,
+    // nothing if it is not that player's turn or the game is over
+    pub destinations_from: Option<Pos>,
+    // Highlight the king of the player in check
+    pub check: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions {
+            unicode: false,
+            color: false,
+            flipped: false,
+            coordinates: true,
+            last_move: true,
+            destinations_from: None,
+            check: true,
+        }
+    }
+}
+
+enum Highlight {
+    None,
+    LastMove,
+    Destination,
+    Check,
+}
+
+// Renders the board of game as a string, one line per rank, according to options
+// Nothing is printed, the caller decides where the string goes
+pub fn render_board(game: &Game, options: &RenderOptions) -> String {
+    let last_move = match options.last_move {
+        true => game.last_move(),
+        false => None,
+    };
+    let destinations: Vec<Pos> = match options.destinations_from {
+        Some(from) => game
+            .legal_moves_from(from)
+            .into_iter()
+            .map(|mv| mv.to)
+            .collect(),
+        None => Vec::new(),
+    };
+    let checked_king = match options.check {
+        true => checked_king(game),
+        false => None,
+    };
+
+    let ranks: Vec<Rank> = match options.flipped {
+        true => Rank::all().collect(),
+        false => Rank::all().rev().collect(),
+    };
+    let files: Vec<File> = match options.flipped {
+        true => File::all().rev().collect(),
+        false => File::all().collect(),
+    };
+
+    let mut files_line = String::from("  ");
+    for &file in &files {
+        files_line.push(' ');
+        files_line.push(file.to_char());
+        files_line.push(' ');
+    }
+    files_line.push('\n');
+
+    let mut out = String::new();
+    if options.coordinates {
+        out.push_str(&files_line);
+    }
+
+    for &rank in &ranks {
+        if options.coordinates {
+            out.push_str(&format!("{} ", rank));
+        }
+        for &file in &files {
+            let pos = Pos::new(file, rank);
+            let square = game.square(pos);
+
+            let highlight = if checked_king == Some(pos) {
+                Highlight::Check
+            } else if destinations.contains(&pos) {
+                Highlight::Destination
+            } else if let Some(mv) = last_move {
+                if mv.from == pos || mv.to == pos {
+                    Highlight::LastMove
+                } else {
+                    Highlight::None
+                }
+            } else {
+                Highlight::None
+            };
+
+            out.push_str(&render_square(square, &highlight, options));
+        }
+        if options.coordinates {
+            out.push_str(&format!(" {}", rank));
+        }
+        out.push('\n');
+    }
+
+    if options.coordinates {
+        out.push_str(&files_line);
+    }
+    out
+}
+
+// Returns the glyph for the piece on square: a letter (uppercase for white),
+// a Unicode chess symbol, or '.' for an empty square
+// filled selects the solid Unicode symbols for both colours, used when the
+// colour of the piece is shown by ANSI colours instead
+pub fn piece_glyph(square: &Square, unicode: bool, filled: bool) -> char {
+    if !square.occupied {
+        return '.';
+    }
+    let white = square.piece.white;
+
+    if unicode {
+        let outlined = white && !filled;
+        return match (&square.piece.piece_type, outlined) {
+            (PieceType::King, true) => '♔',
+            (PieceType::Queen, true) => '♕',
+            (PieceType::Rook, true) => '♖',
+            (PieceType::Bishop, true) => '♗',
+            (PieceType::Knight, true) => '♘',
+            (PieceType::Pawn, true) => '♙',
+            (PieceType::King, false) => '♚',
+            (PieceType::Queen, false) => '♛',
+            (PieceType::Rook, false) => '♜',
+            (PieceType::Bishop, false) => '♝',
+            (PieceType::Knight, false) => '♞',
+            (PieceType::Pawn, false) => '♟',
+            (PieceType::Unoccupied, _) => '.',
+        };
+    }
+
+    let letter = square.piece.piece_type.letter();
+    match white {
+        true => letter,
+        false => letter.to_ascii_lowercase(),
+    }
+}
+
+fn render_square(square: &Square, highlight: &Highlight, options: &RenderOptions) -> String {
+    let glyph = piece_glyph(square, options.unicode, options.color);
+
+    if !options.color {
+        let (left, right) = match highlight {
+            Highlight::None => (' ', ' '),
+            Highlight::LastMove => ('[', ']'),
+            Highlight::Destination => ('(', ')'),
+            Highlight::Check => ('!', '!'),
+        };
+        return format!("{}{}{}", left, glyph, right);
+    }
+
+    let light = (square.pos.file().index() + square.pos.rank().index()) % 2 == 1;
+    let background = match (highlight, light) {
+        (Highlight::None, true) => LIGHT,
+        (Highlight::None, false) => DARK,
+        (Highlight::LastMove, true) => LIGHT_LAST_MOVE,
+        (Highlight::LastMove, false) => DARK_LAST_MOVE,
+        (Highlight::Destination, _) => DESTINATION,
+        (Highlight::Check, _) => CHECK,
+    };
+    let (foreground, glyph) = match (square.occupied, square.piece.white) {
+        (true, true) => (WHITE_PIECE, glyph),
+        (true, false) => (BLACK_PIECE, glyph),
+        (false, _) => ("", ' '),
+    };
+    format!("{}{} {} {}", background, foreground, glyph, RESET)
+}
+
+// Square of the king that is in check, if any
+// After mate the turn does not pass, so the mated king is the other player's
+fn checked_king(game: &Game) -> Option<Pos> {
+    let white_king = game.white_turn != game.mate;
+    let king = match white_king {
+        true => game.w_king_pos,
+        false => game.b_king_pos,
+    };
+    match game.boards.attacked_by(!white_king, king) {
+        true => Some(king),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ASCII: &str = "   a  b  c  d  e  f  g  h \n\
+                         8  r  n  b  q  k  b  n  r  8\n\
+                         7  p  p  p  p  p  p  p  p  7\n\
+                         6  .  .  .  .  .  .  .  .  6\n\
+                         5  .  .  .  .  .  .  .  .  5\n\
+                         4  .  .  .  .  .  .  .  .  4\n\
+                         3  .  .  .  .  .  .  .  .  3\n\
+                         2  P  P  P  P  P  P  P  P  2\n\
+                         1  R  N  B  Q  K  B  N  R  1\n   \
+                         a  b  c  d  e  f  g  h \n";
+
+    const ASCII_FLIPPED: &str = "   h  g  f  e  d  c  b  a \n\
+                                 1  R  N  B  K  Q  B  N  R  1\n\
+                                 2  P  P  P  P  P  P  P  P  2\n\
+                                 3  .  .  .  .  .  .  .  .  3\n\
+                                 4  .  .  .  .  .  .  .  .  4\n\
+                                 5  .  .  .  .  .  .  .  .  5\n\
+                                 6  .  .  .  .  .  .  .  .  6\n\
+                                 7  p  p  p  p  p  p  p  p  7\n\
+                                 8  r  n  b  k  q  b  n  r  8\n   \
+                                 h  g  f  e  d  c  b  a \n";
+
+    const UNICODE: &str = "   a  b  c  d  e  f  g  h \n\
+                           8  ♜  ♞  ♝  ♛  ♚  ♝  ♞  ♜  8\n\
+                           7  ♟  ♟  ♟  ♟  ♟  ♟  ♟  ♟  7\n\
+                           6  .  .  .  .  .  .  .  .  6\n\
+                           5  .  .  .  .  .  .  .  .  5\n\
+                           4  .  .  .  .  .  .  .  .  4\n\
+                           3  .  .  .  .  .  .  .  .  3\n\
+                           2  ♙  ♙  ♙  ♙  ♙  ♙  ♙  ♙  2\n\
+                           1  ♖  ♘  ♗  ♕  ♔  ♗  ♘  ♖  1\n   \
+                           a  b  c  d  e  f  g  h \n";
+
+    const UNICODE_FLIPPED: &str = "   h  g  f  e  d  c  b  a \n\
+                                   1  ♖  ♘  ♗  ♔  ♕  ♗  ♘  ♖  1\n\
+                                   2  ♙  ♙  ♙  ♙  ♙  ♙  ♙  ♙  2\n\
+                                   3  .  .  .  .  .  .  .  .  3\n\
+                                   4  .  .  .  .  .  .  .  .  4\n\
+                                   5  .  .  .  .  .  .  .  .  5\n\
+                                   6  .  .  .  .  .  .  .  .  6\n\
+                                   7  ♟  ♟  ♟  ♟  ♟  ♟  ♟  ♟  7\n\
+                                   8  ♜  ♞  ♝  ♚  ♛  ♝  ♞  ♜  8\n   \
+                                   h  g  f  e  d  c  b  a \n";
+
+    #[test]
+    fn start_position() {
+        let game = Game::new();
+        for (unicode, flipped, expected) in [
+            (false, false, ASCII),
+            (false, true, ASCII_FLIPPED),
+            (true, false, UNICODE),
+            (true, true, UNICODE_FLIPPED),
+        ] {
+            let options = RenderOptions {
+                unicode,
+                flipped,
+                ..RenderOptions::default()
+            };
+            assert_eq!(render_board(&game, &options), expected);
+        }
+    }
+}
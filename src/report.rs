@@ -0,0 +1,442 @@
+use crate::pgn::result;
+use crate::search::{analyse, piece_value, Limit};
+use crate::{Game, Move, Pos};
+use std::fmt;
+
+// A summary of a game: how many moves, captures and checks each side made,
+// the material balance after every move and, when an engine looked at the
+// game, how much each move lost compared to the engine's choice
+//
+// A report is built by replaying the moves from the starting position, e.g.
+// GameReport::new(&Game::new(), &game.history)
+
+// Centipawn losses from which a move counts as an inaccuracy, a mistake and a blunder
+const INACCURACY: i32 = 50;
+const MISTAKE: i32 = 100;
+const BLUNDER: i32 = 300;
+// Scores are capped at this many centipawns, so that a missed mate does not
+// count for more than losing a whole lot of material
+const SCORE_CAP: i32 = 1000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Judgement {
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Judgement {
+    fn of(loss: i32) -> Option<Judgement> {
+        match loss {
+            l if l >= BLUNDER => Some(Judgement::Blunder),
+            l if l >= MISTAKE => Some(Judgement::Mistake),
+            l if l >= INACCURACY => Some(Judgement::Inaccuracy),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Judgement::Inaccuracy => "inaccuracy",
+            Judgement::Mistake => "mistake",
+            Judgement::Blunder => "blunder",
+        }
+    }
+}
+
+// What the engine made of one move
+#[derive(Clone, Debug, PartialEq)]
+pub struct MoveReview {
+    // 1 for the first move of the game
+    pub ply: usize,
+    pub white: bool,
+    pub san: String,
+    // The move the engine would have played, in SAN
+    pub best: String,
+    // The score before the move, in centipawns from white's point of view
+    pub score: i32,
+    // How many centipawns worse the move is than the engine's, at most 2000
+    // as scores are capped at 1000 either way
+    pub loss: i32,
+    pub judgement: Option<Judgement>,
+}
+
+// The numbers of one player
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SideReport {
+    pub moves: usize,
+    pub captures: usize,
+    pub checks: usize,
+    pub promotions: usize,
+    pub castled: bool,
+    // The engine's verdict, None and 0 without an engine or without moves
+    pub average_loss: Option<f64>,
+    // 0 to 100, from how much each move lowered the chance to win
+    pub accuracy: Option<f64>,
+    pub inaccuracies: usize,
+    pub mistakes: usize,
+    pub blunders: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameReport {
+    // The number of half moves
+    pub plies: usize,
+    // "1-0", "0-1", "1/2-1/2" or "*" as in PGN
+    pub result: &'static str,
+    pub white: SideReport,
+    pub black: SideReport,
+    // White's material less black's in centipawns, before the first move and
+    // after every move
+    pub material: Vec<i32>,
+    // One review per move, empty when the report was made without an engine
+    pub reviews: Vec<MoveReview>,
+}
+
+impl GameReport {
+    // The report of the game that starts at start and goes on with moves, up
+    // to the first move that is not legal
+    pub fn new(start: &Game, moves: &[Move]) -> GameReport {
+        GameReport::from_positions(&replay(start, moves))
+    }
+
+    fn from_positions(positions: &[Game]) -> GameReport {
+        let mut report = GameReport {
+            plies: positions.len() - 1,
+            result: result(positions.last().expect("starts with start")),
+            white: SideReport::default(),
+            black: SideReport::default(),
+            material: positions.iter().map(material).collect(),
+            reviews: Vec::new(),
+        };
+
+        for pair in positions.windows(2) {
+            let (before, after) = (&pair[0], &pair[1]);
+            let mv = after.last_move().expect("replayed with a move");
+            let side = report.side_mut(before.side_to_move());
+            side.moves += 1;
+            side.captures += mv.captured.is_some() as usize;
+            side.promotions += mv.promotion.is_some() as usize;
+            side.castled |= mv.castle;
+            side.checks += after
+                .move_history
+                .last()
+                .is_some_and(|san| san.ends_with(['+', '#'])) as usize;
+        }
+        report
+    }
+
+    // new, with every position searched by the engine to limit so that each
+    // move can be compared with the engine's choice
+    // This plays the engine once per position, so it takes a while for long
+    // games and deep limits
+    pub fn analysed(start: &Game, moves: &[Move], limit: Limit) -> GameReport {
+        let positions = replay(start, moves);
+        let mut report = GameReport::from_positions(&positions);
+
+        // Scores from the point of view of the player to move in each position
+        let searched: Vec<(Option<Move>, i32)> = positions
+            .iter()
+            .map(|game| match analyse(game, limit) {
+                Some((mv, score)) => (Some(mv), score.clamp(-SCORE_CAP, SCORE_CAP)),
+                None if game.mate => (None, -SCORE_CAP),
+                None => (None, 0),
+            })
+            .collect();
+
+        for (i, pair) in positions.windows(2).enumerate() {
+            let before = &pair[0];
+            let white = before.side_to_move();
+            let (best, score) = searched[i];
+            let played = -searched[i + 1].1;
+            let loss = (score - played).max(0);
+            let review = MoveReview {
+                ply: i + 1,
+                white,
+                san: pair[1].move_history.last().cloned().unwrap_or_default(),
+                best: best.map_or(String::new(), |mv| san_of(before, mv)),
+                score: if white { score } else { -score },
+                loss,
+                judgement: Judgement::of(loss),
+            };
+            let side = report.side_mut(white);
+            match review.judgement {
+                Some(Judgement::Inaccuracy) => side.inaccuracies += 1,
+                Some(Judgement::Mistake) => side.mistakes += 1,
+                Some(Judgement::Blunder) => side.blunders += 1,
+                None => (),
+            }
+            report.reviews.push(review);
+        }
+
+        for white in [true, false] {
+            let reviews: Vec<&MoveReview> =
+                report.reviews.iter().filter(|r| r.white == white).collect();
+            if reviews.is_empty() {
+                continue;
+            }
+            let count = reviews.len() as f64;
+            let loss: i32 = reviews.iter().map(|r| r.loss).sum();
+            let accuracy: f64 = reviews
+                .iter()
+                .map(|r| {
+                    let before = if white { r.score } else { -r.score };
+                    move_accuracy(win_chance(before), win_chance(before - r.loss))
+                })
+                .sum();
+            let side = report.side_mut(white);
+            side.average_loss = Some(loss as f64 / count);
+            side.accuracy = Some(accuracy / count);
+        }
+        report
+    }
+
+    fn side_mut(&mut self, white: bool) -> &mut SideReport {
+        match white {
+            true => &mut self.white,
+            false => &mut self.black,
+        }
+    }
+
+    // The report as a JSON object, e.g.
+    // {"plies": 2, "result": "*", "white": {"moves": 1, ...}, "black": {...},
+    //  "material": [0, 0, 0], "reviews": []}
+    pub fn to_json(&self) -> String {
+        let reviews: Vec<String> = self
+            .reviews
+            .iter()
+            .map(|r| {
+                format!(
+                    "{{\"ply\": {}, \"san\": {}, \"best\": {}, \"score\": {}, \"loss\": {}, \"judgement\": {}}}",
+                    r.ply,
+                    json_string(&r.san),
+                    json_string(&r.best),
+                    r.score,
+                    r.loss,
+                    r.judgement.map_or("null".to_string(), |j| json_string(j.name()))
+                )
+            })
+            .collect();
+        let material: Vec<String> = self.material.iter().map(i32::to_string).collect();
+        format!(
+            "{{\"plies\": {}, \"result\": {}, \"white\": {}, \"black\": {}, \"material\": [{}], \"reviews\": [{}]}}",
+            self.plies,
+            json_string(self.result),
+            self.white.to_json(),
+            self.black.to_json(),
+            material.join(", "),
+            reviews.join(", ")
+        )
+    }
+}
+
+impl SideReport {
+    fn to_json(&self) -> String {
+        format!(
+            "{{\"moves\": {}, \"captures\": {}, \"checks\": {}, \"promotions\": {}, \"castled\": {}, \"average_loss\": {}, \"accuracy\": {}, \"inaccuracies\": {}, \"mistakes\": {}, \"blunders\": {}}}",
+            self.moves,
+            self.captures,
+            self.checks,
+            self.promotions,
+            self.castled,
+            json_number(self.average_loss),
+            json_number(self.accuracy),
+            self.inaccuracies,
+            self.mistakes,
+            self.blunders
+        )
+    }
+}
+
+// The report as text for a terminal, one line per player and then the
+// moves the engine found fault with
+impl fmt::Display for GameReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} moves, result {}, final material {:+}",
+            self.plies.div_ceil(2),
+            self.result,
+            self.material.last().copied().unwrap_or(0)
+        )?;
+        for (name, side) in [("White", &self.white), ("Black", &self.black)] {
+            write!(
+                f,
+                "{}: {} captures, {} checks, {} promotions{}",
+                name,
+                side.captures,
+                side.checks,
+                side.promotions,
+                if side.castled { ", castled" } else { "" }
+            )?;
+            if let (Some(loss), Some(accuracy)) = (side.average_loss, side.accuracy) {
+                write!(
+                    f,
+                    "; average loss {:.0}, accuracy {:.1}%, {} inaccuracies, {} mistakes, {} blunders",
+                    loss, accuracy, side.inaccuracies, side.mistakes, side.blunders
+                )?;
+            }
+            writeln!(f)?;
+        }
+        for review in &self.reviews {
+            if let Some(judgement) = review.judgement {
+                let number = match review.white {
+                    true => format!("{}.", review.ply.div_ceil(2)),
+                    false => format!("{}...", review.ply.div_ceil(2)),
+                };
+                writeln!(
+                    f,
+                    "{} {} is a {} ({} centipawns), best was {}",
+                    number,
+                    review.san,
+                    judgement.name(),
+                    review.loss,
+                    review.best
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// The positions of the game, start first
+fn replay(start: &Game, moves: &[Move]) -> Vec<Game> {
+    let mut positions = vec![start.clone()];
+    for &mv in moves {
+        let game = positions.last().expect("starts with start");
+        if game.find_move(mv.from, mv.to, mv.promotion) != Some(mv) {
+            break;
+        }
+        positions.push(game.clone().apply_move(mv));
+    }
+    positions
+}
+
+// The move in SAN with its check or mate mark
+fn san_of(game: &Game, mv: Move) -> String {
+    let after = game.clone().apply_move(mv);
+    after.move_history.last().cloned().unwrap_or_default()
+}
+
+fn material(game: &Game) -> i32 {
+    Pos::all()
+        .map(|pos| &game.square(pos).piece)
+        .map(|piece| match piece.white {
+            true => piece_value(&piece.piece_type),
+            false => -piece_value(&piece.piece_type),
+        })
+        .sum()
+}
+
+// The chance to win in percent for a score in centipawns, from the curve
+// Lichess fitted to its games
+fn win_chance(score: i32) -> f64 {
+    50.0 + 50.0 * (2.0 / (1.0 + (-0.00368208 * score as f64).exp()) - 1.0)
+}
+
+// Lichess' accuracy of a move from the chance to win before and after it
+// A move that keeps the chance to win is 100, the others get Lichess' bonus
+// of 1 for the imprecision of the curve
+fn move_accuracy(before: f64, after: f64) -> f64 {
+    if after >= before {
+        return 100.0;
+    }
+    let accuracy = 103.1668 * (-0.04354 * (before - after)).exp() - 3.1669 + 1.0;
+    accuracy.clamp(0.0, 100.0)
+}
+
+fn json_string(s: &str) -> String {
+    let mut json = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+fn json_number(n: Option<f64>) -> String {
+    match n {
+        Some(n) => format!("{:.1}", n),
+        None => "null".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::san::parse_san;
+
+    fn moves(start: &Game, line: &str) -> Vec<Move> {
+        let mut game = start.clone();
+        let mut moves = Vec::new();
+        for san in line.split_whitespace() {
+            let mv = parse_san(&game, san).expect("legal move");
+            game = game.play_move(mv);
+            moves.push(mv);
+        }
+        moves
+    }
+
+    #[test]
+    fn scholars_mate() {
+        let start = Game::new();
+        let report = GameReport::new(&start, &moves(&start, "e4 e5 Bc4 Nc6 Qh5 Nf6 Qxf7#"));
+        assert_eq!(report.plies, 7);
+        assert_eq!(report.result, "1-0");
+        assert_eq!((report.white.moves, report.black.moves), (4, 3));
+        assert_eq!((report.white.captures, report.black.captures), (1, 0));
+        assert_eq!((report.white.checks, report.black.checks), (1, 0));
+        assert_eq!(report.material, vec![0, 0, 0, 0, 0, 0, 0, 100]);
+        assert!(report.reviews.is_empty());
+        assert_eq!(report.white.accuracy, None);
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        let mut keys: Vec<&String> = json.as_object().unwrap().keys().collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            ["black", "material", "plies", "result", "reviews", "white"]
+        );
+        assert_eq!(json["result"], "1-0");
+        assert_eq!(json["white"]["captures"], 1);
+        assert_eq!(json["white"]["checks"], 1);
+        assert_eq!(json["white"]["accuracy"], serde_json::Value::Null);
+        assert_eq!(json["material"][7], 100);
+        assert_eq!(json["reviews"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn best_moves_are_fully_accurate() {
+        // Morphy's mate in two, played out with the engine's own moves
+        let start = Game::from_fen("kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1").unwrap();
+        let limit = Limit::Depth(4);
+        let mut game = start.clone();
+        let mut line = Vec::new();
+        while let Some((mv, _)) = analyse(&game, limit) {
+            line.push(mv);
+            game = game.play_move(mv);
+        }
+        assert!(game.mate);
+
+        let report = GameReport::analysed(&start, &line, limit);
+        assert_eq!(report.reviews.len(), 3);
+        assert!(report
+            .reviews
+            .iter()
+            .all(|r| r.loss == 0 && r.san == r.best));
+        assert!(report.reviews.iter().all(|r| r.judgement.is_none()));
+        assert_eq!(report.white.accuracy, Some(100.0));
+        assert_eq!(report.black.accuracy, Some(100.0));
+        assert_eq!(report.white.average_loss, Some(0.0));
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["white"]["accuracy"], 100.0);
+        assert_eq!(json["reviews"][0]["san"], "Ra6");
+        assert_eq!(json["reviews"][0]["judgement"], serde_json::Value::Null);
+    }
+}
@@ -0,0 +1,133 @@
+use crate::{Game, Move, PieceType, Pos};
+
+// Returns the SAN (standard algebraic notation) for mv in the current
+// position of game, e.g. "Nbd7", "exd5", "e8=Q" or "O-O"
+// The check and mate suffixes are not included since they depend on the
+// position after the move, do_turn adds them once that position is known
+pub fn move_to_san(game: &Game, mv: &Move) -> String {
+    let (from, to) = (mv.from, mv.to);
+    let piece = &game.square(from).piece;
+    let capture = mv.captured.is_some() || mv.en_passant;
+    let mut san = String::new();
+
+    if mv.castle {
+        return match to.file() > from.file() {
+            true => "O-O".to_string(),
+            false => "O-O-O".to_string(),
+        };
+    }
+
+    match piece.piece_type {
+        PieceType::Pawn => {
+            if capture {
+                san.push(from.file().to_char());
+                san.push('x');
+            }
+            san.push_str(&to.to_string());
+            if let Some(piece_type) = mv.promotion {
+                san.push('=');
+                san.push(piece_type.letter());
+            }
+            return san;
+        }
+        PieceType::Unoccupied => return san,
+        _ => san.push(piece.piece_type.letter()),
+    }
+
+    // Other pieces of the same kind that can also reach "to"
+    let rivals: Vec<Pos> = Pos::all()
+        .filter(|&pos| {
+            let square = game.square(pos);
+            pos != from
+                && square.occupied
+                && square.piece.piece_type == piece.piece_type
+                && square.piece.white == piece.white
+                && square.piece.moves.contains(&to)
+        })
+        .collect();
+
+    if !rivals.is_empty() {
+        if rivals.iter().all(|r| r.file() != from.file()) {
+            san.push(from.file().to_char());
+        } else if rivals.iter().all(|r| r.rank() != from.rank()) {
+            san.push(from.rank().to_char());
+        } else {
+            san.push_str(&from.to_string());
+        }
+    }
+
+    if capture {
+        san.push('x');
+    }
+    san.push_str(&to.to_string());
+    san
+}
+
+// Returns the legal move of game written in SAN, e.g. "Nf3", "exd5+" or "O-O"
+// Check, mate and !? suffixes are ignored, and some common deviations are
+// accepted: "0-0" for castling, a promotion without "=", and more
+// disambiguation than needed, e.g. "Ng1f3"
+pub fn parse_san(game: &Game, san: &str) -> Option<Move> {
+    let san = san.trim_end_matches(['+', '#', '!', '?']);
+    let moves = game.moves();
+
+    let castle = match san {
+        "O-O" | "0-0" => Some(true),
+        "O-O-O" | "0-0-0" => Some(false),
+        _ => None,
+    };
+    if let Some(king_side) = castle {
+        return moves
+            .into_iter()
+            .find(|mv| mv.castle && (mv.to.file() > mv.from.file()) == king_side);
+    }
+    if let Some(&mv) = moves.iter().find(|mv| move_to_san(game, mv) == san) {
+        return Some(mv);
+    }
+
+    let mut chars: Vec<char> = san.chars().filter(|c| !"x:-=".contains(*c)).collect();
+    let piece = match chars.first().and_then(|&c| piece_type(c)) {
+        Some(piece_type) => {
+            chars.remove(0);
+            piece_type
+        }
+        None => PieceType::Pawn,
+    };
+    let promotion = match (piece, chars.last()) {
+        (PieceType::Pawn, Some(&c)) if !c.is_ascii_digit() => {
+            chars.pop();
+            Some(piece_type(c.to_ascii_uppercase())?)
+        }
+        _ => None,
+    };
+    if chars.len() < 2 {
+        return None;
+    }
+    let to: String = chars.split_off(chars.len() - 2).into_iter().collect();
+    let to = to.parse::<Pos>().ok()?;
+
+    let mut found = moves.into_iter().filter(|mv| {
+        mv.piece == piece
+            && mv.to == to
+            && !mv.castle
+            && mv.promotion == promotion.or(mv.promotion.and(Some(PieceType::Queen)))
+            && chars
+                .iter()
+                .all(|&hint| hint == mv.from.file().to_char() || hint == mv.from.rank().to_char())
+    });
+    match (found.next(), found.next()) {
+        (Some(mv), None) => Some(mv),
+        _ => None,
+    }
+}
+
+fn piece_type(letter: char) -> Option<PieceType> {
+    match letter {
+        'N' => Some(PieceType::Knight),
+        'B' => Some(PieceType::Bishop),
+        'R' => Some(PieceType::Rook),
+        'Q' => Some(PieceType::Queen),
+        'K' => Some(PieceType::King),
+        _ => None,
+    }
+}
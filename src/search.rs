@@ -0,0 +1,767 @@
+use crate::transposition::{move_key, Bound, Entry, Table};
+use crate::{Game, Move, PieceType, Pos};
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const MATE: i32 = 100_000;
+const INFINITY: i32 = 1_000_000;
+const MAX_DEPTH: u32 = 64;
+// Scores at least this far from 0 are mates, quiescence included
+const MATE_BOUND: i32 = MATE - 2 * MAX_DEPTH as i32;
+// Captures are followed this many plies past the nominal depth
+const QUIESCENCE_DEPTH: u32 = 4;
+// The size of the transposition table the threads of a search share
+const TABLE_MB: usize = 16;
+
+// How long the engine is allowed to think
+#[derive(Clone, Copy)]
+pub enum Limit {
+    // Search exactly this many plies
+    Depth(u32),
+    // Deepen iteratively until the time runs out
+    Time(Duration),
+    // Deepen iteratively until stopped through SearchOptions::stop
+    Infinite,
+}
+
+// Settings for search, SearchOptions::default() gives one line and no way to
+// stop the search early
+#[derive(Clone, Debug)]
+pub struct SearchOptions {
+    // How many of the best moves to find a line for, as in multi_pv
+    pub lines: usize,
+    // Setting this to true from another thread stops the search, which then
+    // returns the best lines found so far
+    pub stop: Option<Arc<AtomicBool>>,
+    // The number of threads to search with
+    // More than one runs a Lazy SMP search: every thread searches the same
+    // position, sharing what they find through a transposition table, and the
+    // lines of the first thread are returned. That is faster on more cores
+    // but does not always give the same move twice, and a line ends early
+    // where its thread found the rest in the table. With one thread the
+    // search is the same as best_move and always gives the same result.
+    pub threads: usize,
+}
+
+impl Default for SearchOptions {
+    fn default() -> SearchOptions {
+        SearchOptions {
+            lines: 1,
+            stop: None,
+            threads: 1,
+        }
+    }
+}
+
+// What the search has found so far, sent for every line each time the
+// search gets one ply deeper
+#[derive(Clone, Debug, PartialEq)]
+pub struct Info {
+    pub depth: u32,
+    // Which line this is, 1 for the best
+    pub line: usize,
+    pub score: Score,
+    // Positions searched since the start
+    pub nodes: u64,
+    // Positions searched per second
+    pub nps: u64,
+    pub time: Duration,
+    pub pv: Vec<Move>,
+}
+
+// Written as a UCI info line, e.g.
+// "info depth 3 multipv 1 score cp 18 nodes 5120 nps 41000 time 124 pv b1c3 b8c6 g1f3"
+impl fmt::Display for Info {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "info depth {} multipv {} score {} nodes {} nps {} time {} pv",
+            self.depth,
+            self.line,
+            self.score,
+            self.nodes,
+            self.nps,
+            self.time.as_millis()
+        )?;
+        for mv in &self.pv {
+            write!(f, " {}", mv)?;
+        }
+        Ok(())
+    }
+}
+
+struct Search<'a> {
+    deadline: Option<Instant>,
+    stop: Option<Arc<AtomicBool>>,
+    // Set when the first thread of a multi-threaded search is done
+    done: Option<&'a AtomicBool>,
+    stopped: bool,
+    // Counted over all threads
+    nodes: &'a AtomicU64,
+    // Only multi-threaded searches have one
+    table: Option<&'a Table>,
+}
+
+impl Search<'_> {
+    // Whether the search has to stop, because time ran out or it was told to
+    fn should_stop(&mut self) -> bool {
+        if let Some(deadline) = self.deadline {
+            if Instant::now() >= deadline {
+                self.stopped = true;
+            }
+        }
+        let flags = [self.stop.as_deref(), self.done];
+        if flags
+            .into_iter()
+            .flatten()
+            .any(|flag| flag.load(Ordering::Relaxed))
+        {
+            self.stopped = true;
+        }
+        self.stopped
+    }
+}
+
+// A score as shown to the user
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Score {
+    // Centipawns from the point of view of the player to move
+    Centipawns(i32),
+    // Mate in this many moves, negative when the player to move gets mated
+    Mate(i32),
+}
+
+impl Score {
+    fn from_search(score: i32) -> Score {
+        if score >= MATE_BOUND {
+            Score::Mate((MATE - score + 1) / 2)
+        } else if score <= -MATE_BOUND {
+            Score::Mate(-(MATE + score) / 2)
+        } else {
+            Score::Centipawns(score)
+        }
+    }
+}
+
+// Written as in UCI, e.g. "cp 35" or "mate -2"
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Score::Centipawns(cp) => write!(f, "cp {}", cp),
+            Score::Mate(moves) => write!(f, "mate {}", moves),
+        }
+    }
+}
+
+// A line of play the engine expects, its first move is the candidate move
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line {
+    pub moves: Vec<Move>,
+    pub score: Score,
+    // The number of plies the line was searched to
+    pub depth: u32,
+}
+
+// A line with its score as the search keeps it
+struct Pv {
+    moves: Vec<Move>,
+    score: i32,
+}
+
+impl Pv {
+    fn to_line(&self, depth: u32) -> Line {
+        Line {
+            moves: self.moves.clone(),
+            score: Score::from_search(self.score),
+            depth,
+        }
+    }
+}
+
+// Where the search is at the end of an iteration
+struct Iteration<'a> {
+    depth: u32,
+    lines: &'a [Pv],
+    nodes: u64,
+    time: Duration,
+}
+
+// Searches the position for the player whose turn it is and returns the best
+// move found, or None if there is no legal move
+pub fn best_move(game: &Game, limit: Limit) -> Option<Move> {
+    best_move_among(game, &game.moves(), limit)
+}
+
+// best_move, choosing only from moves, e.g. the moves a tablebase keeps
+pub fn best_move_among(game: &Game, moves: &[Move], limit: Limit) -> Option<Move> {
+    analyse_among(game, moves, limit).map(|(mv, _)| mv)
+}
+
+// Searches like best_move and also returns the score of the best move in
+// centipawns, from the point of view of the player to move
+// Mates score around 100000, less the number of plies to the mate
+pub fn analyse(game: &Game, limit: Limit) -> Option<(Move, i32)> {
+    analyse_among(game, &game.moves(), limit)
+}
+
+fn analyse_among(game: &Game, moves: &[Move], limit: Limit) -> Option<(Move, i32)> {
+    let options = SearchOptions::default();
+    let (_, lines) = search_root(game, moves, limit, &options, &mut |_| ())?;
+    lines.first().map(|line| (line.moves[0], line.score))
+}
+
+// Searches the position for the best count moves, or all of them if there
+// are fewer, and returns a line for each, best first
+// on_iteration is called with the lines found each time the search gets one
+// ply deeper, e.g. to show them while the search goes on
+// Returns no lines if there is no legal move
+pub fn multi_pv(
+    game: &Game,
+    count: usize,
+    limit: Limit,
+    mut on_iteration: impl FnMut(&[Line]),
+) -> Vec<Line> {
+    let options = SearchOptions {
+        lines: count,
+        ..SearchOptions::default()
+    };
+    let found = search_root(game, &game.moves(), limit, &options, &mut |iteration| {
+        let lines: Vec<Line> = iteration
+            .lines
+            .iter()
+            .map(|pv| pv.to_line(iteration.depth))
+            .collect();
+        on_iteration(&lines)
+    });
+    match found {
+        Some((depth, pvs)) => pvs.iter().map(|pv| pv.to_line(depth)).collect(),
+        None => Vec::new(),
+    }
+}
+
+// Searches like multi_pv with everything options allows, and calls on_info
+// with an Info for each line every time the search gets one ply deeper
+// When stopped, the lines of the deepest search that got far enough are
+// returned, so there is always a move to play unless there is no legal move
+pub fn search(
+    game: &Game,
+    limit: Limit,
+    options: &SearchOptions,
+    mut on_info: impl FnMut(&Info),
+) -> Vec<Line> {
+    let found = search_root(game, &game.moves(), limit, options, &mut |iteration| {
+        let seconds = iteration.time.as_secs_f64();
+        let nps = match seconds > 0.0 {
+            true => (iteration.nodes as f64 / seconds) as u64,
+            false => 0,
+        };
+        for (i, pv) in iteration.lines.iter().enumerate() {
+            on_info(&Info {
+                depth: iteration.depth,
+                line: i + 1,
+                score: Score::from_search(pv.score),
+                nodes: iteration.nodes,
+                nps,
+                time: iteration.time,
+                pv: pv.moves.clone(),
+            });
+        }
+    });
+    match found {
+        Some((depth, pvs)) => pvs.iter().map(|pv| pv.to_line(depth)).collect(),
+        None => Vec::new(),
+    }
+}
+
+// Iterative deepening over the root moves among moves, keeping the best
+// options.lines of them with their lines
+// Returns the depth of the lines and the lines, best first
+fn search_root(
+    game: &Game,
+    moves: &[Move],
+    limit: Limit,
+    options: &SearchOptions,
+    on_iteration: &mut dyn FnMut(&Iteration),
+) -> Option<(u32, Vec<Pv>)> {
+    let root_moves: Vec<Move> = ordered_moves(game)
+        .into_iter()
+        .filter(|mv| moves.contains(mv))
+        .collect();
+    if root_moves.is_empty() || game.mate || game.stalemate {
+        return None;
+    }
+    let count = options.lines.clamp(1, root_moves.len());
+
+    let start = Instant::now();
+    let (max_depth, deadline) = match limit {
+        Limit::Depth(depth) => (depth.max(1), None),
+        Limit::Time(time) => (MAX_DEPTH, Some(start + time)),
+        Limit::Infinite => (MAX_DEPTH, None),
+    };
+    let nodes = AtomicU64::new(0);
+    let new_search = |done, table| Search {
+        deadline,
+        stop: options.stop.clone(),
+        done,
+        stopped: false,
+        nodes: &nodes,
+        table,
+    };
+
+    if options.threads <= 1 {
+        let mut search = new_search(None, None);
+        return Some(deepen(
+            game,
+            root_moves,
+            count,
+            1..=max_depth,
+            start,
+            &mut search,
+            on_iteration,
+        ));
+    }
+
+    // Lazy SMP: the helper threads only fill the table for the first one
+    // Every other helper starts a ply deeper so that they do not all search
+    // the same tree in step
+    let table = Table::new(TABLE_MB);
+    let done = AtomicBool::new(false);
+    let found = thread::scope(|scope| {
+        for helper in 1..options.threads {
+            let mut search = new_search(Some(&done), Some(&table));
+            let root_moves = root_moves.clone();
+            let depths = 1 + helper as u32 % 2..=max_depth;
+            scope.spawn(move || {
+                deepen(
+                    game,
+                    root_moves,
+                    count,
+                    depths,
+                    start,
+                    &mut search,
+                    &mut |_| (),
+                )
+            });
+        }
+        let mut search = new_search(None, Some(&table));
+        let found = deepen(
+            game,
+            root_moves,
+            count,
+            1..=max_depth,
+            start,
+            &mut search,
+            on_iteration,
+        );
+        done.store(true, Ordering::Relaxed);
+        found
+    });
+    Some(found)
+}
+
+// The iterative deepening of search_root for one thread, from the first of
+// depths to the last or until stopped
+fn deepen(
+    game: &Game,
+    mut root_moves: Vec<Move>,
+    count: usize,
+    depths: RangeInclusive<u32>,
+    start: Instant,
+    search: &mut Search,
+    on_iteration: &mut dyn FnMut(&Iteration),
+) -> (u32, Vec<Pv>) {
+    // The first moves in order with the static evaluation stand in until an
+    // iteration gets far enough
+    let mut lines: Vec<Pv> = root_moves[..count]
+        .iter()
+        .map(|&mv| Pv {
+            moves: vec![mv],
+            score: evaluate(game),
+        })
+        .collect();
+    let mut lines_depth = 0;
+
+    for depth in depths {
+        // The best lines so far, best first
+        let mut found: Vec<Pv> = Vec::new();
+        let mut searched = 0;
+
+        for mv in &root_moves {
+            // A move only needs an exact score if it gets into the best count
+            let alpha = match found.len() == count {
+                true => found[count - 1].score,
+                false => -INFINITY,
+            };
+            let mut pv: Vec<Move> = Vec::new();
+            let score = match play(game, mv) {
+                Some(child) => -negamax(&child, depth - 1, 1, -INFINITY, -alpha, &mut pv, search),
+                None => MATE - 1,
+            };
+            if search.stopped {
+                break;
+            }
+            searched += 1;
+            if score > alpha {
+                pv.insert(0, *mv);
+                let at = found.iter().position(|line| line.score < score);
+                found.insert(at.unwrap_or(found.len()), Pv { moves: pv, score });
+                found.truncate(count);
+            }
+        }
+
+        // The best lines of the previous iteration are searched first, so an
+        // iteration cut short after them only keeps lines that were proven to
+        // be at least as good
+        if searched >= count {
+            lines = found;
+            lines_depth = depth;
+        }
+        if search.stopped {
+            break;
+        }
+        on_iteration(&Iteration {
+            depth,
+            lines: &lines,
+            nodes: search.nodes.load(Ordering::Relaxed),
+            time: start.elapsed(),
+        });
+        if lines.iter().all(|line| line.score.abs() >= MATE_BOUND) {
+            break;
+        }
+
+        // Search the best lines first in the next iteration
+        let firsts: Vec<Move> = lines.iter().map(|line| line.moves[0]).collect();
+        root_moves.sort_by_key(|mv| firsts.iter().position(|first| first == mv).unwrap_or(count));
+    }
+
+    (lines_depth, lines)
+}
+
+// Returns the score of game from the point of view of the player to move
+// pv is set to the moves that lead to the score, if it is above alpha
+fn negamax(
+    game: &Game,
+    depth: u32,
+    ply: i32,
+    mut alpha: i32,
+    beta: i32,
+    pv: &mut Vec<Move>,
+    search: &mut Search,
+) -> i32 {
+    search.nodes.fetch_add(1, Ordering::Relaxed);
+    if search.should_stop() || game.stalemate {
+        return 0;
+    }
+    if depth == 0 {
+        return quiescence(game, QUIESCENCE_DEPTH, ply, alpha, beta, search);
+    }
+
+    let hash = search.table.map(|_| game.position_hash());
+    let entry = search
+        .table
+        .zip(hash)
+        .and_then(|(table, hash)| table.probe(hash));
+    if let Some(entry) = entry.filter(|entry| entry.depth >= depth) {
+        let score = from_table(entry.score, ply);
+        match entry.bound {
+            Bound::Exact => return score.clamp(alpha, beta),
+            Bound::Lower if score >= beta => return beta,
+            Bound::Upper if score <= alpha => return alpha,
+            _ => (),
+        }
+    }
+
+    let mut moves = ordered_moves(game);
+    // The best move found before goes first
+    if let Some(key) = entry.and_then(|entry| entry.mv) {
+        if let Some(i) = moves.iter().position(|mv| move_key(mv) == key) {
+            let mv = moves.remove(i);
+            moves.insert(0, mv);
+        }
+    }
+    let table = search.table;
+    let store = |bound: Bound, score: i32, mv: Option<&Move>| {
+        if let (Some(table), Some(hash)) = (table, hash) {
+            let entry = Entry {
+                depth,
+                score: to_table(score, ply),
+                bound,
+                mv: mv.map(move_key),
+            };
+            table.store(hash, entry);
+        }
+    };
+
+    let original_alpha = alpha;
+    let mut best = None;
+    let mut child_pv: Vec<Move> = Vec::new();
+    for mv in &moves {
+        child_pv.clear();
+        let score = match play(game, mv) {
+            Some(child) => -negamax(
+                &child,
+                depth - 1,
+                ply + 1,
+                -beta,
+                -alpha,
+                &mut child_pv,
+                search,
+            ),
+            None => MATE - ply - 1,
+        };
+        if search.stopped {
+            return 0;
+        }
+        if score >= beta {
+            store(Bound::Lower, beta, Some(mv));
+            return beta;
+        }
+        if score > alpha {
+            alpha = score;
+            best = Some(mv);
+            pv.clear();
+            pv.push(*mv);
+            pv.extend_from_slice(&child_pv);
+        }
+    }
+    match alpha > original_alpha {
+        true => store(Bound::Exact, alpha, best),
+        false => store(Bound::Upper, alpha, None),
+    }
+    alpha
+}
+
+// Mate scores count the plies from the root, in the table they count from
+// the position they are stored for so that they hold wherever it comes up
+fn to_table(score: i32, ply: i32) -> i32 {
+    match score {
+        s if s >= MATE_BOUND => s + ply,
+        s if s <= -MATE_BOUND => s - ply,
+        s => s,
+    }
+}
+
+fn from_table(score: i32, ply: i32) -> i32 {
+    match score {
+        s if s >= MATE_BOUND => s - ply,
+        s if s <= -MATE_BOUND => s + ply,
+        s => s,
+    }
+}
+
+// Only looks at captures so that the evaluation is not taken in the middle of
+// an exchange
+fn quiescence(
+    game: &Game,
+    depth: u32,
+    ply: i32,
+    mut alpha: i32,
+    beta: i32,
+    search: &mut Search,
+) -> i32 {
+    search.nodes.fetch_add(1, Ordering::Relaxed);
+    if game.stalemate {
+        return 0;
+    }
+    let stand_pat = evaluate(game);
+    if stand_pat >= beta {
+        return beta;
+    }
+    if stand_pat > alpha {
+        alpha = stand_pat;
+    }
+    if depth == 0 {
+        return alpha;
+    }
+
+    for mv in &ordered_moves(game) {
+        if mv.captured.is_none() && mv.promotion.is_none() {
+            // Captures and promotions are ordered first, so the rest are quiet moves
+            break;
+        }
+        if search.should_stop() {
+            return 0;
+        }
+        let score = match play(game, mv) {
+            Some(child) => -quiescence(&child, depth - 1, ply + 1, -beta, -alpha, search),
+            None => MATE - ply - 1,
+        };
+        if search.stopped {
+            return 0;
+        }
+        if score >= beta {
+            return beta;
+        }
+        if score > alpha {
+            alpha = score;
+        }
+    }
+    alpha
+}
+
+// Plays mv on a copy of game
+// Returns None if the move mates, the resulting game otherwise
+fn play(game: &Game, mv: &Move) -> Option<Game> {
+    let child = game.clone().apply_move(*mv);
+    if child.mate {
+        return None;
+    }
+    Some(child)
+}
+
+// All moves of the player to move, promotions and captures first, captures
+// ordered by most valuable victim and least valuable attacker
+fn ordered_moves(game: &Game) -> Vec<Move> {
+    let mut moves: Vec<(i32, Move)> = game
+        .moves()
+        .into_iter()
+        .map(|mv| {
+            let promotion = mv
+                .promotion
+                .map_or(0, |piece_type| piece_value(&piece_type));
+            let order = match mv.captured {
+                Some(captured) => 10 * piece_value(&captured) - piece_value(&mv.piece) + promotion,
+                None if promotion > 0 => promotion,
+                None => -INFINITY,
+            };
+            (order, mv)
+        })
+        .collect();
+
+    moves.sort_by_key(|m| std::cmp::Reverse(m.0));
+    moves.into_iter().map(|(_, mv)| mv).collect()
+}
+
+pub fn piece_value(piece_type: &PieceType) -> i32 {
+    match piece_type {
+        PieceType::Pawn => 100,
+        PieceType::Knight => 320,
+        PieceType::Bishop => 330,
+        PieceType::Rook => 500,
+        PieceType::Queen => 900,
+        PieceType::King => 0,
+        PieceType::Unoccupied => 0,
+    }
+}
+
+// Static evaluation in centipawns from the point of view of the player to move
+// Material plus small bonuses for advanced pawns, central minor pieces and a
+// sheltered king
+pub fn evaluate(game: &Game) -> i32 {
+    let mut score = 0;
+
+    for pos in Pos::all() {
+        let square = game.square(pos);
+        if !square.occupied {
+            continue;
+        }
+        let piece = &square.piece;
+        let rank = pos.rank().index() as i32;
+        let file = pos.file().index() as i32;
+        // Distance travelled up the board from the piece's own side
+        let advance = if piece.white { rank } else { 7 - rank };
+        let centre = 14 - (2 * rank - 7).abs() - (2 * file - 7).abs();
+
+        let value = piece_value(&piece.piece_type)
+            + match piece.piece_type {
+                PieceType::Pawn => 4 * advance + if (2..=5).contains(&file) { advance } else { 0 },
+                PieceType::Knight | PieceType::Bishop => 3 * centre,
+                PieceType::Queen => centre,
+                PieceType::King => -2 * centre - 4 * advance,
+                _ => 0,
+            };
+
+        if piece.white == game.white_turn {
+            score += value;
+        } else {
+            score -= value;
+        }
+    }
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::san::parse_san;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    // Morphy's mate in 2, solved by 1. Ra6
+    const MATE_IN_TWO: &str = "kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1";
+
+    // The lines of a search with the depth, score, nodes and line of every
+    // Info sent, which leaves out the times
+    fn run(game: &Game, limit: Limit, options: &SearchOptions) -> (Vec<Line>, Vec<String>) {
+        let mut infos: Vec<String> = Vec::new();
+        let lines = search(game, limit, options, |info| {
+            infos.push(format!(
+                "{} {} {} {} {:?}",
+                info.depth, info.line, info.score, info.nodes, info.pv
+            ))
+        });
+        (lines, infos)
+    }
+
+    #[test]
+    fn single_thread_is_deterministic() {
+        let game = Game::from_fen(KIWIPETE).unwrap();
+        let options = SearchOptions::default();
+        let first = run(&game, Limit::Depth(2), &options);
+        let second = run(&game, Limit::Depth(2), &options);
+        assert!(!first.0.is_empty());
+        assert_eq!(first, second);
+        assert_eq!(best_move(&game, Limit::Depth(2)), Some(first.0[0].moves[0]));
+    }
+
+    #[test]
+    fn multi_pv_is_deterministic() {
+        let game = Game::new();
+        let first = multi_pv(&game, 3, Limit::Depth(3), |_| ());
+        let second = multi_pv(&game, 3, Limit::Depth(3), |_| ());
+        assert_eq!(first.len(), 3);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn threads_find_the_mate() {
+        let game = Game::from_fen(MATE_IN_TWO).unwrap();
+        let key = parse_san(&game, "Ra6").unwrap();
+        for threads in [1, 4] {
+            let options = SearchOptions {
+                threads,
+                ..SearchOptions::default()
+            };
+            let (lines, _) = run(&game, Limit::Depth(4), &options);
+            assert_eq!(lines[0].moves[0], key, "with {} threads", threads);
+            assert_eq!(lines[0].score, Score::Mate(2), "with {} threads", threads);
+        }
+    }
+
+    #[test]
+    fn threads_find_the_mate_in_one() {
+        let game = Game::from_fen("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1").unwrap();
+        let options = SearchOptions {
+            threads: 3,
+            ..SearchOptions::default()
+        };
+        let (lines, _) = run(&game, Limit::Depth(2), &options);
+        assert_eq!(lines[0].moves[0], parse_san(&game, "Rd8").unwrap());
+        assert_eq!(lines[0].score, Score::Mate(1));
+    }
+
+    #[test]
+    fn stopped_search_still_has_a_move() {
+        let game = Game::new();
+        let options = SearchOptions {
+            stop: Some(Arc::new(AtomicBool::new(true))),
+            ..SearchOptions::default()
+        };
+        let (lines, _) = run(&game, Limit::Infinite, &options);
+        assert_eq!(lines.len(), 1);
+        assert!(game.moves().contains(&lines[0].moves[0]));
+    }
+}
@@ -0,0 +1,173 @@
+use crate::attacks::{attackers, is_attacked};
+use crate::search::piece_value;
+use crate::{square_to_square, square_to_unoccupied, Game, Move, PieceType, Pos, Rank, Square};
+
+// Static exchange evaluation: the material won or lost in centipawns by mv
+// and the captures on its target square that follow, each side taking with
+// its least valuable piece and stopping when taking further would lose
+// Pieces lined up behind each other (a rook behind a rook, a queen behind a
+// bishop) join in once the piece in front of them has taken
+
+impl Game {
+    // SEE of mv, pins are ignored: every attacker may take except a king
+    // taking onto a defended square
+    pub fn see(&self, mv: &Move) -> i32 {
+        exchange(&self.boards.board, mv, false)
+    }
+
+    // SEE of mv where pieces pinned to their king only take if that does not
+    // leave the king in check
+    pub fn see_with_pins(&self, mv: &Move) -> i32 {
+        exchange(&self.boards.board, mv, true)
+    }
+}
+
+// A king is worth more than anything it could win
+fn see_value(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::King => 20_000,
+        _ => piece_value(&piece_type),
+    }
+}
+
+fn exchange(board: &[Vec<Square>], mv: &Move, pins: bool) -> i32 {
+    let mut board = board.to_vec();
+    let to = mv.to;
+    let white = board[mv.from.rank().index()][mv.from.file().index()]
+        .piece
+        .white;
+
+    // gain[d] is what the side making capture d has won if the exchange stops there
+    let captured = mv.captured.or(match mv.en_passant {
+        true => Some(PieceType::Pawn),
+        false => None,
+    });
+    let mut gain = vec![captured.map_or(0, see_value)];
+    if mv.en_passant {
+        square_to_unoccupied(Pos::new(to.file(), mv.from.rank()), &mut board);
+    }
+    let mut on_square = make_capture(&mut board, mv.from, to, mv.promotion, &mut gain);
+    let mut side = !white;
+
+    while let Some(attacker) = least_valuable_attacker(&board, to, side, pins) {
+        let last = *gain.last().expect("gain starts with the first capture");
+        gain.push(see_value(on_square) - last);
+        on_square = make_capture(&mut board, attacker, to, None, &mut gain);
+        side = !side;
+    }
+
+    // Either side may stop taking when it is their turn
+    while gain.len() > 1 {
+        let last = gain.pop().expect("gain has more than one entry");
+        let before = gain.last_mut().expect("gain has more than one entry");
+        *before = -(-*before).max(last);
+    }
+    gain[0]
+}
+
+// Moves the piece on from to to, promoting a pawn reaching the last rank
+// (to a queen unless promotion says otherwise) and adding that to the latest gain
+// Returns the piece now standing on to
+fn make_capture(
+    board: &mut [Vec<Square>],
+    from: Pos,
+    to: Pos,
+    promotion: Option<PieceType>,
+    gain: &mut [i32],
+) -> PieceType {
+    let piece = board[from.rank().index()][from.file().index()]
+        .piece
+        .clone();
+    square_to_square(from, to, board);
+    square_to_unoccupied(from, board);
+
+    if piece.piece_type == PieceType::Pawn && to.rank() == Rank::back_rank(!piece.white) {
+        let promotion = promotion.unwrap_or(PieceType::Queen);
+        board[to.rank().index()][to.file().index()].piece.piece_type = promotion;
+        if let Some(last) = gain.last_mut() {
+            *last += see_value(promotion) - see_value(PieceType::Pawn);
+        }
+        return promotion;
+    }
+    piece.piece_type
+}
+
+// The cheapest piece of the given colour that can take on to
+fn least_valuable_attacker(board: &[Vec<Square>], to: Pos, white: bool, pins: bool) -> Option<Pos> {
+    let mut candidates = attackers(board, to, white);
+    candidates.sort_by_key(|pos| {
+        see_value(
+            board[pos.rank().index()][pos.file().index()]
+                .piece
+                .piece_type,
+        )
+    });
+
+    candidates.into_iter().find(|&from| {
+        let moves_king = board[from.rank().index()][from.file().index()]
+            .piece
+            .piece_type
+            == PieceType::King;
+        if !moves_king && !pins {
+            return true;
+        }
+        let mut after = board.to_vec();
+        square_to_square(from, to, &mut after);
+        square_to_unoccupied(from, &mut after);
+        let king = match moves_king {
+            true => Some(to),
+            false => find_king(&after, white),
+        };
+        king.is_none_or(|king| !is_attacked(&after, king, !white))
+    })
+}
+
+fn find_king(board: &[Vec<Square>], white: bool) -> Option<Pos> {
+    Pos::all().find(|pos| {
+        let square = &board[pos.rank().index()][pos.file().index()];
+        square.occupied && square.piece.piece_type == PieceType::King && square.piece.white == white
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::san::parse_san;
+    use crate::Game;
+
+    // see and see_with_pins of the move san in the position fen
+    fn see(fen: &str, san: &str) -> (i32, i32) {
+        let game = Game::from_fen(fen).unwrap();
+        let mv = parse_san(&game, san).expect("legal move");
+        (game.see(&mv), game.see_with_pins(&mv))
+    }
+
+    #[test]
+    fn undefended_pawn() {
+        let fen = "1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1";
+        assert_eq!(see(fen, "Rxe5"), (100, 100));
+    }
+
+    #[test]
+    fn knight_for_pawn() {
+        let fen = "1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1";
+        assert_eq!(see(fen, "Nxe5"), (-220, -220));
+    }
+
+    #[test]
+    fn pinned_defender() {
+        let fen = "4k3/3n4/8/1B2p3/8/5N2/8/4K3 w - - 0 1";
+        assert_eq!(see(fen, "Nxe5"), (-220, 100));
+    }
+
+    #[test]
+    fn rooks_lined_up() {
+        let fen = "3rk3/8/8/3p4/8/8/3R4/3RK3 w - - 0 1";
+        assert_eq!(see(fen, "Rxd5"), (100, 100));
+    }
+
+    #[test]
+    fn quiet_move_to_attacked_square() {
+        let fen = "4k3/8/8/3p4/8/8/8/2Q1K3 w - - 0 1";
+        assert_eq!(see(fen, "Qc4"), (-900, -900));
+    }
+}
@@ -0,0 +1,361 @@
+// Serde support, enabled by the "serde" cargo feature
+//
+// The wire form is compact and built on FEN so that it stays stable:
+// - Game: {"fen": "<FEN>", "history": ["e4", "e5", ...], "annotations": [...]},
+//   fen is the position the game started from and history its moves in SAN,
+//   annotations is left out when no move has one
+// - Annotation: {"comments": [...], "nags": [1], "highlights": ["Gd4"], "arrows": ["Re2e4"]}
+// - Boards: the FEN piece placement, e.g. "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR"
+// - Square: {"square": "e4", "piece": "P"}
+// - Piece: its letter, uppercase for white, lowercase for black, "." for none
+// - PieceType: its uppercase letter, "." for Unoccupied
+// - Pos: the square name, e.g. "e4"
+// - Move: {"from": "e2", "to": "e4", "piece": "P", "captured": null, "promotion": null, ...}
+//
+// Generated moves and check boards are never sent, they are recomputed on
+// deserialize. A game is rebuilt by playing its history from the starting
+// position, so move_history and history always agree and a game with a move
+// that is not legal is rejected, as are positions that could not occur in a
+// game.
+
+use crate::annotation::{arrow, highlight, Annotation, CommentError};
+use crate::fen::{find_kings, placement_from_fen, placement_to_fen};
+use crate::san::parse_san;
+use crate::{
+    build_check_board, find_all_moves, Boards, Game, Move, Piece, PieceType, Pos, Rank, Square,
+    PROMOTION_PIECES,
+};
+use serde::de::{self, Deserializer};
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GameWire {
+    fen: String,
+    history: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    annotations: Vec<Annotation>,
+}
+
+impl Serialize for Game {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        GameWire {
+            fen: self.start_fen().to_string(),
+            history: self.move_history.clone(),
+            annotations: match self.annotations.iter().all(Annotation::is_empty) {
+                true => Vec::new(),
+                false => self.annotations.clone(),
+            },
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Game {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Game, D::Error> {
+        let wire = GameWire::deserialize(deserializer)?;
+        let mut game = Game::from_fen(&wire.fen).map_err(de::Error::custom)?;
+        for san in &wire.history {
+            let mv = parse_san(&game, san)
+                .ok_or_else(|| de::Error::custom(format!("illegal move in history \"{}\"", san)))?;
+            game = game.apply_move(mv);
+        }
+        let mut annotations = wire.annotations;
+        if annotations.is_empty() {
+            annotations.resize(wire.history.len(), Annotation::default());
+        } else if annotations.len() != wire.history.len() {
+            return Err(de::Error::custom(format!(
+                "{} annotations for {} moves",
+                annotations.len(),
+                wire.history.len()
+            )));
+        }
+        game.annotations = annotations;
+        Ok(game)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AnnotationWire {
+    #[serde(default)]
+    comments: Vec<String>,
+    #[serde(default)]
+    nags: Vec<u8>,
+    #[serde(default)]
+    highlights: Vec<String>,
+    #[serde(default)]
+    arrows: Vec<String>,
+}
+
+impl Serialize for Annotation {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        AnnotationWire {
+            comments: self.comments.clone(),
+            nags: self.nags.clone(),
+            highlights: self
+                .highlights
+                .iter()
+                .map(|h| format!("{}{}", h.color.letter(), h.square))
+                .collect(),
+            arrows: self
+                .arrows
+                .iter()
+                .map(|a| format!("{}{}{}", a.color.letter(), a.from, a.to))
+                .collect(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Annotation {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Annotation, D::Error> {
+        let wire = AnnotationWire::deserialize(deserializer)?;
+        let invalid = |s: &String| de::Error::custom(format!("invalid mark \"{}\"", s));
+        if let Some(comment) = wire.comments.iter().find(|c| c.contains('}')) {
+            return Err(de::Error::custom(CommentError(comment.clone())));
+        }
+        Ok(Annotation {
+            comments: wire.comments,
+            nags: wire.nags,
+            highlights: wire
+                .highlights
+                .iter()
+                .map(|s| highlight(s).ok_or_else(|| invalid(s)))
+                .collect::<Result<_, _>>()?,
+            arrows: wire
+                .arrows
+                .iter()
+                .map(|s| arrow(s).ok_or_else(|| invalid(s)))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl Serialize for Boards {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&placement_to_fen(&self.board))
+    }
+}
+
+impl<'de> Deserialize<'de> for Boards {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Boards, D::Error> {
+        let placement = String::deserialize(deserializer)?;
+        let board = placement_from_fen(&placement).map_err(de::Error::custom)?;
+        find_kings(&board).map_err(de::Error::custom)?;
+        Ok(find_all_moves(
+            board,
+            build_check_board(),
+            build_check_board(),
+        ))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SquareWire {
+    square: Pos,
+    piece: Piece,
+}
+
+impl Serialize for Square {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SquareWire {
+            square: self.pos,
+            piece: self.piece.clone(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Square {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Square, D::Error> {
+        let wire = SquareWire::deserialize(deserializer)?;
+        let occupied = wire.piece.piece_type != PieceType::Unoccupied;
+        let pawn_on_back_rank = wire.piece.piece_type == PieceType::Pawn
+            && (wire.square.rank() == Rank::R1 || wire.square.rank() == Rank::R8);
+        if pawn_on_back_rank {
+            return Err(de::Error::custom(format!(
+                "pawn on back rank at {}",
+                wire.square
+            )));
+        }
+        Ok(Square {
+            pos: wire.square,
+            piece: wire.piece,
+            occupied,
+        })
+    }
+}
+
+impl Serialize for Piece {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Piece {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Piece, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        let piece_type = piece_type_from_str(&s.to_ascii_uppercase())
+            .ok_or_else(|| de::Error::custom(format!("invalid piece \"{}\"", s)))?;
+        Ok(Piece {
+            // Empty squares are white by convention, see square_to_unoccupied
+            white: piece_type == PieceType::Unoccupied || s.chars().all(|c| c.is_ascii_uppercase()),
+            piece_type,
+            moves: Vec::new(),
+        })
+    }
+}
+
+impl Serialize for PieceType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for PieceType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<PieceType, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        piece_type_from_str(&s)
+            .ok_or_else(|| de::Error::custom(format!("invalid piece type \"{}\"", s)))
+    }
+}
+
+fn piece_type_from_str(s: &str) -> Option<PieceType> {
+    match s {
+        "P" => Some(PieceType::Pawn),
+        "N" => Some(PieceType::Knight),
+        "B" => Some(PieceType::Bishop),
+        "R" => Some(PieceType::Rook),
+        "Q" => Some(PieceType::Queen),
+        "K" => Some(PieceType::King),
+        "." => Some(PieceType::Unoccupied),
+        _ => None,
+    }
+}
+
+impl Serialize for Pos {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+// Only the lowercase form written by serialize is accepted
+impl<'de> Deserialize<'de> for Pos {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Pos, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        match s.parse::<Pos>() {
+            Ok(pos) if !s.as_bytes()[0].is_ascii_uppercase() => Ok(pos),
+            _ => Err(de::Error::custom(format!("invalid square \"{}\"", s))),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MoveWire {
+    from: Pos,
+    to: Pos,
+    piece: PieceType,
+    captured: Option<PieceType>,
+    promotion: Option<PieceType>,
+    castle: bool,
+    en_passant: bool,
+    double_push: bool,
+}
+
+impl Serialize for Move {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        MoveWire {
+            from: self.from,
+            to: self.to,
+            piece: self.piece,
+            captured: self.captured,
+            promotion: self.promotion,
+            castle: self.castle,
+            en_passant: self.en_passant,
+            double_push: self.double_push,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Move {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Move, D::Error> {
+        let wire = MoveWire::deserialize(deserializer)?;
+        let valid = wire.piece != PieceType::Unoccupied
+            && !matches!(wire.captured, Some(PieceType::King | PieceType::Unoccupied))
+            && match wire.promotion {
+                Some(piece_type) => {
+                    wire.piece == PieceType::Pawn && PROMOTION_PIECES.contains(&piece_type)
+                }
+                None => true,
+            };
+        if !valid {
+            return Err(de::Error::custom(format!(
+                "invalid move {}{}",
+                wire.from, wire.to
+            )));
+        }
+        Ok(Move {
+            from: wire.from,
+            to: wire.to,
+            piece: wire.piece,
+            captured: wire.captured,
+            promotion: wire.promotion,
+            castle: wire.castle,
+            en_passant: wire.en_passant,
+            double_push: wire.double_push,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fen::START_FEN;
+    use crate::san::parse_san;
+    use crate::Game;
+
+    fn play(mut game: Game, moves: &[&str]) -> Game {
+        for san in moves {
+            let mv = parse_san(&game, san).expect("legal move");
+            game = game.play_move(mv);
+        }
+        game
+    }
+
+    #[test]
+    fn round_trip_keeps_history() {
+        let game = play(Game::new(), &["e4", "e5", "Nf3"]);
+        let json = serde_json::to_string(&game).unwrap();
+        let back: Game = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.move_history, game.move_history);
+        assert_eq!(back.history, game.history);
+        assert_eq!(back.last_move(), game.last_move());
+        assert_eq!(back.to_fen(), game.to_fen());
+        assert_eq!(back.start_fen(), START_FEN);
+
+        let next = play(back, &["Nc6"]);
+        assert_eq!(next.history.len(), next.move_history.len());
+    }
+
+    #[test]
+    fn round_trip_from_fen() {
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1";
+        let game = play(Game::from_fen(fen).unwrap(), &["c5", "Nf3"]);
+        let back: Game = serde_json::from_str(&serde_json::to_string(&game).unwrap()).unwrap();
+        assert_eq!(back.start_fen(), fen);
+        assert_eq!(back.to_fen(), game.to_fen());
+    }
+
+    #[test]
+    fn illegal_history_is_rejected() {
+        let json = format!(
+            "{{\"fen\": \"{}\", \"history\": [\"Ke8\", \"Qh7#\"]}}",
+            START_FEN
+        );
+        assert!(serde_json::from_str::<Game>(&json).is_err());
+    }
+}
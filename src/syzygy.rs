@@ -0,0 +1,1351 @@
+// Syzygy endgame tablebases (.rtbw and .rtbz files)
+//
+// A table holds one material balance, e.g. KQvKR.rtbw, named with the pieces
+// of the stronger side first. The WDL table tells whether the side to move
+// wins, draws or loses, the DTZ table how many plies it takes until the next
+// capture or pawn move on the best path (distance to zero), which is what is
+// needed to win within the 50 move rule.
+//
+// Positions are turned into an index the way the tables were generated, and
+// the value at that index is read from the table's Huffman coded blocks. The
+// tables leave out positions where a capture decides the result, so those
+// captures are searched before the table is read.
+//
+// Tables are read into memory the first time they are needed. Positions with
+// castling rights are not in any table and are refused.
+
+use crate::{Game, Move, PieceType, Pos};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::Neg;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+
+// Bits of the first byte of a table
+const SPLIT: u8 = 1;
+const HAS_PAWNS: u8 = 2;
+
+// Bits of the flags of each compressed table
+const FLAG_STM: u8 = 1;
+const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+// The order of the pieces in table names
+const NAME_ORDER: [PieceType; 6] = [
+    PieceType::King,
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+    PieceType::Pawn,
+];
+
+#[derive(Debug)]
+pub enum SyzygyError {
+    Io(io::Error),
+    // The directory has no table for this material, e.g. "KQvKR"
+    MissingTable(String),
+    // A table file that could not be read as a Syzygy table
+    Corrupt(String),
+    // The position has more pieces than any table in the directory
+    TooManyPieces(usize),
+    // A player may still castle
+    CastlingRights,
+}
+
+impl fmt::Display for SyzygyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SyzygyError::Io(e) => write!(f, "{}", e),
+            SyzygyError::MissingTable(name) => write!(f, "no tablebase for {}", name),
+            SyzygyError::Corrupt(name) => write!(f, "corrupt tablebase file {}", name),
+            SyzygyError::TooManyPieces(n) => write!(f, "no tablebases for {} pieces", n),
+            SyzygyError::CastlingRights => write!(f, "no tablebases with castling rights"),
+        }
+    }
+}
+
+impl std::error::Error for SyzygyError {}
+
+impl From<io::Error> for SyzygyError {
+    fn from(e: io::Error) -> SyzygyError {
+        SyzygyError::Io(e)
+    }
+}
+
+// The result for the side to move with perfect play
+// A cursed win is a win that takes too long for the 50 move rule and a blessed
+// loss a loss the 50 move rule saves
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Wdl {
+    Loss,
+    BlessedLoss,
+    Draw,
+    CursedWin,
+    Win,
+}
+
+impl Wdl {
+    fn from_value(value: i32) -> Wdl {
+        match value {
+            i32::MIN..=-2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            0 => Wdl::Draw,
+            1 => Wdl::CursedWin,
+            _ => Wdl::Win,
+        }
+    }
+
+    // -2 for a loss up to 2 for a win
+    pub fn value(self) -> i32 {
+        self as i32 - 2
+    }
+}
+
+// The result for the other player
+impl Neg for Wdl {
+    type Output = Wdl;
+
+    fn neg(self) -> Wdl {
+        Wdl::from_value(-self.value())
+    }
+}
+
+// Tables read so far by name and kind (true for DTZ), None if there is no file
+type Tables = HashMap<(String, bool), Option<Arc<Table>>>;
+
+pub struct Tablebase {
+    dir: PathBuf,
+    max_pieces: usize,
+    tables: Mutex<Tables>,
+}
+
+impl Tablebase {
+    // Uses the tables in dir, which are read when first needed
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Tablebase, SyzygyError> {
+        let mut max_pieces = 0;
+        for entry in fs::read_dir(dir.as_ref())? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "rtbw") {
+                if let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    let pieces = stem.chars().filter(|c| c.is_ascii_uppercase()).count();
+                    max_pieces = max_pieces.max(pieces);
+                }
+            }
+        }
+        Ok(Tablebase {
+            dir: dir.as_ref().to_path_buf(),
+            max_pieces,
+            tables: Mutex::new(HashMap::new()),
+        })
+    }
+
+    // The most pieces, kings included, of any table in the directory
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    // Whether the position has few enough pieces to be probed and no
+    // castling rights
+    pub fn covers(&self, game: &Game) -> bool {
+        board_pieces(game).len() <= self.max_pieces && game.castling_rights().is_empty()
+    }
+
+    // Win, draw or loss for the player to move
+    pub fn probe_wdl(&self, game: &Game) -> Result<Wdl, SyzygyError> {
+        self.check_pieces(game)?;
+        Ok(self.search(game, false)?.0)
+    }
+
+    // Distance to zero in plies for the player to move: positive when winning,
+    // negative when losing and 0 for a draw
+    // Cursed wins and blessed losses are 100 plies further from zero than the
+    // 50 move rule allows
+    pub fn probe_dtz(&self, game: &Game) -> Result<i32, SyzygyError> {
+        self.check_pieces(game)?;
+        self.dtz(game)
+    }
+
+    // The legal moves that keep the best result within the 50 move rule, for
+    // the search to choose from
+    // Winning, all wins that can still be converted in time, or the fastest
+    // if none can; drawing, the drawing moves; losing, every move until the
+    // 50 move rule is near and then only the moves that hold out longest
+    pub fn root_moves(&self, game: &Game) -> Result<Vec<Move>, SyzygyError> {
+        self.check_pieces(game)?;
+        let halfmoves = game.halfmove_clock as i32;
+
+        let mut scored: Vec<(Move, i32)> = Vec::new();
+        for mv in game.moves() {
+            let child = game.clone().apply_move(mv);
+            let mut dtz = match child.halfmove_clock == 0 {
+                true => dtz_before_zeroing(-self.search(&child, false)?.0),
+                false => {
+                    let dtz = -self.dtz(&child)?;
+                    dtz + dtz.signum()
+                }
+            };
+            if child.mate && dtz == 2 {
+                dtz = 1;
+            }
+            scored.push((mv, dtz));
+        }
+
+        let keep: Box<dyn Fn(i32) -> bool> = match scored
+            .iter()
+            .map(|&(_, dtz)| dtz)
+            .filter(|&dtz| dtz > 0)
+            .min()
+        {
+            Some(fastest) => {
+                let limit = match fastest + halfmoves <= 99 {
+                    true => 99 - halfmoves,
+                    false => fastest,
+                };
+                Box::new(move |dtz| dtz > 0 && dtz <= limit)
+            }
+            None if scored.iter().any(|&(_, dtz)| dtz == 0) => Box::new(|dtz| dtz == 0),
+            None => {
+                let slowest = scored.iter().map(|&(_, dtz)| dtz).min().unwrap_or(0);
+                match -slowest + halfmoves <= 99 {
+                    true => Box::new(|_| true),
+                    false => Box::new(move |dtz| dtz == slowest),
+                }
+            }
+        };
+        Ok(scored
+            .into_iter()
+            .filter(|&(_, dtz)| keep(dtz))
+            .map(|(mv, _)| mv)
+            .collect())
+    }
+
+    fn check_pieces(&self, game: &Game) -> Result<(), SyzygyError> {
+        let pieces = board_pieces(game).len();
+        if pieces > self.max_pieces {
+            return Err(SyzygyError::TooManyPieces(pieces));
+        }
+        match game.castling_rights().is_empty() {
+            true => Ok(()),
+            false => Err(SyzygyError::CastlingRights),
+        }
+    }
+
+    // The WDL of game, and whether the best move is a capture or pawn move
+    // that the DTZ table cannot be trusted for
+    // With zeroing, winning pawn moves are tried as well as captures
+    fn search(&self, game: &Game, zeroing: bool) -> Result<(Wdl, bool), SyzygyError> {
+        if game.mate {
+            return Ok((Wdl::Loss, false));
+        }
+        if game.stalemate {
+            return Ok((Wdl::Draw, false));
+        }
+
+        let moves = game.moves();
+        let mut best = Wdl::Loss;
+        let mut searched = 0;
+        for mv in &moves {
+            let capture = mv.captured.is_some() || mv.en_passant;
+            if !(capture || zeroing && mv.piece == PieceType::Pawn) {
+                continue;
+            }
+            searched += 1;
+            let value = -self.search(&game.clone().apply_move(*mv), false)?.0;
+            if value > best {
+                best = value;
+                if value == Wdl::Win {
+                    return Ok((value, true));
+                }
+            }
+        }
+
+        // The table may be wrong when every move was searched, e.g. if they
+        // are all captures
+        let all_searched = searched > 0 && searched == moves.len();
+        let value = match all_searched {
+            true => best,
+            false => self
+                .probe_table(game, None)?
+                .map_or(Wdl::Draw, Wdl::from_value),
+        };
+        match best >= value {
+            true => Ok((best, best > Wdl::Draw || all_searched)),
+            false => Ok((value, false)),
+        }
+    }
+
+    fn dtz(&self, game: &Game) -> Result<i32, SyzygyError> {
+        if game.mate {
+            return Ok(-1);
+        }
+        let (wdl, zeroing_best) = self.search(game, true)?;
+        if wdl == Wdl::Draw {
+            return Ok(0);
+        }
+        if zeroing_best {
+            return Ok(dtz_before_zeroing(wdl));
+        }
+
+        if let Some(dtz) = self.probe_table(game, Some(wdl))? {
+            let cursed = matches!(wdl, Wdl::CursedWin | Wdl::BlessedLoss);
+            return Ok((dtz + 100 * cursed as i32) * wdl.value().signum());
+        }
+
+        // The table only has the other side to move, so look one move ahead
+        let mut best = i32::MAX;
+        for mv in game.moves() {
+            let zeroing = mv.captured.is_some() || mv.en_passant || mv.piece == PieceType::Pawn;
+            let child = game.clone().apply_move(mv);
+            let mut dtz = match zeroing {
+                true => -dtz_before_zeroing(self.search(&child, false)?.0),
+                false => -self.dtz(&child)?,
+            };
+            if dtz == 1 && child.mate {
+                best = 1;
+            }
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+            if dtz < best && dtz.signum() == wdl.value().signum() {
+                best = dtz;
+            }
+        }
+        Ok(if best == i32::MAX { -1 } else { best })
+    }
+
+    // Reads the table value for game: the WDL value when wdl is None, else the
+    // DTZ for a position known to have that result
+    // Returns None for a DTZ table that only holds the other side to move
+    fn probe_table(&self, game: &Game, wdl: Option<Wdl>) -> Result<Option<i32>, SyzygyError> {
+        let pieces = board_pieces(game);
+        // Two bare kings
+        if pieces.len() == 2 {
+            return Ok(Some(0));
+        }
+
+        let dtz = wdl.is_some();
+        let white_name = material_name(&pieces, true);
+        let black_name = material_name(&pieces, false);
+        let (table, flip) = match self.table(&white_name, dtz)? {
+            Some(table) => (table, false),
+            None => match self.table(&black_name, dtz)? {
+                Some(table) => (table, true),
+                None => return Err(SyzygyError::MissingTable(white_name)),
+            },
+        };
+        table.probe(&pieces, game.side_to_move(), flip, wdl)
+    }
+
+    fn table(&self, name: &str, dtz: bool) -> Result<Option<Arc<Table>>, SyzygyError> {
+        let mut tables = self.tables.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(table) = tables.get(&(name.to_string(), dtz)) {
+            return Ok(table.clone());
+        }
+        let extension = if dtz { "rtbz" } else { "rtbw" };
+        let path = self.dir.join(format!("{}.{}", name, extension));
+        let table = match fs::read(&path) {
+            Ok(bytes) => Some(Arc::new(
+                Table::parse(bytes, name, dtz)
+                    .ok_or_else(|| SyzygyError::Corrupt(path.display().to_string()))?,
+            )),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+        tables.insert((name.to_string(), dtz), table.clone());
+        Ok(table)
+    }
+}
+
+// The DTZ of a position whose best move is a capture or pawn move with result wdl
+fn dtz_before_zeroing(wdl: Wdl) -> i32 {
+    match wdl {
+        Wdl::Win => 1,
+        Wdl::CursedWin => 101,
+        Wdl::Draw => 0,
+        Wdl::BlessedLoss => -101,
+        Wdl::Loss => -1,
+    }
+}
+
+// The pieces on the board as (square, piece code) from a1 to h8
+// Codes are 1 to 6 for a white pawn, knight, bishop, rook, queen and king,
+// and 9 to 14 for the black ones
+fn board_pieces(game: &Game) -> Vec<(usize, u8)> {
+    Pos::all()
+        .filter_map(|pos| {
+            let square = game.square(pos);
+            let kind = match square.piece.piece_type {
+                PieceType::Pawn => 1,
+                PieceType::Knight => 2,
+                PieceType::Bishop => 3,
+                PieceType::Rook => 4,
+                PieceType::Queen => 5,
+                PieceType::King => 6,
+                PieceType::Unoccupied => return None,
+            };
+            match square.occupied {
+                true => Some((pos.index(), kind + if square.piece.white { 0 } else { 8 })),
+                false => None,
+            }
+        })
+        .collect()
+}
+
+fn piece_code(piece_type: PieceType) -> u8 {
+    match piece_type {
+        PieceType::Pawn => 1,
+        PieceType::Knight => 2,
+        PieceType::Bishop => 3,
+        PieceType::Rook => 4,
+        PieceType::Queen => 5,
+        _ => 6,
+    }
+}
+
+// The table name with the pieces of white (or black if white_first is false) first
+fn material_name(pieces: &[(usize, u8)], white_first: bool) -> String {
+    let side = |white: bool| {
+        let colour = if white { 0 } else { 8 };
+        NAME_ORDER
+            .iter()
+            .flat_map(|&piece_type| {
+                let count = pieces
+                    .iter()
+                    .filter(|&&(_, code)| code == piece_code(piece_type) + colour)
+                    .count();
+                std::iter::repeat_n(piece_type.letter(), count)
+            })
+            .collect::<String>()
+    };
+    format!("{}v{}", side(white_first), side(!white_first))
+}
+
+// Squares are numbered from a1 = 0 to h8 = 63
+fn file_of(sq: usize) -> usize {
+    sq & 7
+}
+
+fn rank_of(sq: usize) -> usize {
+    sq >> 3
+}
+
+// Negative below the a1-h8 diagonal, 0 on it and positive above it
+fn off_diagonal(sq: usize) -> i64 {
+    rank_of(sq) as i64 - file_of(sq) as i64
+}
+
+// n choose k
+fn binomial(k: usize, n: usize) -> u64 {
+    if k > n {
+        return 0;
+    }
+    (0..k as u64).fold(1, |acc, i| acc * (n as u64 - i) / (i + 1))
+}
+
+// Lookup tables for turning piece squares into a table index
+struct Indices {
+    // a2-h7 to 0..47, highest for the pawn nearest the edge and lowest rank
+    map_pawns: [usize; 64],
+    // [lead pawns][square], index of the lead pawn group by its first pawn
+    lead_pawn_idx: [[u64; 64]; 6],
+    // [lead pawns][file], number of lead pawn placements for a file
+    lead_pawns_size: [[u64; 4]; 6],
+    // Squares below the a1-h8 diagonal to 0..27
+    map_b1h1h7: [u64; 64],
+    // The a1-d1-d4 triangle to 0..9, the diagonal last
+    map_a1d1d4: [usize; 64],
+    // [a1-d1-d4 square][other king], the 462 placements of two kings
+    map_kk: [[u64; 64]; 10],
+}
+
+fn indices() -> &'static Indices {
+    static INDICES: OnceLock<Indices> = OnceLock::new();
+    INDICES.get_or_init(|| {
+        let mut indices = Indices {
+            map_pawns: [0; 64],
+            lead_pawn_idx: [[0; 64]; 6],
+            lead_pawns_size: [[0; 4]; 6],
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+        };
+
+        let mut code = 0;
+        for sq in 0..64 {
+            if off_diagonal(sq) < 0 {
+                indices.map_b1h1h7[sq] = code;
+                code += 1;
+            }
+        }
+
+        let mut code = 0;
+        for below in [true, false] {
+            for sq in 0..28 {
+                if file_of(sq) <= 3 && (off_diagonal(sq) < 0) == below && off_diagonal(sq) <= 0 {
+                    indices.map_a1d1d4[sq] = code;
+                    code += 1;
+                }
+            }
+        }
+
+        let mut code = 0;
+        let mut both_on_diagonal: Vec<(usize, usize)> = Vec::new();
+        for idx in 0..10 {
+            // b1 is the only square mapped to 0
+            for king in (0..28).filter(|&sq| indices.map_a1d1d4[sq] == idx && (idx > 0 || sq == 1))
+            {
+                for other in 0..64 {
+                    let touching = file_of(king).abs_diff(file_of(other)) <= 1
+                        && rank_of(king).abs_diff(rank_of(other)) <= 1;
+                    if touching || (off_diagonal(king) == 0 && off_diagonal(other) > 0) {
+                        continue;
+                    }
+                    if off_diagonal(king) == 0 && off_diagonal(other) == 0 {
+                        both_on_diagonal.push((idx, other));
+                    } else {
+                        indices.map_kk[idx][other] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (idx, other) in both_on_diagonal {
+            indices.map_kk[idx][other] = code;
+            code += 1;
+        }
+
+        let mut available = 47;
+        for lead in 1..=5 {
+            for file in 0..4 {
+                let mut idx = 0;
+                for rank in 1..=6 {
+                    let sq = rank * 8 + file;
+                    if lead == 1 {
+                        indices.map_pawns[sq] = available;
+                        indices.map_pawns[sq ^ 7] = available - 1;
+                        available = available.saturating_sub(2);
+                    }
+                    indices.lead_pawn_idx[lead][sq] = idx;
+                    idx += binomial(lead - 1, indices.map_pawns[sq]);
+                }
+                indices.lead_pawns_size[lead][file] = idx;
+            }
+        }
+        indices
+    })
+}
+
+// One compressed table: a side to move, and with pawns a file of the lead pawn
+struct PairsData {
+    flags: u8,
+    // The piece codes in the order they are encoded
+    pieces: Vec<u8>,
+    // Sizes of the groups of pieces encoded together
+    group_len: Vec<usize>,
+    // Multiplier of each group, the last one is the number of positions
+    group_idx: Vec<u64>,
+    block_size: usize,
+    span: u64,
+    sparse_index: usize,
+    sparse_index_size: usize,
+    block_lengths: usize,
+    block_lengths_size: usize,
+    blocks: usize,
+    data: usize,
+    // The stored value for FLAG_SINGLE_VALUE
+    min_sym_len: u8,
+    lowest_sym: usize,
+    base64: Vec<u64>,
+    // How many values each symbol stands for, less one
+    symlen: Vec<u8>,
+    btree: usize,
+    // Where the DTZ value maps for each result start
+    map_idx: [usize; 4],
+}
+
+struct Table {
+    // The file name, for errors
+    name: String,
+    bytes: Vec<u8>,
+    has_pawns: bool,
+    has_unique_pieces: bool,
+    // Pawns of the lead pawn colour and of the other one
+    pawn_count: [usize; 2],
+    symmetric: bool,
+    // [file][side to move]
+    pairs: Vec<Vec<PairsData>>,
+}
+
+impl Table {
+    // Parses a table file, None if it is not a valid table for name
+    fn parse(bytes: Vec<u8>, name: &str, dtz: bool) -> Option<Table> {
+        let magic = if dtz { DTZ_MAGIC } else { WDL_MAGIC };
+        if bytes.get(0..4)? != magic {
+            return None;
+        }
+
+        let (white, black) = name.split_once('v')?;
+        let count = |side: &str, letter: char| side.chars().filter(|&c| c == letter).count();
+        let piece_count = white.len() + black.len();
+        let (white_pawns, black_pawns) = (count(white, 'P'), count(black, 'P'));
+        let has_pawns = white_pawns + black_pawns > 0;
+        let has_unique_pieces = "QRBNP"
+            .chars()
+            .any(|letter| count(white, letter) == 1 || count(black, letter) == 1);
+        let lead_white = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+        let pawn_count = match lead_white {
+            true => [white_pawns, black_pawns],
+            false => [black_pawns, white_pawns],
+        };
+        let symmetric = white == black;
+
+        let mut table = Table {
+            name: format!("{}.{}", name, if dtz { "rtbz" } else { "rtbw" }),
+            bytes,
+            has_pawns,
+            has_unique_pieces,
+            pawn_count,
+            symmetric,
+            pairs: Vec::new(),
+        };
+        let layout = *table.bytes.get(4)?;
+        // WDL tables are split by side to move unless the material is symmetric
+        if (layout & HAS_PAWNS != 0) != has_pawns || (!dtz && (layout & SPLIT != 0) == symmetric) {
+            return None;
+        }
+
+        let sides = if !dtz && !symmetric { 2 } else { 1 };
+        let files = if has_pawns { 4 } else { 1 };
+        let both_pawns = has_pawns && pawn_count[1] > 0;
+
+        let mut at = 5;
+        for file in 0..files {
+            let first = *table.bytes.get(at)?;
+            let second = match both_pawns {
+                true => *table.bytes.get(at + 1)?,
+                false => 0xff,
+            };
+            let order = [[first & 0xf, second & 0xf], [first >> 4, second >> 4]];
+            at += 1 + both_pawns as usize;
+
+            let codes = table.bytes.get(at..at + piece_count)?;
+            let mut file_pairs = Vec::new();
+            for (side, order) in order.iter().enumerate().take(sides) {
+                let pieces: Vec<u8> = codes
+                    .iter()
+                    .map(|&code| if side == 0 { code & 0xf } else { code >> 4 })
+                    .collect();
+                file_pairs.push(table.groups(pieces, *order, file)?);
+            }
+            at += piece_count;
+            table.pairs.push(file_pairs);
+        }
+        at += at & 1;
+
+        for file in 0..files {
+            for side in 0..sides {
+                at = table.set_sizes(file, side, at)?;
+            }
+        }
+
+        if dtz {
+            for file in 0..files {
+                let pairs = &mut table.pairs[file][0];
+                if pairs.flags & FLAG_MAPPED == 0 {
+                    continue;
+                }
+                for i in 0..4 {
+                    match pairs.flags & FLAG_WIDE != 0 {
+                        true => {
+                            at += at & 1;
+                            pairs.map_idx[i] = at + 2;
+                            at += 2 * read_u16(&table.bytes, at)? as usize + 2;
+                        }
+                        false => {
+                            pairs.map_idx[i] = at + 1;
+                            at += *table.bytes.get(at)? as usize + 1;
+                        }
+                    }
+                }
+            }
+            at += at & 1;
+        }
+
+        for file in 0..files {
+            for side in 0..sides {
+                let pairs = &mut table.pairs[file][side];
+                pairs.sparse_index = at;
+                at += pairs.sparse_index_size * 6;
+            }
+        }
+        for file in 0..files {
+            for side in 0..sides {
+                let pairs = &mut table.pairs[file][side];
+                pairs.block_lengths = at;
+                at += pairs.block_lengths_size * 2;
+            }
+        }
+        for file in 0..files {
+            for side in 0..sides {
+                let pairs = &mut table.pairs[file][side];
+                at = (at + 0x3f) & !0x3f;
+                pairs.data = at;
+                at += pairs.blocks * pairs.block_size;
+            }
+        }
+
+        match at <= table.bytes.len() {
+            true => Some(table),
+            false => None,
+        }
+    }
+
+    // Splits pieces into the groups encoded together and works out the
+    // multiplier of each group from the order they are encoded in
+    fn groups(&self, pieces: Vec<u8>, order: [u8; 2], file: usize) -> Option<PairsData> {
+        let mut first_len: i32 = match (self.has_pawns, self.has_unique_pieces) {
+            (true, _) => 0,
+            (false, true) => 3,
+            (false, false) => 2,
+        };
+        let mut group_len = vec![1];
+        for i in 1..pieces.len() {
+            first_len -= 1;
+            if first_len > 0 || pieces[i] == pieces[i - 1] {
+                *group_len.last_mut()? += 1;
+            } else {
+                group_len.push(1);
+            }
+        }
+
+        let groups = group_len.len();
+        let both_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = if both_pawns { 2 } else { 1 };
+        let mut free_squares =
+            64usize.checked_sub(group_len[0] + if both_pawns { *group_len.get(1)? } else { 0 })?;
+        let mut group_idx = vec![0u64; groups + 1];
+        let mut idx: u64 = 1;
+        let (order, order2) = (order[0] as usize, order[1] as usize);
+
+        let mut k = 0;
+        while next < groups || k == order || k == order2 {
+            if k == order {
+                group_idx[0] = idx;
+                idx = idx.checked_mul(match (self.has_pawns, self.has_unique_pieces) {
+                    (true, _) => *indices().lead_pawns_size.get(group_len[0])?.get(file)?,
+                    (false, true) => 31332,
+                    (false, false) => 462,
+                })?;
+            } else if k == order2 {
+                group_idx[1] = idx;
+                idx = idx.checked_mul(binomial(*group_len.get(1)?, 48 - group_len[0]))?;
+            } else {
+                group_idx[next] = idx;
+                idx = idx.checked_mul(binomial(group_len[next], free_squares))?;
+                free_squares = free_squares.checked_sub(group_len[next])?;
+                next += 1;
+            }
+            k += 1;
+        }
+        group_idx[groups] = idx;
+
+        Some(PairsData {
+            flags: 0,
+            pieces,
+            group_len,
+            group_idx,
+            block_size: 0,
+            span: 1,
+            sparse_index: 0,
+            sparse_index_size: 0,
+            block_lengths: 0,
+            block_lengths_size: 0,
+            blocks: 0,
+            data: 0,
+            min_sym_len: 0,
+            lowest_sym: 0,
+            base64: Vec::new(),
+            symlen: Vec::new(),
+            btree: 0,
+            map_idx: [0; 4],
+        })
+    }
+
+    // Reads the header of a compressed table at at, returns where it ends
+    fn set_sizes(&mut self, file: usize, side: usize, mut at: usize) -> Option<usize> {
+        let bytes = &self.bytes;
+        let pairs = &mut self.pairs[file][side];
+        pairs.flags = *bytes.get(at)?;
+        at += 1;
+        if pairs.flags & FLAG_SINGLE_VALUE != 0 {
+            pairs.min_sym_len = *bytes.get(at)?;
+            return Some(at + 1);
+        }
+
+        let size = *pairs.group_idx.last()?;
+        pairs.block_size = 1usize.checked_shl(*bytes.get(at)? as u32)?;
+        pairs.span = 1u64.checked_shl(*bytes.get(at + 1)? as u32)?;
+        pairs.sparse_index_size = size.div_ceil(pairs.span) as usize;
+        let padding = *bytes.get(at + 2)? as usize;
+        pairs.blocks = read_u32(bytes, at + 3)? as usize;
+        pairs.block_lengths_size = pairs.blocks + padding;
+        let max_sym_len = *bytes.get(at + 7)?;
+        pairs.min_sym_len = *bytes.get(at + 8)?;
+        at += 9;
+        pairs.lowest_sym = at;
+
+        // Canonical Huffman codes: the codes of each length, left aligned to
+        // 64 bits, start at base64[length - min_sym_len]
+        let lengths = (max_sym_len as usize + 1).checked_sub(pairs.min_sym_len as usize)?;
+        let mut base64 = vec![0u64; lengths];
+        for i in (0..lengths.saturating_sub(1)).rev() {
+            let lowest = read_u16(bytes, pairs.lowest_sym + 2 * i)? as u64;
+            let next = read_u16(bytes, pairs.lowest_sym + 2 * i + 2)? as u64;
+            base64[i] = base64[i + 1].wrapping_add(lowest).wrapping_sub(next) / 2;
+        }
+        for (i, base) in base64.iter_mut().enumerate() {
+            let shift = 64usize.checked_sub(i + pairs.min_sym_len as usize)?;
+            *base = base.checked_shl(shift as u32).unwrap_or(0);
+        }
+        pairs.base64 = base64;
+        at += lengths * 2;
+
+        let symbols = read_u16(bytes, at)? as usize;
+        at += 2;
+        pairs.btree = at;
+
+        // Each symbol is either a value or a pair of symbols
+        let mut symlen = vec![0u8; symbols];
+        let mut done = vec![false; symbols];
+        for symbol in 0..symbols {
+            let mut stack = vec![symbol];
+            while let Some(&top) = stack.last() {
+                if done[top] {
+                    stack.pop();
+                    continue;
+                }
+                let (left, right) = btree_pair(bytes, pairs.btree, top)?;
+                if right == 0xfff {
+                    done[top] = true;
+                    stack.pop();
+                    continue;
+                }
+                let (left, right) = (left as usize, right as usize);
+                if left >= symbols || right >= symbols || stack.len() > symbols {
+                    return None;
+                }
+                match (done[left], done[right]) {
+                    (false, _) => stack.push(left),
+                    (_, false) => stack.push(right),
+                    (true, true) => {
+                        symlen[top] = symlen[left].wrapping_add(symlen[right]).wrapping_add(1);
+                        done[top] = true;
+                        stack.pop();
+                    }
+                }
+            }
+        }
+        pairs.symlen = symlen;
+
+        Some(at + symbols * 3 + (symbols & 1))
+    }
+
+    // The stored value for the position, see Tablebase::probe_table
+    fn probe(
+        &self,
+        board: &[(usize, u8)],
+        white_to_move: bool,
+        black_stronger: bool,
+        wdl: Option<Wdl>,
+    ) -> Result<Option<i32>, SyzygyError> {
+        let corrupt = || SyzygyError::Corrupt(self.name.clone());
+        let indices = indices();
+
+        // Tables are stored with the stronger side as white, and symmetric
+        // ones only with white to move
+        let flip = black_stronger || (self.symmetric && !white_to_move);
+        let flip_colour = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let stm = (flip == white_to_move) as usize;
+
+        // With pawns the table is split by the file of the lead pawn, the one
+        // nearest the edge and then lowest
+        let mut squares: Vec<usize> = Vec::new();
+        let mut pieces: Vec<u8> = Vec::new();
+        let mut file = 0;
+        if self.has_pawns {
+            let lead = self.pairs[0][0].pieces[0];
+            for &(sq, code) in board
+                .iter()
+                .filter(|&&(_, code)| code ^ flip_colour == lead)
+            {
+                squares.push(sq ^ flip_squares);
+                pieces.push(code ^ flip_colour);
+            }
+            let first = (0..squares.len())
+                .rev()
+                .max_by_key(|&i| indices.map_pawns[squares[i]])
+                .unwrap_or(0);
+            squares.swap(0, first);
+            file = file_of(squares[0]).min(7 - file_of(squares[0]));
+        }
+        let lead_pawns = squares.len();
+
+        // DTZ tables only hold one side to move
+        if wdl.is_some()
+            && (self.pairs[file][0].flags & FLAG_STM) as usize != stm
+            && (self.has_pawns || !self.symmetric)
+        {
+            return Ok(None);
+        }
+
+        for &(sq, code) in board {
+            if !self.has_pawns || code ^ flip_colour != self.pairs[0][0].pieces[0] {
+                squares.push(sq ^ flip_squares);
+                pieces.push(code ^ flip_colour);
+            }
+        }
+        let size = squares.len();
+        let pairs = &self.pairs[file][stm % self.pairs[file].len()];
+        if pairs.pieces.len() != size {
+            return Err(corrupt());
+        }
+
+        // Put the pieces in the order of the table
+        for i in lead_pawns..size.saturating_sub(1) {
+            if let Some(j) = (i + 1..size).find(|&j| pieces[j] == pairs.pieces[i]) {
+                pieces.swap(i, j);
+                squares.swap(i, j);
+            }
+        }
+
+        // Mirror so the first piece is on files a-d
+        if file_of(squares[0]) > 3 {
+            squares.iter_mut().for_each(|sq| *sq ^= 7);
+        }
+
+        let mut idx: u64;
+        if self.has_pawns {
+            idx = indices.lead_pawn_idx[lead_pawns][squares[0]];
+            squares[1..lead_pawns].sort_by_key(|&sq| indices.map_pawns[sq]);
+            for (i, &sq) in squares.iter().enumerate().take(lead_pawns).skip(1) {
+                idx += binomial(i, indices.map_pawns[sq]);
+            }
+        } else {
+            // Without pawns also mirror to ranks 1-4 and below the diagonal
+            if rank_of(squares[0]) > 3 {
+                squares.iter_mut().for_each(|sq| *sq ^= 56);
+            }
+            for i in 0..pairs.group_len[0] {
+                match off_diagonal(squares[i]) {
+                    0 => continue,
+                    off if off > 0 => squares[i..]
+                        .iter_mut()
+                        .for_each(|sq| *sq = ((*sq >> 3) | (*sq << 3)) & 63),
+                    _ => (),
+                }
+                break;
+            }
+
+            idx = match self.has_unique_pieces {
+                true => {
+                    let s: Vec<i64> = squares.iter().take(3).map(|&sq| sq as i64).collect();
+                    let rank = |i: usize| rank_of(squares[i]) as i64;
+                    let adjust1 = (s[1] > s[0]) as i64;
+                    let adjust2 = (s[2] > s[0]) as i64 + (s[2] > s[1]) as i64;
+                    let below = |i: usize| indices.map_b1h1h7[squares[i]] as i64;
+                    let idx = if off_diagonal(squares[0]) != 0 {
+                        (indices.map_a1d1d4[squares[0]] as i64 * 63 + (s[1] - adjust1)) * 62 + s[2]
+                            - adjust2
+                    } else if off_diagonal(squares[1]) != 0 {
+                        (6 * 63 + rank(0) * 28 + below(1)) * 62 + s[2] - adjust2
+                    } else if off_diagonal(squares[2]) != 0 {
+                        6 * 63 * 62
+                            + 4 * 28 * 62
+                            + rank(0) * 7 * 28
+                            + (rank(1) - adjust1) * 28
+                            + below(2)
+                    } else {
+                        6 * 63 * 62
+                            + 4 * 28 * 62
+                            + 4 * 7 * 28
+                            + rank(0) * 7 * 6
+                            + (rank(1) - adjust1) * 6
+                            + (rank(2) - adjust2)
+                    };
+                    idx as u64
+                }
+                false => indices.map_kk[indices.map_a1d1d4[squares[0]]][squares[1]],
+            };
+        }
+
+        // The other groups, each as a combination of the squares left
+        idx *= pairs.group_idx[0];
+        let mut start = pairs.group_len[0];
+        let mut other_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        for next in 1..pairs.group_len.len() {
+            let len = pairs.group_len[next];
+            squares[start..start + len].sort_unstable();
+            let mut n = 0;
+            for i in 0..len {
+                let sq = squares[start + i];
+                let before = squares[..start].iter().filter(|&&other| sq > other).count();
+                let free = sq - before - if other_pawns { 8 } else { 0 };
+                n += binomial(i + 1, free);
+            }
+            other_pawns = false;
+            idx += n * pairs.group_idx[next];
+            start += len;
+        }
+
+        let value = self.decompress(pairs, idx).ok_or_else(corrupt)?;
+        match wdl {
+            Some(wdl) => self
+                .map_dtz(pairs, value, wdl)
+                .map(Some)
+                .ok_or_else(corrupt),
+            None => Ok(Some(value - 2)),
+        }
+    }
+
+    // The value at idx
+    fn decompress(&self, pairs: &PairsData, idx: u64) -> Option<i32> {
+        if pairs.flags & FLAG_SINGLE_VALUE != 0 {
+            return Some(pairs.min_sym_len as i32);
+        }
+        let bytes = &self.bytes;
+
+        // The sparse index gives the block and offset of every span-th value,
+        // from there step to the block holding idx
+        let k = (idx / pairs.span) as usize;
+        if k >= pairs.sparse_index_size {
+            return None;
+        }
+        let entry = pairs.sparse_index + 6 * k;
+        let mut block = read_u32(bytes, entry)? as usize;
+        let mut offset = read_u16(bytes, entry + 4)? as i64;
+        offset += (idx % pairs.span) as i64 - (pairs.span / 2) as i64;
+
+        let block_length = |block: usize| -> Option<i64> {
+            if block >= pairs.block_lengths_size {
+                return None;
+            }
+            Some(read_u16(bytes, pairs.block_lengths + 2 * block)? as i64)
+        };
+        while offset < 0 {
+            block = block.checked_sub(1)?;
+            offset += block_length(block)? + 1;
+        }
+        while offset > block_length(block)? {
+            offset -= block_length(block)? + 1;
+            block += 1;
+        }
+
+        // Read Huffman coded symbols until the one holding offset
+        let mut at = pairs.data + block * pairs.block_size;
+        let mut buffer = read_u64_be(bytes, at)?;
+        at += 8;
+        let mut buffered = 64;
+        let min_sym_len = pairs.min_sym_len as usize;
+        let mut symbol;
+        loop {
+            let mut len = 0;
+            while buffer < *pairs.base64.get(len)? {
+                len += 1;
+            }
+            let shift = 64usize.checked_sub(len + min_sym_len)?;
+            let code = (buffer - pairs.base64[len])
+                .checked_shr(shift as u32)
+                .unwrap_or(0);
+            let lowest = read_u16(bytes, pairs.lowest_sym + 2 * len)?;
+            symbol = (code as u16).wrapping_add(lowest) as usize;
+
+            let values = *pairs.symlen.get(symbol)? as i64 + 1;
+            if offset < values {
+                break;
+            }
+            offset -= values;
+            let len = (len + min_sym_len) as u32;
+            buffer = buffer.checked_shl(len).unwrap_or(0);
+            buffered -= len as i32;
+            if buffered <= 32 {
+                buffered += 32;
+                // Past the end of the file reads as zeros
+                let word = read_u32_be(bytes, at).unwrap_or(0) as u64;
+                buffer |= word << (64 - buffered);
+                at += 4;
+            }
+        }
+
+        // Expand the pairs the symbol stands for down to the value
+        while pairs.symlen[symbol] != 0 {
+            let (left, right) = btree_pair(bytes, pairs.btree, symbol)?;
+            let left_values = *pairs.symlen.get(left as usize)? as i64 + 1;
+            if offset < left_values {
+                symbol = left as usize;
+            } else {
+                offset -= left_values;
+                symbol = right as usize;
+            }
+            pairs.symlen.get(symbol)?;
+        }
+        Some(btree_pair(bytes, pairs.btree, symbol)?.0 as i32)
+    }
+
+    // Turns a stored DTZ value into plies
+    fn map_dtz(&self, pairs: &PairsData, value: i32, wdl: Wdl) -> Option<i32> {
+        let flags = pairs.flags;
+        let mut value = value;
+        if flags & FLAG_MAPPED != 0 {
+            let map = pairs.map_idx[match wdl {
+                Wdl::Loss => 1,
+                Wdl::BlessedLoss => 3,
+                Wdl::Draw | Wdl::Win => 0,
+                Wdl::CursedWin => 2,
+            }];
+            value = match flags & FLAG_WIDE != 0 {
+                true => read_u16(&self.bytes, map + 2 * value as usize)? as i32,
+                false => *self.bytes.get(map + value as usize)? as i32,
+            };
+        }
+
+        // Values are in moves unless the table says plies
+        let in_moves = match wdl {
+            Wdl::Win => flags & FLAG_WIN_PLIES == 0,
+            Wdl::Loss => flags & FLAG_LOSS_PLIES == 0,
+            _ => true,
+        };
+        if in_moves {
+            value *= 2;
+        }
+        Some(value + 1)
+    }
+}
+
+// The two 12 bit symbols of a node of the pair tree: a leaf has the value on
+// the left and 0xfff on the right
+fn btree_pair(bytes: &[u8], btree: usize, symbol: usize) -> Option<(u16, u16)> {
+    let node = bytes.get(btree + 3 * symbol..btree + 3 * symbol + 3)?;
+    let left = ((node[1] as u16 & 0xf) << 8) | node[0] as u16;
+    let right = ((node[2] as u16) << 4) | (node[1] as u16 >> 4);
+    Some((left, right))
+}
+
+fn read_u16(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn read_u32_be(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn read_u64_be(bytes: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(bytes.get(at..at + 8)?.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game(fen: &str) -> Game {
+        Game::from_fen(fen).unwrap()
+    }
+
+    fn san(game: &Game, moves: &[Move]) -> Vec<String> {
+        let mut names: Vec<String> = moves
+            .iter()
+            .map(|&mv| crate::san::move_to_san(game, &mv))
+            .collect();
+        names.sort();
+        names
+    }
+
+    // A KQvK table whose compressed tables each hold a single value, pieces
+    // encoded as white king, white queen, black king
+    fn single_value_table(magic: [u8; 4], layout: u8, values: &[(u8, u8)]) -> Vec<u8> {
+        let mut bytes = magic.to_vec();
+        bytes.extend_from_slice(&[layout, 0x00, 0x66, 0x55, 0xee, 0x00]);
+        for &(flags, value) in values {
+            bytes.extend_from_slice(&[flags | FLAG_SINGLE_VALUE, value]);
+        }
+        bytes.resize(64, 0);
+        bytes
+    }
+
+    // A directory with the fixture KQvK tables
+    // The WDL table has every position won with white to move and lost with
+    // black to move, which is right for KQvK once captures of the queen and
+    // stalemates are searched. The DTZ table only has black to move, always
+    // 2 plies from zero, so it is exact for black to move into a mate in one
+    fn kqvk(test: &str) -> Tablebase {
+        let dir =
+            std::env::temp_dir().join(format!("simonsev-chess-{}-{}", test, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let wdl = single_value_table(WDL_MAGIC, SPLIT, &[(0, 4), (0, 0)]);
+        fs::write(dir.join("KQvK.rtbw"), wdl).unwrap();
+        let dtz = single_value_table(DTZ_MAGIC, 0, &[(FLAG_STM | FLAG_LOSS_PLIES, 1)]);
+        fs::write(dir.join("KQvK.rtbz"), dtz).unwrap();
+        Tablebase::open(&dir).unwrap()
+    }
+
+    #[test]
+    fn material_names() {
+        let pieces = board_pieces(&game("4k3/8/8/8/8/8/8/R2QK3 w - - 0 1"));
+        assert_eq!(material_name(&pieces, true), "KQRvK");
+        assert_eq!(material_name(&pieces, false), "KvKQR");
+        let pieces = board_pieces(&game("4k3/2n1p3/8/3n4/8/8/5P2/1R2K3 b - - 0 1"));
+        assert_eq!(material_name(&pieces, true), "KRPvKNNP");
+        assert_eq!(material_name(&pieces, false), "KNNPvKRP");
+    }
+
+    #[test]
+    fn index_tables() {
+        assert_eq!(binomial(0, 5), 1);
+        assert_eq!(binomial(2, 5), 10);
+        assert_eq!(binomial(3, 48), 17296);
+        assert_eq!(binomial(6, 5), 0);
+
+        let indices = indices();
+        // The ten squares of the a1-d1-d4 triangle, the diagonal last
+        let triangle: Vec<usize> = (0..64)
+            .filter(|&sq| file_of(sq) <= 3 && off_diagonal(sq) <= 0)
+            .collect();
+        assert_eq!(triangle.len(), 10);
+        let mut codes: Vec<usize> = triangle.iter().map(|&sq| indices.map_a1d1d4[sq]).collect();
+        codes.sort();
+        assert_eq!(codes, (0..10).collect::<Vec<usize>>());
+        assert_eq!(indices.map_a1d1d4[0], 6);
+        assert_eq!(indices.map_a1d1d4[27], 9);
+
+        // Two kings that do not touch, the first in the triangle, take 462
+        // codes
+        let mut kk: Vec<u64> = Vec::new();
+        for &king in &triangle {
+            for other in 0..64 {
+                let touching = file_of(king).abs_diff(file_of(other)) <= 1
+                    && rank_of(king).abs_diff(rank_of(other)) <= 1;
+                let mirrored = off_diagonal(king) == 0 && off_diagonal(other) > 0;
+                if !touching && !mirrored {
+                    kk.push(indices.map_kk[indices.map_a1d1d4[king]][other]);
+                }
+            }
+        }
+        kk.sort();
+        assert_eq!(kk, (0..462).collect::<Vec<u64>>());
+
+        let below: Vec<u64> = (0..64)
+            .filter(|&sq| off_diagonal(sq) < 0)
+            .map(|sq| indices.map_b1h1h7[sq])
+            .collect();
+        assert_eq!(below, (0..28).collect::<Vec<u64>>());
+
+        // A single lead pawn has six ranks on each file
+        assert_eq!(indices.lead_pawns_size[1], [6; 4]);
+        let mut pawns: Vec<usize> = (8..56).map(|sq| indices.map_pawns[sq]).collect();
+        pawns.sort();
+        assert_eq!(pawns, (0..48).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn wdl_negation() {
+        assert_eq!(-Wdl::Win, Wdl::Loss);
+        assert_eq!(-Wdl::CursedWin, Wdl::BlessedLoss);
+        assert_eq!(-Wdl::Draw, Wdl::Draw);
+        assert_eq!(-Wdl::BlessedLoss, Wdl::CursedWin);
+        assert_eq!(-Wdl::Loss, Wdl::Win);
+        assert_eq!(Wdl::Win.value(), 2);
+        assert_eq!(Wdl::BlessedLoss.value(), -1);
+    }
+
+    #[test]
+    fn errors() {
+        let tablebase = kqvk("errors");
+        assert_eq!(tablebase.max_pieces(), 3);
+        assert!(matches!(
+            tablebase.probe_wdl(&Game::new()),
+            Err(SyzygyError::TooManyPieces(32))
+        ));
+        let castling = game("4k3/8/8/8/8/8/8/Q3K2R w K - 0 1");
+        assert!(matches!(
+            tablebase.probe_wdl(&castling),
+            Err(SyzygyError::TooManyPieces(4))
+        ));
+        let castling = game("4k3/8/8/8/8/8/8/4K2R w K - 0 1");
+        assert!(!tablebase.covers(&castling));
+        assert!(matches!(
+            tablebase.probe_wdl(&castling),
+            Err(SyzygyError::CastlingRights)
+        ));
+        let krvk = game("4k3/8/8/8/8/8/8/4K2R w - - 0 1");
+        assert!(tablebase.covers(&krvk));
+        match tablebase.probe_wdl(&krvk) {
+            Err(SyzygyError::MissingTable(name)) => assert_eq!(name, "KRvK"),
+            other => panic!("expected a missing table, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn probe_fixture_tables() {
+        let tablebase = kqvk("probe");
+        let wdl = |fen: &str| tablebase.probe_wdl(&game(fen)).unwrap();
+        let dtz = |fen: &str| tablebase.probe_dtz(&game(fen)).unwrap();
+
+        assert_eq!(wdl("4k3/8/8/8/8/8/8/3QK3 w - - 0 1"), Wdl::Win);
+        assert_eq!(wdl("4k3/8/8/8/8/8/8/3QK3 b - - 0 1"), Wdl::Loss);
+        // The table is stored with the queen's side as white
+        assert_eq!(wdl("3qk3/8/8/8/8/8/8/4K3 b - - 0 1"), Wdl::Win);
+        // The queen is taken
+        assert_eq!(wdl("4k3/3Q4/8/8/8/8/8/4K3 b - - 0 1"), Wdl::Draw);
+        assert_eq!(wdl("4k3/8/8/8/8/8/3q4/4K3 w - - 0 1"), Wdl::Draw);
+        assert_eq!(wdl("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1"), Wdl::Draw);
+        assert_eq!(wdl("4k3/8/8/8/8/8/8/4K3 w - - 0 1"), Wdl::Draw);
+
+        // Mated, and mate in one
+        assert_eq!(dtz("k7/1Q6/1K6/8/8/8/8/8 b - - 0 1"), -1);
+        assert_eq!(dtz("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1"), 1);
+        // Ka7 is forced and Qb7 mates
+        assert_eq!(dtz("k7/2K5/8/8/8/8/8/1Q6 b - - 0 1"), -2);
+        assert_eq!(dtz("4k3/3Q4/8/8/8/8/8/4K3 b - - 0 1"), 0);
+        assert_eq!(dtz("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1"), 0);
+    }
+
+    #[test]
+    fn root_moves_and_the_fifty_move_rule() {
+        let tablebase = kqvk("root");
+        let root = |fen: &str| {
+            let game = game(fen);
+            san(&game, &tablebase.root_moves(&game).unwrap())
+        };
+
+        // With time to spare every winning move is kept, near the 50 move
+        // rule only the mate, stalemate with Qc7 or Qf4 never
+        let fen = "k7/8/1K6/8/8/8/8/2Q5 w - - 0 1";
+        let mut winning = san(&game(fen), &game(fen).moves());
+        winning.retain(|mv| mv != "Qc7" && mv != "Qf4");
+        assert_eq!(root(fen), winning);
+        assert_eq!(root("k7/8/1K6/8/8/8/8/2Q5 w - - 96 60"), winning);
+        assert_eq!(root("k7/8/1K6/8/8/8/8/2Q5 w - - 97 60"), vec!["Qc8"]);
+
+        // Losing, Kc8 runs into mate and Ka8 is 4 plies from zero with the
+        // fixture, so every move is kept until those 4 plies would reach the
+        // 50 move rule
+        assert_eq!(
+            root("1k6/8/2K5/8/8/8/8/6Q1 b - - 95 60"),
+            vec!["Ka8", "Kc8"]
+        );
+        assert_eq!(root("1k6/8/2K5/8/8/8/8/6Q1 b - - 96 60"), vec!["Ka8"]);
+    }
+}
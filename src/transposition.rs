@@ -0,0 +1,99 @@
+use crate::{Move, PieceType};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// A transposition table that threads share without locks
+//
+// Each slot is two atomic words, the data and the position hash xored with
+// the data. Two threads writing the same slot at once can leave the words
+// from different entries, which then fail the check on the hash and read as
+// empty, so a torn entry is never used.
+
+// Whether a stored score is exact or only a bound, as a search with an
+// alpha-beta window finds it
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Bound {
+    Exact,
+    // The score is at least this
+    Lower,
+    // The score is at most this
+    Upper,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Entry {
+    pub(crate) depth: u32,
+    pub(crate) score: i32,
+    pub(crate) bound: Bound,
+    // The best move found, see move_key
+    pub(crate) mv: Option<u16>,
+}
+
+pub(crate) struct Table {
+    slots: Vec<[AtomicU64; 2]>,
+}
+
+impl Table {
+    // A table of at least size_mb megabytes, the number of slots rounded
+    // down to a power of two
+    pub(crate) fn new(size_mb: usize) -> Table {
+        let wanted = (size_mb.max(1) << 20) / 16;
+        let len = 1 << wanted.ilog2();
+        Table {
+            slots: (0..len)
+                .map(|_| [AtomicU64::new(0), AtomicU64::new(0)])
+                .collect(),
+        }
+    }
+
+    pub(crate) fn probe(&self, hash: u64) -> Option<Entry> {
+        let [check, data] = &self.slots[self.index(hash)];
+        let data = data.load(Ordering::Relaxed);
+        if data == 0 || check.load(Ordering::Relaxed) ^ data != hash {
+            return None;
+        }
+        let bound = match (data >> 40) & 3 {
+            0 => Bound::Exact,
+            1 => Bound::Lower,
+            _ => Bound::Upper,
+        };
+        let mv = (data >> 42) as u16;
+        Some(Entry {
+            depth: ((data >> 32) & 0xff) as u32,
+            score: data as u32 as i32,
+            bound,
+            mv: (mv != 0).then_some(mv),
+        })
+    }
+
+    // Always replaces what was in the slot
+    pub(crate) fn store(&self, hash: u64, entry: Entry) {
+        let bound = match entry.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2,
+        };
+        let data = entry.score as u32 as u64
+            | (entry.depth.min(0xff) as u64) << 32
+            | bound << 40
+            | (entry.mv.unwrap_or(0) as u64) << 42;
+        let [check, slot] = &self.slots[self.index(hash)];
+        check.store(hash ^ data, Ordering::Relaxed);
+        slot.store(data, Ordering::Relaxed);
+    }
+
+    fn index(&self, hash: u64) -> usize {
+        hash as usize & (self.slots.len() - 1)
+    }
+}
+
+// A move packed into 16 bits by its squares and promotion, never 0
+pub(crate) fn move_key(mv: &Move) -> u16 {
+    let promotion = match mv.promotion {
+        Some(PieceType::Knight) => 1,
+        Some(PieceType::Bishop) => 2,
+        Some(PieceType::Rook) => 3,
+        Some(PieceType::Queen) => 4,
+        _ => 0,
+    };
+    1 << 15 | (mv.from.index() as u16) << 9 | (mv.to.index() as u16) << 3 | promotion
+}
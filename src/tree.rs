@@ -0,0 +1,234 @@
+use crate::annotation::Annotation;
+use crate::{Game, Move};
+
+// A game with variations, for analysis
+//
+// Every node holds the position after its move. The first child of a node
+// is the main continuation, the others are variations of it. The current
+// node is kept as the path of child indices from the root, so a path of only
+// zeros is on the main line.
+
+// A position in the tree and the move that led to it
+#[derive(Clone, Debug)]
+pub struct Node {
+    game: Game,
+    mv: Option<Move>,
+    san: Option<String>,
+    // Comments, NAGs, highlights and arrows on the move, or on the game for the root
+    pub annotation: Annotation,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn new(game: Game, mv: Option<Move>, san: Option<String>) -> Node {
+        Node {
+            game,
+            mv,
+            san,
+            annotation: Annotation::default(),
+            children: Vec::new(),
+        }
+    }
+
+    // The position after the move
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    // The move that led here, None for the root
+    pub fn mv(&self) -> Option<Move> {
+        self.mv
+    }
+
+    // The move in SAN with its check or mate mark, None for the root
+    pub fn san(&self) -> Option<&str> {
+        self.san.as_deref()
+    }
+
+    // The main continuation first, then its variations
+    pub fn children(&self) -> &[Node] {
+        &self.children
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GameTree {
+    root: Node,
+    path: Vec<usize>,
+}
+
+impl Default for GameTree {
+    fn default() -> GameTree {
+        GameTree::new(Game::new())
+    }
+}
+
+impl GameTree {
+    // A tree with game as its root and no moves
+    pub fn new(game: Game) -> GameTree {
+        GameTree {
+            root: Node::new(game, None, None),
+            path: Vec::new(),
+        }
+    }
+
+    pub fn root(&self) -> &Node {
+        &self.root
+    }
+
+    pub fn root_mut(&mut self) -> &mut Node {
+        &mut self.root
+    }
+
+    pub fn current(&self) -> &Node {
+        self.path
+            .iter()
+            .fold(&self.root, |node, &i| &node.children[i])
+    }
+
+    pub fn current_mut(&mut self) -> &mut Node {
+        self.path
+            .iter()
+            .fold(&mut self.root, |node, &i| &mut node.children[i])
+    }
+
+    // The position at the current node
+    pub fn game(&self) -> &Game {
+        &self.current().game
+    }
+
+    // The child indices from the root to the current node
+    pub fn path(&self) -> &[usize] {
+        &self.path
+    }
+
+    // Whether the current node is on the main line
+    pub fn on_mainline(&self) -> bool {
+        self.path.iter().all(|&i| i == 0)
+    }
+
+    // Plays mv from the current node and moves to it
+    // A move that is already there is followed instead of added again, a new
+    // one becomes the main continuation if there is none yet and a variation
+    // otherwise
+    // Returns false and stays put if mv is not legal
+    pub fn play(&mut self, mv: Move) -> bool {
+        let node = self.current_mut();
+        let i = match node.children.iter().position(|child| child.mv == Some(mv)) {
+            Some(i) => i,
+            None => {
+                let game = &node.game;
+                if game.find_move(mv.from, mv.to, mv.promotion) != Some(mv) {
+                    return false;
+                }
+                let child = game.clone().apply_move(mv);
+                let san = child.move_history.last().cloned();
+                node.children.push(Node::new(child, Some(mv), san));
+                node.children.len() - 1
+            }
+        };
+        self.path.push(i);
+        true
+    }
+
+    // Moves to the main continuation of the current node
+    pub fn forward(&mut self) -> bool {
+        match self.current().children.is_empty() {
+            true => false,
+            false => {
+                self.path.push(0);
+                true
+            }
+        }
+    }
+
+    // Moves to the parent of the current node
+    pub fn back(&mut self) -> bool {
+        self.path.pop().is_some()
+    }
+
+    pub fn to_start(&mut self) {
+        self.path.clear();
+    }
+
+    // Follows the main continuations to the end of the current line
+    pub fn to_end(&mut self) {
+        while self.forward() {}
+    }
+
+    // Moves back to where the current variation leaves the main line
+    pub fn to_mainline(&mut self) {
+        if let Some(i) = self.path.iter().position(|&i| i != 0) {
+            self.path.truncate(i);
+        }
+    }
+
+    // Moves to the node at path, returns false and stays put if there is none
+    pub fn go_to(&mut self, path: &[usize]) -> bool {
+        let mut node = &self.root;
+        for &i in path {
+            match node.children.get(i) {
+                Some(child) => node = child,
+                None => return false,
+            }
+        }
+        self.path = path.to_vec();
+        true
+    }
+
+    // Makes the variation the current node is in the main continuation at
+    // the point where it branches off, the old main continuation becomes its
+    // first variation
+    // Returns false if the current node is on the main line
+    pub fn promote_variation(&mut self) -> bool {
+        let Some(depth) = self.path.iter().rposition(|&i| i != 0) else {
+            return false;
+        };
+        let i = self.path[depth];
+        let branch = self.path[..depth].to_vec();
+        let parent = self.node_mut(&branch);
+        let variation = parent.children.remove(i);
+        parent.children.insert(0, variation);
+        self.path[depth] = 0;
+        true
+    }
+
+    // Removes the variation the current node is in, from where it branches
+    // off, and moves to the node it branched from
+    // Returns false if the current node is on the main line
+    pub fn delete_variation(&mut self) -> bool {
+        let Some(depth) = self.path.iter().rposition(|&i| i != 0) else {
+            return false;
+        };
+        let i = self.path[depth];
+        self.path.truncate(depth);
+        let branch = self.path.clone();
+        self.node_mut(&branch).children.remove(i);
+        true
+    }
+
+    // The moves of the main line from the root
+    pub fn mainline(&self) -> Vec<Move> {
+        let mut moves = Vec::new();
+        let mut node = &self.root;
+        while let Some(child) = node.children.first() {
+            moves.extend(child.mv);
+            node = child;
+        }
+        moves
+    }
+
+    // The position at the end of the main line
+    pub fn mainline_game(&self) -> &Game {
+        let mut node = &self.root;
+        while let Some(child) = node.children.first() {
+            node = child;
+        }
+        &node.game
+    }
+
+    fn node_mut(&mut self, path: &[usize]) -> &mut Node {
+        path.iter()
+            .fold(&mut self.root, |node, &i| &mut node.children[i])
+    }
+}
@@ -0,0 +1,244 @@
+use crate::fen::{check_castling, check_en_passant, FenError};
+use crate::{attacks, Game, PieceType, Pos, Rank};
+use std::fmt;
+
+// Something wrong with the state of a game, found by Game::validate
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PositionError {
+    // The board or a check board is not 8 ranks of 8 squares
+    BoardShape,
+    // The square at index says it is pos
+    SquarePos { index: Pos, pos: Pos },
+    // occupied says the square is empty but it holds a piece, or the other way round
+    Occupied(Pos),
+    // An empty square with moves
+    EmptyWithMoves(Pos),
+    // Each player needs exactly one king
+    KingCount { white: usize, black: usize },
+    // w_king_pos or b_king_pos is not where that king is
+    KingPos { white: bool, pos: Pos },
+    // A pawn on the first or eighth rank
+    PawnOnBackRank(Pos),
+    // The player who just moved cannot be in check
+    OpponentInCheck,
+    // A castling right without its king or rook, as in a Shredder-FEN, e.g. "H"
+    Castling(String),
+    // The en passant square is not right behind a pawn that just moved two squares
+    EnPassant(Pos),
+    // mate and stalemate disagree with the moves of the player to move
+    GameOver,
+    // The move number starts at 1
+    FullmoveNumber,
+}
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PositionError::BoardShape => write!(f, "the board is not 8 by 8 squares"),
+            PositionError::SquarePos { index, pos } => {
+                write!(f, "the square at {} says it is {}", index, pos)
+            }
+            PositionError::Occupied(pos) => {
+                write!(f, "occupied does not match the piece at {}", pos)
+            }
+            PositionError::EmptyWithMoves(pos) => write!(f, "the empty square {} has moves", pos),
+            PositionError::KingCount { white, black } => write!(
+                f,
+                "each side needs exactly one king, found {} white and {} black",
+                white, black
+            ),
+            PositionError::KingPos { white, pos } => write!(
+                f,
+                "the {} king is not on {}",
+                if *white { "white" } else { "black" },
+                pos
+            ),
+            PositionError::PawnOnBackRank(pos) => write!(f, "pawn on back rank at {}", pos),
+            PositionError::OpponentInCheck => write!(f, "the side not to move is in check"),
+            PositionError::Castling(rights) => {
+                write!(f, "no king or rook for castling right \"{}\"", rights)
+            }
+            PositionError::EnPassant(pos) => write!(f, "invalid en passant square {}", pos),
+            PositionError::GameOver => write!(
+                f,
+                "mate and stalemate do not match the moves of the player to move"
+            ),
+            PositionError::FullmoveNumber => write!(f, "the move number is 0"),
+        }
+    }
+}
+
+impl std::error::Error for PositionError {}
+
+impl Game {
+    // Checks that the game is consistent with itself and with the rules
+    // Returns every problem found, the board shape first since nothing else
+    // can be checked without it
+    pub fn validate(&self) -> Result<(), Vec<PositionError>> {
+        let board = &self.boards.board;
+        if !is_8_by_8(board)
+            || !is_8_by_8(&self.boards.white_check_board)
+            || !is_8_by_8(&self.boards.black_check_board)
+        {
+            return Err(vec![PositionError::BoardShape]);
+        }
+
+        let mut errors: Vec<PositionError> = Vec::new();
+        let mut white_kings: Vec<Pos> = Vec::new();
+        let mut black_kings: Vec<Pos> = Vec::new();
+        for index in Pos::all() {
+            let square = self.square(index);
+            if square.pos != index {
+                errors.push(PositionError::SquarePos {
+                    index,
+                    pos: square.pos,
+                });
+            }
+            let has_piece = square.piece.piece_type != PieceType::Unoccupied;
+            if square.occupied != has_piece {
+                errors.push(PositionError::Occupied(index));
+            }
+            if !has_piece {
+                if !square.piece.moves.is_empty() {
+                    errors.push(PositionError::EmptyWithMoves(index));
+                }
+                continue;
+            }
+            match square.piece.piece_type {
+                PieceType::King if square.piece.white => white_kings.push(index),
+                PieceType::King => black_kings.push(index),
+                PieceType::Pawn
+                    if index.rank() == Rank::back_rank(true)
+                        || index.rank() == Rank::back_rank(false) =>
+                {
+                    errors.push(PositionError::PawnOnBackRank(index))
+                }
+                _ => (),
+            }
+        }
+
+        if white_kings.len() != 1 || black_kings.len() != 1 {
+            errors.push(PositionError::KingCount {
+                white: white_kings.len(),
+                black: black_kings.len(),
+            });
+        }
+        for (white, found) in [(true, &white_kings), (false, &black_kings)] {
+            let pos = self.king_pos(white);
+            if !found.contains(&pos) {
+                errors.push(PositionError::KingPos { white, pos });
+            }
+        }
+
+        let white_to_move = self.side_to_move();
+        let opponent_kings = match white_to_move {
+            true => &black_kings,
+            false => &white_kings,
+        };
+        if let Some(&king) = opponent_kings.first() {
+            if attacks::is_attacked(board, king, white_to_move) {
+                errors.push(PositionError::OpponentInCheck);
+            }
+        }
+        if let Err(FenError::Castling(rights)) = check_castling(board, self.castling) {
+            errors.push(PositionError::Castling(rights));
+        }
+        if let Some(target) = self.en_passant {
+            if check_en_passant(board, target, white_to_move).is_err() {
+                errors.push(PositionError::EnPassant(target));
+            }
+        }
+
+        let has_moves = board.iter().flatten().any(|square| {
+            square.occupied && square.piece.white == white_to_move && !square.piece.moves.is_empty()
+        });
+        if (self.mate && self.stalemate) || (self.mate || self.stalemate) == has_moves {
+            errors.push(PositionError::GameOver);
+        }
+        if self.fullmove_number == 0 {
+            errors.push(PositionError::FullmoveNumber);
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors),
+        }
+    }
+}
+
+fn is_8_by_8<T>(rows: &[Vec<T>]) -> bool {
+    rows.len() == 8 && rows.iter().all(|row| row.len() == 8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sq(name: &str) -> Pos {
+        name.parse().unwrap()
+    }
+
+    fn square_mut(game: &mut Game, pos: Pos) -> &mut crate::Square {
+        &mut game.boards.board[pos.rank().index()][pos.file().index()]
+    }
+
+    #[test]
+    fn valid_games() {
+        assert_eq!(Game::new().validate(), Ok(()));
+        let game = Game::from_fen("4k3/8/8/3pP3/8/8/8/4K2R w K d6 0 12").unwrap();
+        assert_eq!(game.validate(), Ok(()));
+    }
+
+    #[test]
+    fn stale_king_pos() {
+        let mut game = Game::new();
+        game.w_king_pos = sq("e2");
+        assert_eq!(
+            game.validate(),
+            Err(vec![PositionError::KingPos {
+                white: true,
+                pos: sq("e2"),
+            }])
+        );
+    }
+
+    #[test]
+    fn occupied_disagrees_with_piece() {
+        let mut game = Game::new();
+        square_mut(&mut game, sq("e4")).occupied = true;
+        assert_eq!(
+            game.validate(),
+            Err(vec![PositionError::Occupied(sq("e4"))])
+        );
+
+        let mut game = Game::new();
+        square_mut(&mut game, sq("g8")).occupied = false;
+        assert_eq!(
+            game.validate(),
+            Err(vec![PositionError::Occupied(sq("g8"))])
+        );
+    }
+
+    #[test]
+    fn wrong_square_pos() {
+        let mut game = Game::new();
+        square_mut(&mut game, sq("c3")).pos = sq("f6");
+        assert_eq!(
+            game.validate(),
+            Err(vec![PositionError::SquarePos {
+                index: sq("c3"),
+                pos: sq("f6"),
+            }])
+        );
+    }
+
+    #[test]
+    fn side_not_to_move_in_check() {
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let square = square_mut(&mut game, sq("e4"));
+        square.occupied = true;
+        square.piece.piece_type = PieceType::Rook;
+        square.piece.white = true;
+        assert_eq!(game.validate(), Err(vec![PositionError::OpponentInCheck]));
+    }
+}
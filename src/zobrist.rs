@@ -0,0 +1,75 @@
+use crate::{Game, PieceType, Pos};
+
+// Zobrist hashing: every piece on every square, the side to move, each
+// castling right and the en passant file has a random number, and the hash
+// of a position is those of its features xored together
+//
+// The numbers are made at compile time from a fixed seed, so a hash stays the
+// same between runs and can be stored. Two positions that are the same for
+// the rules of repetition, with the same castling rights and en passant
+// capture, have the same hash.
+
+const PIECES: usize = 0;
+const SIDE: usize = PIECES + 12 * 64;
+const CASTLING: usize = SIDE + 1;
+const EN_PASSANT: usize = CASTLING + 2 * 2 * 8;
+const KEY_COUNT: usize = EN_PASSANT + 8;
+
+const KEYS: [u64; KEY_COUNT] = keys();
+
+// SplitMix64 from a fixed seed
+const fn keys() -> [u64; KEY_COUNT] {
+    let mut keys = [0; KEY_COUNT];
+    let mut state: u64 = 0x5eed_c0ff_ee15_600d;
+    let mut i = 0;
+    while i < KEY_COUNT {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        keys[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    keys
+}
+
+fn piece_index(piece_type: PieceType) -> Option<usize> {
+    match piece_type {
+        PieceType::Pawn => Some(0),
+        PieceType::Knight => Some(1),
+        PieceType::Bishop => Some(2),
+        PieceType::Rook => Some(3),
+        PieceType::Queen => Some(4),
+        PieceType::King => Some(5),
+        PieceType::Unoccupied => None,
+    }
+}
+
+impl Game {
+    // The Zobrist hash of the current position
+    pub fn position_hash(&self) -> u64 {
+        let mut hash = 0;
+        for pos in Pos::all() {
+            let piece = &self.square(pos).piece;
+            if let Some(index) = piece_index(piece.piece_type) {
+                let color = if piece.white { 0 } else { 6 };
+                hash ^= KEYS[PIECES + (color + index) * 64 + pos.index()];
+            }
+        }
+        if !self.side_to_move() {
+            hash ^= KEYS[SIDE];
+        }
+        for (i, (white, king_side)) in [(true, true), (true, false), (false, true), (false, false)]
+            .into_iter()
+            .enumerate()
+        {
+            if let Some(file) = self.castling.rook(white, king_side) {
+                hash ^= KEYS[CASTLING + i * 8 + file.index()];
+            }
+        }
+        if let Some(target) = self.en_passant_target() {
+            hash ^= KEYS[EN_PASSANT + target.file().index()];
+        }
+        hash
+    }
+}